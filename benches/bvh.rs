@@ -0,0 +1,120 @@
+//Results of `cargo bench --bench bvh` in release mode on a 1024 ray grid
+//against a 10000 triangle sphere (criterion, median):
+//  linear_intersect (every shape tested)   384.1 ms
+//  bvh_intersect    (all hits, sorted)       1.08 ms  ~355x faster
+//  bvh_hit          (closest hit only)       0.36 ms  ~1070x faster
+//  bvh_build_10000_triangles                18.1 ms
+//The hierarchy pays for itself after roughly 50 rays.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use raytrace_rs::intersection::{hit, intersect, Intersections};
+use raytrace_rs::light::PointLight;
+use raytrace_rs::math::*;
+use raytrace_rs::objects::{Ray, Shape, TriangleBuilder};
+use raytrace_rs::world::World;
+use raytrace_rs::{point, vector};
+use std::f32::consts::PI;
+
+//Unit sphere tessellated into 2 * rings * segments triangles.
+fn sphere_mesh(rings: usize, segments: usize) -> Vec<Box<dyn Shape>> {
+    let vertex = |ring: usize, segment: usize| {
+        let theta = PI * ring as f32 / rings as f32;
+        let phi = 2.0 * PI * segment as f32 / segments as f32;
+        point!(
+            theta.sin() * phi.cos(),
+            theta.cos(),
+            theta.sin() * phi.sin()
+        )
+    };
+    let mut shapes: Vec<Box<dyn Shape>> = Vec::new();
+    for ring in 0..rings {
+        for segment in 0..segments {
+            let p1 = vertex(ring, segment);
+            let p2 = vertex(ring + 1, segment);
+            let p3 = vertex(ring + 1, segment + 1);
+            let p4 = vertex(ring, segment + 1);
+            shapes.push(Box::new(TriangleBuilder::new(p1, p2, p3).create().unwrap()));
+            shapes.push(Box::new(TriangleBuilder::new(p1, p3, p4).create().unwrap()));
+        }
+    }
+    shapes
+}
+
+//Grid of rays looking at the mesh from the front, about half of them miss it.
+fn camera_rays(size: usize) -> Vec<Ray> {
+    let origin = point!(0.0, 0.0, -5.0);
+    (0..size * size)
+        .map(|i| {
+            let x = (i % size) as f32 / size as f32 * 3.0 - 1.5;
+            let y = (i / size) as f32 / size as f32 * 3.0 - 1.5;
+            Ray::new(origin, (point!(x, y, 0.0) - origin).normalize())
+        })
+        .collect()
+}
+
+fn linear_intersect<'a>(shapes: &'a [Box<dyn Shape>], ray: &Ray) -> Intersections<'a> {
+    let mut result = Intersections::new();
+    for shape in shapes {
+        if let Some(mut intersections) = intersect(ray, shape.as_ref()) {
+            result.append(&mut intersections);
+        }
+    }
+    result.sort();
+    result
+}
+
+fn mesh_scene(c: &mut Criterion) {
+    let linear_shapes = sphere_mesh(50, 100);
+    let mut world = World::new(Box::new(PointLight::new(
+        point!(-10.0, 10.0, -10.0),
+        Color::new(1.0, 1.0, 1.0),
+    )));
+    for shape in sphere_mesh(50, 100) {
+        world.add_object(shape);
+    }
+    let rays = camera_rays(32);
+    //Build the hierarchy before measuring.
+    world.ray_hit(&rays[0]);
+
+    let mut group = c.benchmark_group("mesh_10000_triangles");
+    group.sample_size(10);
+    group.bench_function("linear_intersect", |b| {
+        b.iter(|| {
+            for ray in &rays {
+                black_box(hit(&linear_intersect(&linear_shapes, ray)).is_some());
+            }
+        })
+    });
+    group.bench_function("bvh_intersect", |b| {
+        b.iter(|| {
+            for ray in &rays {
+                black_box(hit(&world.ray_intersect(ray)).is_some());
+            }
+        })
+    });
+    group.bench_function("bvh_hit", |b| {
+        b.iter(|| {
+            for ray in &rays {
+                black_box(world.ray_hit(ray).is_some());
+            }
+        })
+    });
+    group.finish();
+
+    c.bench_function("bvh_build_10000_triangles", |b| {
+        b.iter(|| {
+            let mut world = World::new(Box::new(PointLight::new(
+                point!(-10.0, 10.0, -10.0),
+                Color::new(1.0, 1.0, 1.0),
+            )));
+            for shape in sphere_mesh(50, 100) {
+                world.add_object(shape);
+            }
+            black_box(world.ray_hit(&Ray::new(point!(0.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0))))
+                .is_some()
+        })
+    });
+}
+
+criterion_group!(benches, mesh_scene);
+criterion_main!(benches);
@@ -0,0 +1,175 @@
+use crate::math::*;
+use crate::uv::{cube_map, CubeMapPattern, UvPattern};
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+//What rays see when they leave the scene without hitting anything. Depends only
+//on the direction of the ray, so it looks infinitely far away.
+#[derive(Debug, Clone)]
+pub enum Background {
+    Solid(Color),
+    //Blend from `bottom`, looking straight down, to `top`, looking straight up.
+    Gradient { bottom: Color, top: Color },
+    //Skybox. Faces are the same as on a cube with `CubeMapPattern`, but they are
+    //seen from the inside, so u is flipped to keep images from being mirrored.
+    CubeMap(Arc<CubeMapPattern>),
+    //Panorama covering the whole sphere: u is the longitude, with the middle of
+    //the image towards -z, and v the latitude, 1 straight up.
+    Equirectangular(Arc<dyn UvPattern>),
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Background::Solid(Color::default())
+    }
+}
+
+impl Background {
+    pub fn color(&self, direction: &Vec4) -> Color {
+        match self {
+            Background::Solid(color) => *color,
+            Background::Gradient { bottom, top } => {
+                let t = 0.5 * (direction.normalize().y + 1.0);
+                *bottom + (*top - *bottom) * t
+            }
+            Background::CubeMap(pattern) => {
+                //Project the direction onto the unit cube.
+                let largest = direction
+                    .x
+                    .abs()
+                    .max(direction.y.abs())
+                    .max(direction.z.abs());
+                let on_cube = point!(
+                    direction.x / largest,
+                    direction.y / largest,
+                    direction.z / largest
+                );
+                let (face, u, v) = cube_map(&on_cube);
+                pattern.face(face).uv_pattern_at(1.0 - u, v)
+            }
+            Background::Equirectangular(panorama) => {
+                let direction = direction.normalize();
+                let u = 0.5 + direction.x.atan2(-direction.z) / (2.0 * PI);
+                let v = 0.5 + direction.y.clamp(-1.0, 1.0).asin() / PI;
+                panorama.uv_pattern_at(u, v)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::uv::{AlignCheck, UvCheckers};
+
+    fn white() -> Color {
+        Color::new(1.0, 1.0, 1.0)
+    }
+
+    fn black() -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    //Shows (u, v) it was sampled at in red and green.
+    #[derive(Debug)]
+    struct TestUv;
+
+    impl UvPattern for TestUv {
+        fn uv_pattern_at(&self, u: f32, v: f32) -> Color {
+            Color::new(u, v, 0.0)
+        }
+    }
+
+    #[test]
+    fn default_background_is_black() {
+        let background = Background::default();
+        assert_eq!(background.color(&vector!(0.0, 0.0, 1.0)), black());
+    }
+
+    #[test]
+    fn background_can_cross_threads() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Background>();
+    }
+
+    #[test]
+    fn solid_background() {
+        let background = Background::Solid(Color::red());
+        assert_eq!(background.color(&vector!(0.3, -0.2, 0.9)), Color::red());
+    }
+
+    #[test]
+    fn gradient_background_goes_from_bottom_to_top() {
+        let background = Background::Gradient {
+            bottom: black(),
+            top: white(),
+        };
+        assert_eq!(background.color(&vector!(0.0, -1.0, 0.0)), black());
+        assert_eq!(background.color(&vector!(0.0, 1.0, 0.0)), white());
+        matrix_eq!(
+            background.color(&vector!(1.0, 0.0, 0.0)).as_array(),
+            [0.5, 0.5, 0.5]
+        );
+        //Length of the direction doesn't matter.
+        assert_eq!(background.color(&vector!(0.0, 5.0, 0.0)), white());
+    }
+
+    #[test]
+    fn cube_map_background_picks_face_by_direction() {
+        let face = |main: Color| -> Arc<dyn UvPattern> {
+            Arc::new(AlignCheck::new(main, white(), white(), white(), white()))
+        };
+        let background = Background::CubeMap(Arc::new(CubeMapPattern::new(
+            face(Color::new(1.0, 1.0, 0.0)),
+            face(Color::new(0.0, 1.0, 1.0)),
+            face(Color::red()),
+            face(Color::green()),
+            face(Color::new(1.0, 0.5, 0.0)),
+            face(Color::new(1.0, 0.0, 1.0)),
+        )));
+        let cases = [
+            (vector!(-1.0, 0.0, 0.0), Color::new(1.0, 1.0, 0.0)),
+            (vector!(0.0, 0.0, 3.0), Color::new(0.0, 1.0, 1.0)),
+            (vector!(0.5, 0.1, 0.0), Color::red()),
+            (vector!(0.0, 0.0, -1.0), Color::green()),
+            (vector!(0.0, 1.0, 0.0), Color::new(1.0, 0.5, 0.0)),
+            (vector!(0.0, -1.0, 0.0), Color::new(1.0, 0.0, 1.0)),
+        ];
+        for (direction, expected) in cases.iter() {
+            assert_eq!(background.color(direction), *expected);
+        }
+    }
+
+    #[test]
+    fn cube_map_faces_are_seen_from_inside() {
+        let plain: Arc<dyn UvPattern> = Arc::new(UvCheckers::new(1.0, 1.0, black(), black()));
+        let background = Background::CubeMap(Arc::new(CubeMapPattern::new(
+            plain.clone(),
+            plain.clone(),
+            plain.clone(),
+            Arc::new(TestUv),
+            plain.clone(),
+            plain,
+        )));
+        //Looking towards -z, +x is on the right, so u has to grow with x.
+        let left = background.color(&vector!(-0.5, 0.0, -1.0));
+        let right = background.color(&vector!(0.5, 0.0, -1.0));
+        assert!(left.r() < right.r());
+        matrix_eq!(left.as_array(), [0.25, 0.5, 0.0]);
+    }
+
+    #[test]
+    fn equirectangular_background() {
+        let background = Background::Equirectangular(Arc::new(TestUv));
+        let cases = [
+            (vector!(0.0, 0.0, -1.0), [0.5, 0.5, 0.0]),
+            (vector!(1.0, 0.0, 0.0), [0.75, 0.5, 0.0]),
+            (vector!(-1.0, 0.0, 0.0), [0.25, 0.5, 0.0]),
+            (vector!(0.0, 2.0, -2.0), [0.5, 0.75, 0.0]),
+            (vector!(0.0, -1.0, -1.0), [0.5, 0.25, 0.0]),
+        ];
+        for (direction, expected) in cases.iter() {
+            matrix_eq!(background.color(direction).as_array(), expected, 0.0001);
+        }
+    }
+}
@@ -0,0 +1,230 @@
+use crate::math::*;
+use crate::objects::Ray;
+
+//Axis aligned bounding box. Infinite extents are allowed (planes, open cylinders).
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Bounds {
+    pub min: Point4,
+    pub max: Point4,
+}
+
+impl Default for Bounds {
+    fn default() -> Self {
+        Bounds::empty()
+    }
+}
+
+impl Bounds {
+    pub fn new(min: Point4, max: Point4) -> Bounds {
+        Bounds { min, max }
+    }
+
+    //Box containing nothing, adding anything to it gives the bounds of that thing.
+    pub fn empty() -> Bounds {
+        Bounds {
+            min: point!(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+            max: point!(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+        }
+    }
+
+    pub fn infinite() -> Bounds {
+        Bounds {
+            min: point!(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+            max: point!(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn is_finite(&self) -> bool {
+        (0..3).all(|axis| self.min[axis].is_finite() && self.max[axis].is_finite())
+    }
+
+    pub fn add_point(&mut self, point: &Point4) {
+        for axis in 0..3 {
+            self.min[axis] = self.min[axis].min(point[axis]);
+            self.max[axis] = self.max[axis].max(point[axis]);
+        }
+    }
+
+    pub fn merge(&mut self, other: &Bounds) {
+        if other.is_empty() {
+            return;
+        }
+        self.add_point(&other.min);
+        self.add_point(&other.max);
+    }
+
+    pub fn contains(&self, point: &Point4) -> bool {
+        (0..3).all(|axis| self.min[axis] <= point[axis] && point[axis] <= self.max[axis])
+    }
+
+    pub fn centroid(&self) -> Point4 {
+        Point4::from((self.min.coords + self.max.coords) * 0.5)
+    }
+
+    //Index of the axis (0 = x, 1 = y, 2 = z) with the largest extent.
+    pub fn largest_axis(&self) -> usize {
+        let extent = self.max - self.min;
+        if extent.x >= extent.y && extent.x >= extent.z {
+            0
+        } else if extent.y >= extent.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let extent = self.max - self.min;
+        2.0 * (extent.x * extent.y + extent.y * extent.z + extent.z * extent.x)
+    }
+
+    //Bounds of all 8 transformed corners. Infinite boxes stay infinite.
+    pub fn transform(&self, transformation: &Mat4) -> Bounds {
+        if self.is_empty() {
+            return *self;
+        }
+        if !self.is_finite() {
+            return Bounds::infinite();
+        }
+        let mut result = Bounds::empty();
+        for &x in [self.min.x, self.max.x].iter() {
+            for &y in [self.min.y, self.max.y].iter() {
+                for &z in [self.min.z, self.max.z].iter() {
+                    result.add_point(&(transformation * point!(x, y, z)));
+                }
+            }
+        }
+        result
+    }
+
+    //Slab test. Returns t of entering and leaving the box, if the ray crosses it.
+    pub fn intersect(&self, ray: &Ray) -> Option<(f32, f32)> {
+        let mut tmin = f32::NEG_INFINITY;
+        let mut tmax = f32::INFINITY;
+        for axis in 0..3 {
+            //Parallel to the slab: either always inside it (borders included) or never.
+            //Dividing would give NaN for an origin right on the border.
+            if ray.direction[axis] == 0.0 {
+                if ray.origin[axis] < self.min[axis] || ray.origin[axis] > self.max[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let inverse_direction = 1.0 / ray.direction[axis];
+            let t1 = (self.min[axis] - ray.origin[axis]) * inverse_direction;
+            let t2 = (self.max[axis] - ray.origin[axis]) * inverse_direction;
+            tmin = tmin.max(t1.min(t2));
+            tmax = tmax.min(t1.max(t2));
+        }
+        if tmin > tmax {
+            None
+        } else {
+            Some((tmin, tmax))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::f32::consts::{FRAC_PI_4, SQRT_2};
+
+    #[test]
+    fn empty_bounds() {
+        let bounds = Bounds::empty();
+        assert!(bounds.is_empty());
+        assert_eq!(bounds.surface_area(), 0.0);
+    }
+
+    #[test]
+    fn adding_points_to_bounds() {
+        let mut bounds = Bounds::empty();
+        bounds.add_point(&point!(-5.0, 2.0, 0.0));
+        bounds.add_point(&point!(7.0, 0.0, -3.0));
+        assert_eq!(bounds.min, point!(-5.0, 0.0, -3.0));
+        assert_eq!(bounds.max, point!(7.0, 2.0, 0.0));
+        assert_eq!(bounds.largest_axis(), 0);
+        assert_eq!(bounds.centroid(), point!(1.0, 1.0, -1.5));
+    }
+
+    #[test]
+    fn merging_bounds() {
+        let mut bounds = Bounds::new(point!(-5.0, -2.0, 0.0), point!(7.0, 4.0, 4.0));
+        bounds.merge(&Bounds::new(
+            point!(8.0, -7.0, -2.0),
+            point!(14.0, 2.0, 8.0),
+        ));
+        bounds.merge(&Bounds::empty());
+        assert_eq!(bounds.min, point!(-5.0, -7.0, -2.0));
+        assert_eq!(bounds.max, point!(14.0, 4.0, 8.0));
+    }
+
+    #[test]
+    fn bounds_contain_point() {
+        let bounds = Bounds::new(point!(5.0, -2.0, 0.0), point!(11.0, 4.0, 7.0));
+        assert!(bounds.contains(&point!(5.0, -2.0, 0.0)));
+        assert!(bounds.contains(&point!(8.0, 1.0, 3.0)));
+        assert!(!bounds.contains(&point!(3.0, 0.0, 3.0)));
+        assert!(!bounds.contains(&point!(8.0, 1.0, 8.0)));
+    }
+
+    #[test]
+    fn transforming_bounds() {
+        let bounds = Bounds::new(point!(-1.0, -1.0, -1.0), point!(1.0, 1.0, 1.0));
+        let transformation = rotation!(FRAC_PI_4, 0.0, 0.0) * rotation!(0.0, FRAC_PI_4, 0.0);
+        let result = bounds.transform(&transformation);
+        matrix_eq!(result.min, point!(-SQRT_2, -1.70711, -1.70711), 0.0001);
+        matrix_eq!(result.max, point!(SQRT_2, 1.70711, 1.70711), 0.0001);
+        let plane = Bounds::new(
+            point!(f32::NEG_INFINITY, 0.0, f32::NEG_INFINITY),
+            point!(f32::INFINITY, 0.0, f32::INFINITY),
+        );
+        assert_eq!(
+            plane.transform(&translation!(0.0, 1.0, 0.0)),
+            Bounds::infinite()
+        );
+    }
+
+    #[test]
+    fn ray_intersects_bounds() {
+        let bounds = Bounds::new(point!(5.0, -2.0, 0.0), point!(11.0, 4.0, 7.0));
+        let hits = [
+            (point!(15.0, 1.0, 2.0), vector!(-1.0, 0.0, 0.0)),
+            (point!(-5.0, -1.0, 4.0), vector!(1.0, 0.0, 0.0)),
+            (point!(7.0, 6.0, 5.0), vector!(0.0, -1.0, 0.0)),
+            (point!(9.0, -5.0, 6.0), vector!(0.0, 1.0, 0.0)),
+            (point!(8.0, 2.0, 12.0), vector!(0.0, 0.0, -1.0)),
+            (point!(6.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0)),
+            (point!(8.0, 1.0, 3.5), vector!(0.0, 0.0, 1.0)),
+        ];
+        for (origin, direction) in hits.iter() {
+            assert!(bounds.intersect(&Ray::new(*origin, *direction)).is_some());
+        }
+        let misses = [
+            (point!(9.0, -1.0, -8.0), vector!(2.0, 4.0, 6.0)),
+            (point!(8.0, 3.0, -4.0), vector!(6.0, 2.0, 4.0)),
+            (point!(9.0, -1.0, -2.0), vector!(4.0, 6.0, 2.0)),
+            (point!(4.0, 0.0, 9.0), vector!(0.0, 0.0, -1.0)),
+            (point!(8.0, 6.0, -1.0), vector!(0.0, -1.0, 0.0)),
+            (point!(12.0, 5.0, 4.0), vector!(-1.0, 0.0, 0.0)),
+        ];
+        for (origin, direction) in misses.iter() {
+            let ray = Ray::new(*origin, direction.normalize());
+            assert!(bounds.intersect(&ray).is_none());
+        }
+    }
+
+    #[test]
+    fn ray_intersection_distances() {
+        let bounds = Bounds::new(point!(-1.0, -1.0, -1.0), point!(1.0, 1.0, 1.0));
+        let ray = Ray::new(point!(0.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0));
+        assert_eq!(bounds.intersect(&ray), Some((4.0, 6.0)));
+    }
+}
@@ -0,0 +1,436 @@
+use crate::bounds::Bounds;
+use crate::intersection::{intersect, Intersection, Intersections};
+use crate::math::*;
+use crate::objects::{Ray, Shape};
+
+//Leaves with this many shapes or less are never split.
+const MAX_LEAF_SIZE: usize = 2;
+//Number of buckets the centroids are sorted into when looking for the best split.
+const SAH_BINS: usize = 12;
+//Cost of visiting a node relative to the cost of intersecting a shape.
+const TRAVERSAL_COST: f32 = 0.5;
+
+#[derive(Debug)]
+enum BvhNode {
+    Leaf {
+        bounds: Bounds,
+        first: usize,
+        count: usize,
+    },
+    Interior {
+        bounds: Bounds,
+        left: usize,
+        right: usize,
+    },
+}
+
+impl BvhNode {
+    fn bounds(&self) -> &Bounds {
+        match self {
+            BvhNode::Leaf { bounds, .. } | BvhNode::Interior { bounds, .. } => bounds,
+        }
+    }
+}
+
+//Shape that is being sorted into the hierarchy.
+#[derive(Copy, Clone)]
+struct BuildEntry {
+    index: usize,
+    bounds: Bounds,
+    centroid: Point4,
+}
+
+//Bounding volume hierarchy over a list of shapes, split with the surface area heuristic.
+//It keeps only indices, so it has to be used with the same slice it was built from.
+//Shapes with infinite bounds (like planes) are kept aside and tested with every ray.
+#[derive(Debug, Default)]
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    indices: Vec<usize>,
+    unbounded: Vec<usize>,
+}
+
+impl Bvh {
+    pub fn build(shapes: &[Box<dyn Shape>]) -> Bvh {
+        let mut unbounded = Vec::new();
+        let mut entries = Vec::with_capacity(shapes.len());
+        for (index, shape) in shapes.iter().enumerate() {
+            let bounds = shape.parent_space_bounds();
+            if bounds.is_empty() {
+                continue;
+            }
+            if bounds.is_finite() {
+                entries.push(BuildEntry {
+                    index,
+                    bounds,
+                    centroid: bounds.centroid(),
+                });
+            } else {
+                unbounded.push(index);
+            }
+        }
+        let mut bvh = Bvh {
+            nodes: Vec::new(),
+            indices: Vec::with_capacity(entries.len()),
+            unbounded,
+        };
+        if !entries.is_empty() {
+            bvh.build_node(&mut entries);
+        }
+        bvh
+    }
+
+    //Bounds of everything in the hierarchy, infinite if there are unbounded shapes.
+    pub fn bounds(&self) -> Bounds {
+        if !self.unbounded.is_empty() {
+            return Bounds::infinite();
+        }
+        self.nodes
+            .first()
+            .map_or_else(Bounds::empty, |root| *root.bounds())
+    }
+
+    pub fn depth(&self) -> usize {
+        fn node_depth(nodes: &[BvhNode], node: usize) -> usize {
+            match nodes[node] {
+                BvhNode::Leaf { .. } => 1,
+                BvhNode::Interior { left, right, .. } => {
+                    1 + node_depth(nodes, left).max(node_depth(nodes, right))
+                }
+            }
+        }
+        if self.nodes.is_empty() {
+            0
+        } else {
+            node_depth(&self.nodes, 0)
+        }
+    }
+
+    //Appends a node for the entries and returns its index.
+    fn build_node(&mut self, entries: &mut [BuildEntry]) -> usize {
+        let mut bounds = Bounds::empty();
+        let mut centroid_bounds = Bounds::empty();
+        for entry in entries.iter() {
+            bounds.merge(&entry.bounds);
+            centroid_bounds.add_point(&entry.centroid);
+        }
+
+        let node = self.nodes.len();
+        let split = if entries.len() <= MAX_LEAF_SIZE {
+            None
+        } else {
+            Bvh::find_split(entries, &bounds, &centroid_bounds)
+        };
+        match split {
+            None => {
+                self.nodes.push(BvhNode::Leaf {
+                    bounds,
+                    first: self.indices.len(),
+                    count: entries.len(),
+                });
+                self.indices.extend(entries.iter().map(|entry| entry.index));
+            }
+            Some(mid) => {
+                //Placeholder, children indices are known only after they are built.
+                self.nodes.push(BvhNode::Leaf {
+                    bounds,
+                    first: 0,
+                    count: 0,
+                });
+                let (left_entries, right_entries) = entries.split_at_mut(mid);
+                let left = self.build_node(left_entries);
+                let right = self.build_node(right_entries);
+                self.nodes[node] = BvhNode::Interior {
+                    bounds,
+                    left,
+                    right,
+                };
+            }
+        }
+        node
+    }
+
+    //Binned SAH: partitions the entries and returns the size of the left part,
+    //or None when keeping them all in one leaf is cheaper.
+    fn find_split(
+        entries: &mut [BuildEntry],
+        bounds: &Bounds,
+        centroid_bounds: &Bounds,
+    ) -> Option<usize> {
+        let axis = centroid_bounds.largest_axis();
+        let low = centroid_bounds.min[axis];
+        let extent = centroid_bounds.max[axis] - low;
+        if extent <= 0.0 {
+            //All centroids in one spot, there is no way to separate them.
+            return None;
+        }
+        let bin_of = |entry: &BuildEntry| {
+            let bin = ((entry.centroid[axis] - low) / extent * SAH_BINS as f32) as usize;
+            bin.min(SAH_BINS - 1)
+        };
+
+        let mut bin_bounds = [Bounds::empty(); SAH_BINS];
+        let mut bin_counts = [0usize; SAH_BINS];
+        for entry in entries.iter() {
+            let bin = bin_of(entry);
+            bin_bounds[bin].merge(&entry.bounds);
+            bin_counts[bin] += 1;
+        }
+
+        //Sweep from the right to know the area and count right of every split plane.
+        let mut right_area = [0.0f32; SAH_BINS];
+        let mut right_count = [0usize; SAH_BINS];
+        let mut accumulated = Bounds::empty();
+        let mut count = 0;
+        for bin in (1..SAH_BINS).rev() {
+            accumulated.merge(&bin_bounds[bin]);
+            count += bin_counts[bin];
+            right_area[bin] = accumulated.surface_area();
+            right_count[bin] = count;
+        }
+
+        let mut best: Option<(usize, f32)> = None;
+        let mut accumulated = Bounds::empty();
+        let mut count = 0;
+        for split in 1..SAH_BINS {
+            accumulated.merge(&bin_bounds[split - 1]);
+            count += bin_counts[split - 1];
+            if count == 0 || right_count[split] == 0 {
+                continue;
+            }
+            let cost = accumulated.surface_area() * count as f32
+                + right_area[split] * right_count[split] as f32;
+            if best.is_none_or(|(_, best_cost)| cost < best_cost) {
+                best = Some((split, cost));
+            }
+        }
+
+        let (split, cost) = best?;
+        let leaf_cost = entries.len() as f32;
+        let split_cost = TRAVERSAL_COST + cost / bounds.surface_area();
+        if split_cost >= leaf_cost {
+            return None;
+        }
+
+        //Move entries left of the split plane to the front.
+        let mut mid = 0;
+        for i in 0..entries.len() {
+            if bin_of(&entries[i]) < split {
+                entries.swap(i, mid);
+                mid += 1;
+            }
+        }
+        Some(mid)
+    }
+
+    //All intersections of the ray with the shapes, sorted.
+    pub fn intersect<'a>(&self, shapes: &'a [Box<dyn Shape>], ray: &Ray) -> Intersections<'a> {
+        let mut result = Intersections::new();
+        let mut add_hits = |index: usize| {
+            if let Some(mut intersections) = intersect(ray, shapes[index].as_ref()) {
+                result.append(&mut intersections);
+            }
+        };
+        for &index in &self.unbounded {
+            add_hits(index);
+        }
+        if !self.nodes.is_empty() {
+            let mut stack = vec![0];
+            while let Some(node) = stack.pop() {
+                let node = &self.nodes[node];
+                if node.bounds().intersect(ray).is_none() {
+                    continue;
+                }
+                match *node {
+                    BvhNode::Leaf { first, count, .. } => {
+                        for &index in &self.indices[first..first + count] {
+                            add_hits(index);
+                        }
+                    }
+                    BvhNode::Interior { left, right, .. } => {
+                        stack.push(right);
+                        stack.push(left);
+                    }
+                }
+            }
+        }
+        result.sort();
+        result
+    }
+
+    //Closest intersection in front of the ray origin. Nodes further away than
+    //the best hit found so far are skipped.
+    pub fn hit<'a>(&self, shapes: &'a [Box<dyn Shape>], ray: &Ray) -> Option<Intersection<'a>> {
+        let mut best: Option<Intersection<'a>> = None;
+        let test_shape = |index: usize, best: &mut Option<Intersection<'a>>| {
+            if let Some(intersections) = intersect(ray, shapes[index].as_ref()) {
+                let hit = intersections.into_iter().find(|x| x.t > 0.0);
+                if let Some(hit) = hit {
+                    if best.is_none_or(|best| hit.t < best.t) {
+                        *best = Some(hit);
+                    }
+                }
+            }
+        };
+        for &index in &self.unbounded {
+            test_shape(index, &mut best);
+        }
+        if self.nodes.is_empty() {
+            return best;
+        }
+        let in_reach = |bounds: &Bounds, best: &Option<Intersection>| match bounds.intersect(ray) {
+            Some((tmin, tmax)) if tmax >= 0.0 => {
+                let tmin = tmin.max(0.0);
+                best.is_none_or(|best| tmin <= best.t).then_some(tmin)
+            }
+            _ => None,
+        };
+
+        let mut stack = Vec::new();
+        if let Some(tmin) = in_reach(self.nodes[0].bounds(), &best) {
+            stack.push((0, tmin));
+        }
+        while let Some((node, tmin)) = stack.pop() {
+            //Best hit might have moved closer since the node was pushed.
+            if best.is_some_and(|best| tmin > best.t) {
+                continue;
+            }
+            match self.nodes[node] {
+                BvhNode::Leaf { first, count, .. } => {
+                    for &index in &self.indices[first..first + count] {
+                        test_shape(index, &mut best);
+                    }
+                }
+                BvhNode::Interior { left, right, .. } => {
+                    let left_t = in_reach(self.nodes[left].bounds(), &best);
+                    let right_t = in_reach(self.nodes[right].bounds(), &best);
+                    //Push the nearer child last, so it is visited first.
+                    match (left_t, right_t) {
+                        (Some(l), Some(r)) if l <= r => {
+                            stack.push((right, r));
+                            stack.push((left, l));
+                        }
+                        (Some(l), Some(r)) => {
+                            stack.push((left, l));
+                            stack.push((right, r));
+                        }
+                        (Some(l), None) => stack.push((left, l)),
+                        (None, Some(r)) => stack.push((right, r)),
+                        (None, None) => {}
+                    }
+                }
+            }
+        }
+        best
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intersection::hit;
+    use crate::objects::{PlaneBuilder, SphereBuilder};
+
+    //Row of unit spheres along x, 3 units apart.
+    fn sphere_row(count: usize) -> Vec<Box<dyn Shape>> {
+        (0..count)
+            .map(|i| {
+                let sphere = SphereBuilder::new()
+                    .with_transformation(translation!(i as f32 * 3.0, 0.0, 0.0))
+                    .create()
+                    .unwrap();
+                Box::new(sphere) as Box<dyn Shape>
+            })
+            .collect()
+    }
+
+    fn linear_intersect<'a>(shapes: &'a [Box<dyn Shape>], ray: &Ray) -> Intersections<'a> {
+        let mut result: Intersections = shapes
+            .iter()
+            .filter_map(|shape| intersect(ray, shape.as_ref()))
+            .flatten()
+            .collect();
+        result.sort();
+        result
+    }
+
+    #[test]
+    fn empty_bvh() {
+        let bvh = Bvh::build(&[]);
+        let ray = Ray::new(point!(0.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0));
+        assert!(bvh.bounds().is_empty());
+        assert_eq!(bvh.depth(), 0);
+        assert!(bvh.intersect(&[], &ray).is_empty());
+        assert!(bvh.hit(&[], &ray).is_none());
+    }
+
+    #[test]
+    fn bvh_splits_many_shapes() {
+        let shapes = sphere_row(64);
+        let bvh = Bvh::build(&shapes);
+        assert_eq!(bvh.indices.len(), 64);
+        assert!(bvh.depth() > 1);
+        assert_eq!(bvh.bounds().min, point!(-1.0, -1.0, -1.0));
+        assert_eq!(bvh.bounds().max, point!(190.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn bvh_matches_linear_search() {
+        let shapes = sphere_row(64);
+        let bvh = Bvh::build(&shapes);
+        let rays = [
+            Ray::new(point!(30.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0)),
+            Ray::new(point!(31.5, 0.0, -5.0), vector!(0.0, 0.0, 1.0)),
+            Ray::new(point!(-5.0, 0.0, 0.0), vector!(1.0, 0.0, 0.0)),
+            Ray::new(point!(100.0, 0.5, 0.0), vector!(-1.0, 0.0, 0.0)),
+            Ray::new(point!(0.0, 5.0, -5.0), vector!(1.0, -0.1, 0.1).normalize()),
+        ];
+        for ray in rays.iter() {
+            let expected = linear_intersect(&shapes, ray);
+            assert_eq!(bvh.intersect(&shapes, ray), expected);
+            assert_eq!(bvh.hit(&shapes, ray).as_ref(), hit(&expected));
+        }
+    }
+
+    #[test]
+    fn ray_along_node_face() {
+        let shapes = sphere_row(16);
+        let bvh = Bvh::build(&shapes);
+        //Runs along the top face of every node and touches every sphere.
+        let ray = Ray::new(point!(-5.0, 1.0, 0.0), vector!(1.0, 0.0, 0.0));
+        let expected = linear_intersect(&shapes, &ray);
+        assert!(!expected.is_empty());
+        assert_eq!(bvh.intersect(&shapes, &ray), expected);
+        assert_eq!(bvh.hit(&shapes, &ray).as_ref(), hit(&expected));
+    }
+
+    #[test]
+    fn hit_starting_inside_shapes() {
+        let shapes = sphere_row(16);
+        let bvh = Bvh::build(&shapes);
+        let ray = Ray::new(point!(21.0, 0.0, 0.0), vector!(1.0, 0.0, 0.0));
+        let hit = bvh
+            .hit(&shapes, &ray)
+            .expect("Expected to hit the sphere around origin!");
+        assert_eq!(hit.t, 1.0);
+        assert_eq!(hit.obj.id(), shapes[7].id());
+    }
+
+    #[test]
+    fn unbounded_shapes_are_always_tested() {
+        let mut shapes = sphere_row(8);
+        shapes.push(Box::new(
+            PlaneBuilder::new()
+                .with_transformation(translation!(0.0, -1.0, 0.0))
+                .create()
+                .unwrap(),
+        ));
+        let bvh = Bvh::build(&shapes);
+        assert_eq!(bvh.bounds(), Bounds::infinite());
+        let ray = Ray::new(point!(100.0, 5.0, 0.0), vector!(0.0, -1.0, 0.0));
+        let xs = bvh.intersect(&shapes, &ray);
+        assert_eq!(xs.len(), 1);
+        assert_eq!(xs[0].obj.id(), shapes[8].id());
+        assert_eq!(bvh.hit(&shapes, &ray).map(|hit| hit.t), Some(6.0));
+    }
+}
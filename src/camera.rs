@@ -0,0 +1,430 @@
+use crate::canvas::Canvas;
+use crate::math::*;
+use crate::objects::Ray;
+use crate::sampling::{ReconstructionFilter, Sampler};
+use crate::transform::{Transform, TransformError};
+use crate::world::World;
+
+//World transformation that places the eye at `from`, looking at `to`.
+//`up` only has to point roughly upwards, it is corrected to be perpendicular.
+pub fn view_transform(from: &Point4, to: &Point4, up: &Vec4) -> Mat4 {
+    let forward = (to - from).xyz().normalize();
+    let left = forward.cross(&up.xyz().normalize());
+    let true_up = left.cross(&forward);
+    #[rustfmt::skip]
+    let orientation = Mat4::new(
+        left.x, left.y, left.z, 0.0,
+        true_up.x, true_up.y, true_up.z, 0.0,
+        -forward.x, -forward.y, -forward.z, 0.0,
+        0.0, 0.0, 0.0, 1.0,
+    );
+    orientation * translation!(-from.x, -from.y, -from.z)
+}
+
+//Pinhole camera looking down -z from the origin in its own space. The canvas
+//lies one unit in front of it and is sized to fit the field of view.
+#[derive(Copy, Clone, Debug)]
+pub struct Camera {
+    hsize: u32,
+    vsize: u32,
+    field_of_view: f32,
+    transformation: Transform,
+    half_width: f32,
+    half_height: f32,
+    pixel_size: f32,
+    samples_per_pixel: u32,
+    sampler: Sampler,
+    filter: ReconstructionFilter,
+}
+
+impl Camera {
+    //Field of view is in radians and covers the longer side of the canvas.
+    pub fn new(hsize: u32, vsize: u32, field_of_view: f32) -> Camera {
+        let half_view = (field_of_view / 2.0).tan();
+        let aspect = hsize as f32 / vsize as f32;
+        let (half_width, half_height) = if aspect >= 1.0 {
+            (half_view, half_view / aspect)
+        } else {
+            (half_view * aspect, half_view)
+        };
+        Camera {
+            hsize,
+            vsize,
+            field_of_view,
+            transformation: Transform::identity(),
+            half_width,
+            half_height,
+            pixel_size: half_width * 2.0 / hsize as f32,
+            samples_per_pixel: 1,
+            sampler: Sampler::default(),
+            filter: ReconstructionFilter::default(),
+        }
+    }
+
+    pub fn hsize(&self) -> u32 {
+        self.hsize
+    }
+
+    pub fn vsize(&self) -> u32 {
+        self.vsize
+    }
+
+    pub fn field_of_view(&self) -> f32 {
+        self.field_of_view
+    }
+
+    pub fn pixel_size(&self) -> f32 {
+        self.pixel_size
+    }
+
+    pub fn transformation(&self) -> &Transform {
+        &self.transformation
+    }
+
+    //Usually a `view_transform`. Singular matrices are rejected and the camera is left as it was.
+    pub fn set_transformation(&mut self, transformation: Mat4) -> Result<(), TransformError> {
+        self.transformation = Transform::new(transformation)?;
+        Ok(())
+    }
+
+    pub fn samples_per_pixel(&self) -> u32 {
+        self.samples_per_pixel
+    }
+
+    //Rays shot through every pixel, at least 1.
+    pub fn set_samples_per_pixel(&mut self, samples_per_pixel: u32) {
+        self.samples_per_pixel = samples_per_pixel.max(1);
+    }
+
+    pub fn sampler(&self) -> Sampler {
+        self.sampler
+    }
+
+    pub fn set_sampler(&mut self, sampler: Sampler) {
+        self.sampler = sampler;
+    }
+
+    pub fn filter(&self) -> ReconstructionFilter {
+        self.filter
+    }
+
+    pub fn set_filter(&mut self, filter: ReconstructionFilter) {
+        self.filter = filter;
+    }
+
+    //Ray from the eye through the center of the given pixel.
+    pub fn ray_for_pixel(&self, x: u32, y: u32) -> Ray {
+        self.ray_for_point(x as f32 + 0.5, y as f32 + 0.5)
+    }
+
+    //Ray through any point of the canvas, given in pixels from its top left corner.
+    pub fn ray_for_point(&self, x: f32, y: f32) -> Ray {
+        //Canvas is at z = -1, x grows to the left as the camera looks towards -z.
+        let world_x = self.half_width - x * self.pixel_size;
+        let world_y = self.half_height - y * self.pixel_size;
+
+        let inverse = self.transformation.inverse();
+        let pixel = inverse * point!(world_x, world_y, -1.0);
+        let origin = inverse * point!(0.0, 0.0, 0.0);
+        Ray::new(origin, (pixel - origin).normalize())
+    }
+
+    //Shoots `samples_per_pixel` rays through every pixel, placed by the sampler,
+    //and splats every sample into all pixels within reach of the filter.
+    pub fn render(&self, world: &World) -> Canvas {
+        let size = (self.hsize * self.vsize) as usize;
+        let mut color_sums = vec![Color::default(); size];
+        let mut weight_sums = vec![0.0f32; size];
+        for y in 0..self.vsize {
+            for x in 0..self.hsize {
+                for (dx, dy) in self.sampler.offsets(x, y, self.samples_per_pixel) {
+                    let (sample_x, sample_y) = (x as f32 + dx, y as f32 + dy);
+                    let color = world.color_at(&self.ray_for_point(sample_x, sample_y));
+                    self.splat(sample_x, sample_y, color, &mut color_sums, &mut weight_sums);
+                }
+            }
+        }
+
+        let mut canvas = Canvas::new(self.hsize, self.vsize);
+        for y in 0..self.vsize {
+            for x in 0..self.hsize {
+                let index = (y * self.hsize + x) as usize;
+                //Negative lobes can cancel everything out, such pixel is left black.
+                if weight_sums[index] > 0.0 {
+                    canvas.set_pixel(x, y, color_sums[index] * (1.0 / weight_sums[index]));
+                }
+            }
+        }
+        canvas
+    }
+
+    //Adds weighted sample to every pixel whose center is within the filter radius.
+    fn splat(
+        &self,
+        sample_x: f32,
+        sample_y: f32,
+        color: Color,
+        color_sums: &mut [Color],
+        weight_sums: &mut [f32],
+    ) {
+        let radius = self.filter.radius();
+        let first_x = (sample_x - 0.5 - radius).ceil().max(0.0) as i64;
+        let last_x = ((sample_x - 0.5 + radius).floor() as i64).min(self.hsize as i64 - 1);
+        let first_y = (sample_y - 0.5 - radius).ceil().max(0.0) as i64;
+        let last_y = ((sample_y - 0.5 + radius).floor() as i64).min(self.vsize as i64 - 1);
+        for y in first_y..=last_y {
+            for x in first_x..=last_x {
+                let weight = self
+                    .filter
+                    .weight(sample_x - (x as f32 + 0.5), sample_y - (y as f32 + 0.5));
+                if weight != 0.0 {
+                    let index = (y as u32 * self.hsize + x as u32) as usize;
+                    color_sums[index] += color * weight;
+                    weight_sums[index] += weight;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::light::PointLight;
+    use crate::material::Material;
+    use crate::objects::SphereBuilder;
+    use std::f32::consts::{FRAC_PI_2, FRAC_PI_4};
+
+    #[test]
+    fn default_view_transformation() {
+        let t = view_transform(
+            &point!(0.0, 0.0, 0.0),
+            &point!(0.0, 0.0, -1.0),
+            &vector!(0.0, 1.0, 0.0),
+        );
+        assert_eq!(t, Mat4::identity());
+    }
+
+    #[test]
+    fn view_transformation_looking_in_positive_z() {
+        let t = view_transform(
+            &point!(0.0, 0.0, 0.0),
+            &point!(0.0, 0.0, 1.0),
+            &vector!(0.0, 1.0, 0.0),
+        );
+        assert_eq!(t, scaling!(-1.0, 1.0, -1.0));
+    }
+
+    #[test]
+    fn view_transformation_moves_world() {
+        let t = view_transform(
+            &point!(0.0, 0.0, 8.0),
+            &point!(0.0, 0.0, 0.0),
+            &vector!(0.0, 1.0, 0.0),
+        );
+        assert_eq!(t, translation!(0.0, 0.0, -8.0));
+    }
+
+    #[test]
+    fn arbitrary_view_transformation() {
+        let t = view_transform(
+            &point!(1.0, 3.0, 2.0),
+            &point!(4.0, -2.0, 8.0),
+            &vector!(1.0, 1.0, 0.0),
+        );
+        #[rustfmt::skip]
+        let expected = Mat4::new(
+            -0.50709, 0.50709, 0.67612, -2.36643,
+            0.76772, 0.60609, 0.12122, -2.82843,
+            -0.35857, 0.59761, -0.71714, 0.0,
+            0.0, 0.0, 0.0, 1.0,
+        );
+        matrix_eq!(t, expected, 0.0001);
+    }
+
+    #[test]
+    fn constructing_camera() {
+        let c = Camera::new(160, 120, FRAC_PI_2);
+        assert_eq!(c.hsize(), 160);
+        assert_eq!(c.vsize(), 120);
+        assert_eq!(c.field_of_view(), FRAC_PI_2);
+        assert_eq!(c.transformation().matrix(), &Mat4::identity());
+    }
+
+    #[test]
+    fn pixel_size_for_canvas() {
+        let horizontal = Camera::new(200, 125, FRAC_PI_2);
+        assert!((horizontal.pixel_size() - 0.01).abs() < EPSILON);
+        let vertical = Camera::new(125, 200, FRAC_PI_2);
+        assert!((vertical.pixel_size() - 0.01).abs() < EPSILON);
+    }
+
+    #[test]
+    fn ray_through_center_of_canvas() {
+        let c = Camera::new(201, 101, FRAC_PI_2);
+        let r = c.ray_for_pixel(100, 50);
+        matrix_eq!(r.origin, point!(0.0, 0.0, 0.0));
+        matrix_eq!(r.direction, vector!(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn ray_through_corner_of_canvas() {
+        let c = Camera::new(201, 101, FRAC_PI_2);
+        let r = c.ray_for_pixel(0, 0);
+        matrix_eq!(r.origin, point!(0.0, 0.0, 0.0));
+        matrix_eq!(r.direction, vector!(0.66519, 0.33259, -0.66851), 0.0001);
+    }
+
+    #[test]
+    fn ray_when_camera_is_transformed() {
+        let mut c = Camera::new(201, 101, FRAC_PI_2);
+        c.set_transformation(rotation!(0.0, FRAC_PI_4, 0.0) * translation!(0.0, -2.0, 5.0))
+            .unwrap();
+        let r = c.ray_for_pixel(100, 50);
+        let sq = 2.0f32.sqrt() / 2.0;
+        matrix_eq!(r.origin, point!(0.0, 2.0, -5.0), 0.0001);
+        matrix_eq!(r.direction, vector!(sq, 0.0, -sq), 0.0001);
+    }
+
+    #[test]
+    fn singular_camera_transformation_is_rejected() {
+        let mut c = Camera::new(10, 10, FRAC_PI_2);
+        assert!(c.set_transformation(scaling!(0.0, 0.0, 0.0)).is_err());
+        assert_eq!(c.transformation().matrix(), &Mat4::identity());
+    }
+
+    #[test]
+    fn box_filter_splats_border_sample_into_one_pixel() {
+        let c = Camera::new(2, 2, FRAC_PI_2);
+        let mut color_sums = vec![Color::default(); 4];
+        let mut weight_sums = vec![0.0; 4];
+        //Corner shared by all four pixels.
+        c.splat(1.0, 1.0, Color::red(), &mut color_sums, &mut weight_sums);
+        assert_eq!(weight_sums, vec![0.0, 0.0, 0.0, 1.0]);
+        //Border between the two top pixels.
+        c.splat(1.0, 0.5, Color::red(), &mut color_sums, &mut weight_sums);
+        assert_eq!(weight_sums, vec![0.0, 1.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn rendering_world_with_camera() {
+        let w = World::default();
+        let mut c = Camera::new(11, 11, FRAC_PI_2);
+        let from = point!(0.0, 0.0, -5.0);
+        let to = point!(0.0, 0.0, 0.0);
+        let up = vector!(0.0, 1.0, 0.0);
+        c.set_transformation(view_transform(&from, &to, &up))
+            .unwrap();
+        let image = c.render(&w);
+        assert_eq!(image.width(), 11);
+        assert_eq!(image.height(), 11);
+        matrix_eq!(
+            image.pixel(5, 5).as_array(),
+            [0.38066, 0.47583, 0.2855],
+            0.0001
+        );
+        assert_eq!(image.pixel(0, 0), Color::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn camera_takes_one_sample_by_default() {
+        let mut c = Camera::new(10, 10, FRAC_PI_2);
+        assert_eq!(c.samples_per_pixel(), 1);
+        assert_eq!(c.sampler(), Sampler::Regular);
+        assert_eq!(c.filter(), ReconstructionFilter::Box);
+        c.set_samples_per_pixel(0);
+        assert_eq!(c.samples_per_pixel(), 1);
+    }
+
+    #[test]
+    fn ray_through_point_of_canvas() {
+        let c = Camera::new(201, 101, FRAC_PI_2);
+        let r = c.ray_for_point(100.5, 50.5);
+        matrix_eq!(r.direction, c.ray_for_pixel(100, 50).direction);
+        let r = c.ray_for_point(0.0, 0.0);
+        let corner = c.ray_for_pixel(0, 0);
+        assert!(r.direction.x > corner.direction.x);
+        assert!(r.direction.y > corner.direction.y);
+    }
+
+    fn edge_world() -> World {
+        //Big white sphere with nothing but ambient light, so it has a flat color
+        //and a sharp edge against the black background.
+        let light = PointLight::new(point!(0.0, 0.0, -100.0), Color::new(1.0, 1.0, 1.0));
+        let mut w = World::new(Box::new(light));
+        let sphere = SphereBuilder::new()
+            .with_material(Material::new(Color::new(1.0, 1.0, 1.0), 1.0, 0.0, 0.0, 1.0))
+            .with_transformation(translation!(-1.0, 0.0, 0.0))
+            .create()
+            .unwrap();
+        w.add_object(Box::new(sphere));
+        w
+    }
+
+    fn edge_camera() -> Camera {
+        let mut c = Camera::new(9, 9, FRAC_PI_2);
+        c.set_transformation(view_transform(
+            &point!(0.0, 0.0, -1.5),
+            &point!(0.0, 0.0, 0.0),
+            &vector!(0.0, 1.0, 0.0),
+        ))
+        .unwrap();
+        c
+    }
+
+    #[test]
+    fn supersampling_softens_edges() {
+        let w = edge_world();
+        let mut c = edge_camera();
+        let aliased = c.render(&w);
+        let is_flat = |canvas: &Canvas| {
+            (0..9).all(|y| {
+                (0..9).all(|x| {
+                    let r = canvas.pixel(x, y).r();
+                    r == 0.0 || r == 1.0
+                })
+            })
+        };
+        assert!(is_flat(&aliased));
+
+        for sampler in [
+            Sampler::Regular,
+            Sampler::Stratified { seed: 1 },
+            Sampler::Halton,
+        ]
+        .iter()
+        {
+            c.set_sampler(*sampler);
+            c.set_samples_per_pixel(16);
+            let smooth = c.render(&w);
+            assert!(!is_flat(&smooth));
+            //Deep inside of the sphere and far from it nothing changes.
+            assert_eq!(smooth.pixel(0, 4), Color::new(1.0, 1.0, 1.0));
+            assert_eq!(smooth.pixel(8, 0), Color::new(0.0, 0.0, 0.0));
+        }
+    }
+
+    #[test]
+    fn reconstruction_filters_keep_flat_areas() {
+        let w = edge_world();
+        let mut c = edge_camera();
+        c.set_samples_per_pixel(4);
+        let filters = [
+            ReconstructionFilter::Tent { radius: 1.0 },
+            ReconstructionFilter::Gaussian {
+                radius: 1.5,
+                alpha: 2.0,
+            },
+            ReconstructionFilter::MitchellNetravali {
+                b: 1.0 / 3.0,
+                c: 1.0 / 3.0,
+            },
+        ];
+        for filter in filters.iter() {
+            c.set_filter(*filter);
+            let image = c.render(&w);
+            matrix_eq!(image.pixel(0, 4).as_array(), [1.0, 1.0, 1.0], 0.0001);
+            matrix_eq!(image.pixel(8, 8).as_array(), [0.0, 0.0, 0.0], 0.0001);
+        }
+    }
+}
@@ -1,11 +1,64 @@
 use crate::math::Color;
-use std::error::Error;
+use image::codecs::hdr::HdrEncoder;
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
 use std::path::Path;
 
-pub(crate) struct Canvas {
+mod exr;
+mod ppm;
+
+pub use ppm::PpmError;
+
+//Maps linear radiance, which can be anywhere in [0, inf), into displayable [0, 1].
+//Applied to every channel separately when the canvas is written out.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum ToneMapping {
+    //Everything above 1 is cut off.
+    #[default]
+    Clamp,
+    //c / (1 + c), never reaches 1, so no highlight is ever fully white.
+    Reinhard,
+    //Reinhard that maps `white` (and anything brighter) to 1.
+    ExtendedReinhard {
+        white: f32,
+    },
+    //Curve fitted to the ACES filmic one, with a slight toe and soft shoulder.
+    AcesFilmic,
+    //1 - e^(-c * exposure). Output is sRGB encoded afterwards, so there is no gamma step.
+    Exposure {
+        exposure: f32,
+    },
+}
+
+impl ToneMapping {
+    pub fn map(&self, color: Color) -> Color {
+        let map_channel = |c: f32| -> f32 {
+            let c = c.max(0.0);
+            let mapped = match *self {
+                ToneMapping::Clamp => c,
+                ToneMapping::Reinhard => c / (1.0 + c),
+                ToneMapping::ExtendedReinhard { white } => {
+                    c * (1.0 + c / (white * white)) / (1.0 + c)
+                }
+                ToneMapping::AcesFilmic => (c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14),
+                ToneMapping::Exposure { exposure } => 1.0 - (-c * exposure).exp(),
+            };
+            mapped.min(1.0)
+        };
+        Color::new(
+            map_channel(color.r()),
+            map_channel(color.g()),
+            map_channel(color.b()),
+        )
+    }
+}
+
+//Image in linear, unbounded colors. Converted to 8-bit sRGB only when written out.
+pub struct Canvas {
     width: u32,
     height: u32,
-    pixels: Vec<[u8; 3]>,
+    pixels: Vec<Color>,
+    tone_mapping: ToneMapping,
 }
 
 impl Canvas {
@@ -13,7 +66,8 @@ impl Canvas {
         Canvas {
             width,
             height,
-            pixels: vec![[0, 0, 0]; (width * height) as usize],
+            pixels: vec![Color::default(); (width * height) as usize],
+            tone_mapping: ToneMapping::default(),
         }
     }
 
@@ -25,22 +79,256 @@ impl Canvas {
         self.height
     }
 
+    pub fn tone_mapping(&self) -> ToneMapping {
+        self.tone_mapping
+    }
+
+    pub fn set_tone_mapping(&mut self, tone_mapping: ToneMapping) {
+        self.tone_mapping = tone_mapping;
+    }
+
     pub fn set_pixel(&mut self, x: u32, y: u32, color: Color) {
-        //TODO: This conversion is bad; fix this;
-        let r = (color.r() * 255.0) as u8;
-        let g = (color.g() * 255.0) as u8;
-        let b = (color.b() * 255.0) as u8;
         assert!(x < self.width);
         assert!(y < self.height);
-        self.pixels[(y * self.width + x) as usize] = [r, g, b];
+        self.pixels[(y * self.width + x) as usize] = color;
+    }
+
+    //Color as it was rendered, before tone mapping.
+    pub fn pixel(&self, x: u32, y: u32) -> Color {
+        assert!(x < self.width);
+        assert!(y < self.height);
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    //Pixel tone mapped and sRGB encoded, the way it ends up in the file.
+    pub fn output_pixel(&self, x: u32, y: u32) -> [u8; 3] {
+        self.tone_mapping.map(self.pixel(x, y)).to_srgb8()
     }
 
+    //Format is picked by the extension. OpenEXR (.exr), Radiance (.hdr) and PFM (.pfm)
+    //files keep the linear colors as they are, everything else gets `output_pixel`s.
     pub fn to_file<P: AsRef<Path>>(&self, path: P) -> image::error::ImageResult<()> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("exr") => return self.to_exr(path),
+            Some("hdr") => return self.to_hdr(path),
+            Some("pfm") => {
+                return Ok(self.write_pfm(BufWriter::new(File::create(path)?))?);
+            }
+            _ => {}
+        }
         let mut imgbuf = image::ImageBuffer::new(self.width, self.height);
         for (x, y, pixel) in imgbuf.enumerate_pixels_mut() {
-            //TODO: Check if there is a better way to convert float to byte.
-            *pixel = image::Rgb(self.pixels[(y * self.width + x) as usize])
+            *pixel = image::Rgb(self.output_pixel(x, y))
         }
         imgbuf.save(path)
     }
+
+    //Unclamped 32-bit float radiance, without tone mapping.
+    pub fn to_exr<P: AsRef<Path>>(&self, path: P) -> image::error::ImageResult<()> {
+        let file = BufWriter::new(File::create(path)?);
+        exr::write_exr(self, file)?;
+        Ok(())
+    }
+
+    //Radiance RGBE, shared 8-bit exponent for all channels. Negative values are lost.
+    pub fn to_hdr<P: AsRef<Path>>(&self, path: P) -> image::error::ImageResult<()> {
+        let file = BufWriter::new(File::create(path)?);
+        let pixels: Vec<image::Rgb<f32>> = self
+            .pixels
+            .iter()
+            .map(|color| image::Rgb(color.as_array()))
+            .collect();
+        HdrEncoder::new(file).encode(&pixels, self.width as usize, self.height as usize)
+    }
+
+    //Plain text P3 PPM of the `output_pixel`s.
+    pub fn write_ppm<W: Write>(&self, writer: W) -> io::Result<()> {
+        ppm::write_ppm(self, writer)
+    }
+
+    pub fn to_ppm(&self) -> String {
+        let mut bytes = Vec::new();
+        self.write_ppm(&mut bytes)
+            .expect("Writing to Vec never fails");
+        String::from_utf8(bytes).expect("PPM is plain ASCII")
+    }
+
+    //Binary P6 PPM of the `output_pixel`s.
+    pub fn write_ppm_binary<W: Write>(&self, writer: W) -> io::Result<()> {
+        ppm::write_ppm_binary(self, writer)
+    }
+
+    //Portable float map with the linear colors, without tone mapping.
+    pub fn write_pfm<W: Write>(&self, writer: W) -> io::Result<()> {
+        ppm::write_pfm(self, writer)
+    }
+
+    //Plain (P3) or binary (P6) PPM, its sRGB values are decoded to linear colors.
+    pub fn read_ppm<R: Read>(reader: R) -> Result<Canvas, PpmError> {
+        ppm::read_ppm(reader)
+    }
+
+    pub fn read_pfm<R: Read>(reader: R) -> Result<Canvas, PpmError> {
+        ppm::read_pfm(reader)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn gray(value: f32) -> Color {
+        Color::new(value, value, value)
+    }
+
+    //Unique per test and process, so parallel runs never share a file.
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("raytrace_rs_{}_{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn canvas_keeps_values_above_one() {
+        let mut canvas = Canvas::new(10, 20);
+        assert_eq!(canvas.pixel(9, 19), gray(0.0));
+        canvas.set_pixel(2, 3, Color::new(1.9, 0.5, 0.0));
+        assert_eq!(canvas.pixel(2, 3), Color::new(1.9, 0.5, 0.0));
+        assert_eq!(canvas.output_pixel(2, 3), [255, 188, 0]);
+    }
+
+    #[test]
+    fn clamp_cuts_off_highlights() {
+        let mapping = ToneMapping::Clamp;
+        assert_eq!(
+            mapping.map(Color::new(1.9, 0.5, -0.2)),
+            Color::new(1.0, 0.5, 0.0)
+        );
+    }
+
+    #[test]
+    fn reinhard_rolls_off_highlights() {
+        let mapping = ToneMapping::Reinhard;
+        matrix_eq!(mapping.map(gray(1.0)).as_array(), [0.5; 3]);
+        matrix_eq!(mapping.map(gray(3.0)).as_array(), [0.75; 3]);
+        assert!(mapping.map(gray(1000.0)).r() < 1.0);
+    }
+
+    #[test]
+    fn extended_reinhard_maps_white_point_to_one() {
+        let mapping = ToneMapping::ExtendedReinhard { white: 4.0 };
+        matrix_eq!(mapping.map(gray(4.0)).as_array(), [1.0; 3]);
+        matrix_eq!(mapping.map(gray(8.0)).as_array(), [1.0; 3]);
+        assert!(mapping.map(gray(1.0)).r() > ToneMapping::Reinhard.map(gray(1.0)).r());
+    }
+
+    #[test]
+    fn aces_filmic_curve() {
+        let mapping = ToneMapping::AcesFilmic;
+        assert_eq!(mapping.map(gray(0.0)), gray(0.0));
+        matrix_eq!(mapping.map(gray(1.0)).as_array(), [0.80377; 3], 0.0001);
+        matrix_eq!(mapping.map(gray(100.0)).as_array(), [1.0; 3]);
+    }
+
+    #[test]
+    fn exposure_is_encoded_once() {
+        let mapping = ToneMapping::Exposure { exposure: 1.0 };
+        matrix_eq!(mapping.map(gray(1.0)).as_array(), [0.63212; 3], 0.0001);
+        let brighter = ToneMapping::Exposure { exposure: 2.0 };
+        assert!(brighter.map(gray(1.0)).r() > mapping.map(gray(1.0)).r());
+        //Only the sRGB curve is applied on the way out: 0.63212 encodes to 208.
+        let mut canvas = Canvas::new(1, 1);
+        canvas.set_pixel(0, 0, gray(1.0));
+        canvas.set_tone_mapping(mapping);
+        assert_eq!(canvas.output_pixel(0, 0), [208, 208, 208]);
+    }
+
+    #[test]
+    fn highlights_are_ordered_after_tone_mapping() {
+        let mappings = [
+            ToneMapping::Reinhard,
+            ToneMapping::ExtendedReinhard { white: 10.0 },
+            ToneMapping::AcesFilmic,
+            ToneMapping::Exposure { exposure: 1.0 },
+        ];
+        for mapping in mappings.iter() {
+            let values: Vec<f32> = [0.5, 1.0, 1.5, 2.0, 4.0]
+                .iter()
+                .map(|&c| mapping.map(gray(c)).r())
+                .collect();
+            assert!(values.windows(2).all(|pair| pair[0] < pair[1]));
+        }
+    }
+
+    #[test]
+    fn output_uses_tone_mapping() {
+        let mut canvas = Canvas::new(1, 1);
+        canvas.set_pixel(0, 0, gray(3.0));
+        assert_eq!(canvas.output_pixel(0, 0), [255, 255, 255]);
+        canvas.set_tone_mapping(ToneMapping::Reinhard);
+        assert_eq!(canvas.tone_mapping(), ToneMapping::Reinhard);
+        assert_eq!(canvas.output_pixel(0, 0), [225, 225, 225]);
+    }
+
+    #[test]
+    fn hdr_file_keeps_high_values() {
+        let path = temp_path("hdr_file_keeps_high_values.hdr");
+        let mut canvas = Canvas::new(2, 1);
+        canvas.set_pixel(0, 0, Color::new(12.0, 0.5, 0.25));
+        canvas.set_pixel(1, 0, gray(1.0));
+        canvas.to_file(&path).unwrap();
+
+        let file = std::io::BufReader::new(File::open(&path).unwrap());
+        let pixels = image::codecs::hdr::HdrDecoder::new(file)
+            .unwrap()
+            .read_image_hdr()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(pixels.len(), 2);
+        //RGBE keeps 8 bits of mantissa relative to the largest channel.
+        matrix_eq!(pixels[0].0, [12.0, 0.5, 0.25], 0.05);
+        matrix_eq!(pixels[1].0, [1.0, 1.0, 1.0], 0.01);
+    }
+
+    #[test]
+    fn exr_file_is_picked_by_extension() {
+        let path = temp_path("exr_file_is_picked_by_extension.EXR");
+        let mut canvas = Canvas::new(3, 2);
+        canvas.set_pixel(2, 1, gray(7.5));
+        canvas.to_file(&path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(&bytes[..4], &[0x76, 0x2f, 0x31, 0x01]);
+        let last = f32::from_le_bytes([
+            bytes[bytes.len() - 4],
+            bytes[bytes.len() - 3],
+            bytes[bytes.len() - 2],
+            bytes[bytes.len() - 1],
+        ]);
+        assert_eq!(last, 7.5);
+    }
+
+    #[test]
+    fn canvas_to_ppm() {
+        let mut canvas = Canvas::new(2, 1);
+        canvas.set_pixel(1, 0, Color::red());
+        assert_eq!(canvas.to_ppm(), "P3\n2 1\n255\n0 0 0 255 0 0\n");
+        let read = Canvas::read_ppm(canvas.to_ppm().as_bytes()).unwrap();
+        assert_eq!(read.pixel(1, 0), Color::red());
+    }
+
+    #[test]
+    fn pfm_file_is_picked_by_extension() {
+        let path = temp_path("pfm_file_is_picked_by_extension.pfm");
+        let mut canvas = Canvas::new(2, 3);
+        canvas.set_pixel(1, 2, Color::new(5.0, 0.5, -2.0));
+        canvas.to_file(&path).unwrap();
+        let read = Canvas::read_pfm(File::open(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(read.pixel(1, 2), Color::new(5.0, 0.5, -2.0));
+        assert_eq!(read.pixel(0, 0), gray(0.0));
+    }
 }
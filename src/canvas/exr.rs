@@ -0,0 +1,136 @@
+use super::Canvas;
+use std::io::{self, Write};
+
+//Minimal OpenEXR writer: single part, scanline image, no compression, 32-bit
+//float R, G and B channels. That is enough for every compositor to read it.
+
+const MAGIC: u32 = 20000630;
+const VERSION: u32 = 2;
+const PIXEL_TYPE_FLOAT: i32 = 2;
+//Channels have to be listed, and stored, in alphabetical order.
+const CHANNELS: [&str; 3] = ["B", "G", "R"];
+
+fn attribute<W: Write>(writer: &mut W, name: &str, kind: &str, value: &[u8]) -> io::Result<()> {
+    writer.write_all(name.as_bytes())?;
+    writer.write_all(&[0])?;
+    writer.write_all(kind.as_bytes())?;
+    writer.write_all(&[0])?;
+    writer.write_all(&(value.len() as i32).to_le_bytes())?;
+    writer.write_all(value)
+}
+
+fn header(canvas: &Canvas) -> Vec<u8> {
+    let mut header = Vec::new();
+    header.extend_from_slice(&MAGIC.to_le_bytes());
+    header.extend_from_slice(&VERSION.to_le_bytes());
+
+    let mut channels = Vec::new();
+    for name in CHANNELS.iter() {
+        channels.extend_from_slice(name.as_bytes());
+        channels.push(0);
+        channels.extend_from_slice(&PIXEL_TYPE_FLOAT.to_le_bytes());
+        //pLinear and 3 reserved bytes.
+        channels.extend_from_slice(&[0, 0, 0, 0]);
+        //x and y sampling.
+        channels.extend_from_slice(&1i32.to_le_bytes());
+        channels.extend_from_slice(&1i32.to_le_bytes());
+    }
+    channels.push(0);
+
+    let mut window = Vec::new();
+    for value in [0, 0, canvas.width as i32 - 1, canvas.height as i32 - 1].iter() {
+        window.extend_from_slice(&value.to_le_bytes());
+    }
+
+    let attributes: [(&str, &str, &[u8]); 8] = [
+        ("channels", "chlist", &channels),
+        ("compression", "compression", &[0]),
+        ("dataWindow", "box2i", &window),
+        ("displayWindow", "box2i", &window),
+        ("lineOrder", "lineOrder", &[0]),
+        ("pixelAspectRatio", "float", &1.0f32.to_le_bytes()),
+        ("screenWindowCenter", "v2f", &[0; 8]),
+        ("screenWindowWidth", "float", &1.0f32.to_le_bytes()),
+    ];
+    for (name, kind, value) in attributes.iter() {
+        attribute(&mut header, name, kind, value).expect("Writing to Vec never fails");
+    }
+    header.push(0);
+    header
+}
+
+pub(super) fn write_exr<W: Write>(canvas: &Canvas, mut writer: W) -> io::Result<()> {
+    let header = header(canvas);
+    writer.write_all(&header)?;
+
+    //Every scanline is a chunk of its y, data size and then all values of
+    //the first channel, all values of the second one and so on.
+    let line_size = canvas.width as usize * CHANNELS.len() * 4;
+    let chunk_size = (8 + line_size) as u64;
+    let first_chunk = (header.len() + canvas.height as usize * 8) as u64;
+    for y in 0..canvas.height as u64 {
+        writer.write_all(&(first_chunk + y * chunk_size).to_le_bytes())?;
+    }
+
+    let mut line = Vec::with_capacity(line_size);
+    for y in 0..canvas.height {
+        line.clear();
+        for channel in 0..CHANNELS.len() {
+            for x in 0..canvas.width {
+                let [r, g, b] = canvas.pixel(x, y).as_array();
+                let value = [b, g, r][channel];
+                line.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        writer.write_all(&(y as i32).to_le_bytes())?;
+        writer.write_all(&(line_size as i32).to_le_bytes())?;
+        writer.write_all(&line)?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::math::Color;
+
+    fn read_u32(bytes: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+    }
+
+    fn read_f32(bytes: &[u8], at: usize) -> f32 {
+        f32::from_bits(read_u32(bytes, at))
+    }
+
+    #[test]
+    fn exr_stores_unclamped_floats() {
+        let mut canvas = Canvas::new(2, 2);
+        canvas.set_pixel(0, 0, Color::new(12.5, 0.25, -1.0));
+        canvas.set_pixel(1, 1, Color::new(0.0, 3.0, 1000.0));
+        let mut bytes = Vec::new();
+        write_exr(&canvas, &mut bytes).unwrap();
+
+        assert_eq!(read_u32(&bytes, 0), MAGIC);
+        assert_eq!(read_u32(&bytes, 4), VERSION);
+        let header_size = header(&canvas).len();
+        let line_size = 2 * 3 * 4;
+        assert_eq!(bytes.len(), header_size + 2 * 8 + 2 * (8 + line_size));
+
+        let first = read_u32(&bytes, header_size) as usize;
+        let second = read_u32(&bytes, header_size + 8) as usize;
+        assert_eq!(first, header_size + 16);
+        assert_eq!(second, first + 8 + line_size);
+
+        //Line 0: y, size, then B, G and R of both pixels.
+        assert_eq!(read_u32(&bytes, first), 0);
+        assert_eq!(read_u32(&bytes, first + 4) as usize, line_size);
+        assert_eq!(read_f32(&bytes, first + 8), -1.0);
+        assert_eq!(read_f32(&bytes, first + 16), 0.25);
+        assert_eq!(read_f32(&bytes, first + 24), 12.5);
+        //Line 1, second pixel.
+        assert_eq!(read_u32(&bytes, second), 1);
+        assert_eq!(read_f32(&bytes, second + 12), 1000.0);
+        assert_eq!(read_f32(&bytes, second + 20), 3.0);
+        assert_eq!(read_f32(&bytes, second + 28), 0.0);
+    }
+}
@@ -0,0 +1,430 @@
+use super::Canvas;
+use crate::math::Color;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
+
+//Longest line allowed in plain PPM files.
+const MAX_LINE_LENGTH: usize = 70;
+
+#[derive(Debug)]
+pub enum PpmError {
+    Io(io::Error),
+    //Not a PPM/PFM file, or a broken one.
+    Format(String),
+}
+
+impl fmt::Display for PpmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PpmError::Io(err) => write!(f, "Unable to read image: {}", err),
+            PpmError::Format(message) => write!(f, "Malformed image: {}", message),
+        }
+    }
+}
+
+impl Error for PpmError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PpmError::Io(err) => Some(err),
+            PpmError::Format(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for PpmError {
+    fn from(err: io::Error) -> Self {
+        PpmError::Io(err)
+    }
+}
+
+fn format_error<T>(message: &str) -> Result<T, PpmError> {
+    Err(PpmError::Format(message.to_string()))
+}
+
+//Plain P3: every value in decimal, every row starts on a new line and no line
+//is longer than 70 characters.
+pub(super) fn write_ppm<W: Write>(canvas: &Canvas, mut writer: W) -> io::Result<()> {
+    write!(writer, "P3\n{} {}\n255\n", canvas.width, canvas.height)?;
+    let mut line = String::with_capacity(MAX_LINE_LENGTH);
+    for y in 0..canvas.height {
+        for x in 0..canvas.width {
+            for value in canvas.output_pixel(x, y).iter() {
+                let value = value.to_string();
+                if !line.is_empty() && line.len() + 1 + value.len() > MAX_LINE_LENGTH {
+                    writeln!(writer, "{}", line)?;
+                    line.clear();
+                }
+                if !line.is_empty() {
+                    line.push(' ');
+                }
+                line.push_str(&value);
+            }
+        }
+        writeln!(writer, "{}", line)?;
+        line.clear();
+    }
+    writer.flush()
+}
+
+//Binary P6, 3 bytes per pixel.
+pub(super) fn write_ppm_binary<W: Write>(canvas: &Canvas, mut writer: W) -> io::Result<()> {
+    write!(writer, "P6\n{} {}\n255\n", canvas.width, canvas.height)?;
+    for y in 0..canvas.height {
+        for x in 0..canvas.width {
+            writer.write_all(&canvas.output_pixel(x, y))?;
+        }
+    }
+    writer.flush()
+}
+
+//Little endian, linear floats, rows from the bottom of the image up.
+pub(super) fn write_pfm<W: Write>(canvas: &Canvas, mut writer: W) -> io::Result<()> {
+    write!(writer, "PF\n{} {}\n-1.0\n", canvas.width, canvas.height)?;
+    for y in (0..canvas.height).rev() {
+        for x in 0..canvas.width {
+            for value in canvas.pixel(x, y).as_array().iter() {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+        }
+    }
+    writer.flush()
+}
+
+//Reads header fields separated by whitespace, `#` comments included.
+struct Header<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Header<'a> {
+    fn token(&mut self) -> Result<&'a str, PpmError> {
+        loop {
+            match self.bytes.get(self.position) {
+                Some(b'#') => {
+                    while self.bytes.get(self.position).is_some_and(|&b| b != b'\n') {
+                        self.position += 1;
+                    }
+                }
+                Some(b) if b.is_ascii_whitespace() => self.position += 1,
+                Some(_) => break,
+                None => return format_error("unexpected end of header"),
+            }
+        }
+        let start = self.position;
+        while self
+            .bytes
+            .get(self.position)
+            .is_some_and(|b| !b.is_ascii_whitespace())
+        {
+            self.position += 1;
+        }
+        std::str::from_utf8(&self.bytes[start..self.position])
+            .or_else(|_| format_error("header is not text"))
+    }
+
+    fn number<T: std::str::FromStr>(&mut self, what: &str) -> Result<T, PpmError> {
+        let token = self.token()?;
+        token
+            .parse()
+            .or_else(|_| format_error(&format!("invalid {} '{}'", what, token)))
+    }
+
+    //Binary data starts after exactly one whitespace character.
+    fn data(self) -> &'a [u8] {
+        &self.bytes[(self.position + 1).min(self.bytes.len())..]
+    }
+}
+
+fn size(header: &mut Header) -> Result<(u32, u32), PpmError> {
+    let width = header.number("width")?;
+    let height = header.number("height")?;
+    if width == 0 || height == 0 {
+        return format_error("image has no pixels");
+    }
+    Ok((width, height))
+}
+
+//Number of values in the image. Header values can't be trusted, so overflow is an
+//error. Canvas indexes its pixels with u32, so their number has to fit in one.
+fn value_count(width: u32, height: u32, channels: usize) -> Result<usize, PpmError> {
+    match width
+        .checked_mul(height)
+        .and_then(|pixels| (pixels as usize).checked_mul(channels))
+    {
+        Some(count) => Ok(count),
+        None => format_error("image too large"),
+    }
+}
+
+//P3 or P6. Values are sRGB encoded and scaled by the maximum value from the header.
+pub(super) fn read_ppm<R: Read>(mut reader: R) -> Result<Canvas, PpmError> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    let mut header = Header {
+        bytes: &bytes,
+        position: 0,
+    };
+    let magic = header.token()?;
+    if magic != "P3" && magic != "P6" {
+        return format_error(&format!("unsupported format '{}'", magic));
+    }
+    let (width, height) = size(&mut header)?;
+    let max_value: u32 = header.number("maximum value")?;
+    if max_value == 0 || max_value > 65535 {
+        return format_error("maximum value out of range");
+    }
+
+    let count = value_count(width, height, 3)?;
+    let values: Vec<u32> = if magic == "P3" {
+        //Not allocated up front, the count comes from the header. Runs out of
+        //values with an error, when the file is shorter than the header claims.
+        let mut values = Vec::new();
+        for _ in 0..count {
+            values.push(header.number("value")?);
+        }
+        values
+    } else {
+        let bytes_per_value = if max_value > 255 { 2 } else { 1 };
+        let data = header.data();
+        if data.len() / bytes_per_value < count {
+            return format_error("not enough pixel data");
+        }
+        data.chunks(bytes_per_value)
+            .take(count)
+            .map(|chunk| chunk.iter().fold(0, |value, &b| value << 8 | b as u32))
+            .collect()
+    };
+    if values.iter().any(|&value| value > max_value) {
+        return format_error("value larger than maximum");
+    }
+
+    let mut canvas = Canvas::new(width, height);
+    let scale = max_value as f32;
+    for (i, rgb) in values.chunks(3).enumerate() {
+        let color = Color::from_srgb(
+            rgb[0] as f32 / scale,
+            rgb[1] as f32 / scale,
+            rgb[2] as f32 / scale,
+        );
+        canvas.pixels[i] = color;
+    }
+    Ok(canvas)
+}
+
+//PF (color) or Pf (grayscale), in either byte order.
+pub(super) fn read_pfm<R: Read>(mut reader: R) -> Result<Canvas, PpmError> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    let mut header = Header {
+        bytes: &bytes,
+        position: 0,
+    };
+    let channels = match header.token()? {
+        "PF" => 3,
+        "Pf" => 1,
+        magic => return format_error(&format!("unsupported format '{}'", magic)),
+    };
+    let (width, height) = size(&mut header)?;
+    //Only the sign matters, negative means little endian.
+    let scale: f32 = header.number("scale")?;
+    let little_endian = scale < 0.0;
+
+    let data = header.data();
+    let count = value_count(width, height, channels)?;
+    if data.len() / 4 < count {
+        return format_error("not enough pixel data");
+    }
+    let values: Vec<f32> = data
+        .chunks(4)
+        .take(count)
+        .map(|chunk| {
+            let chunk = [chunk[0], chunk[1], chunk[2], chunk[3]];
+            if little_endian {
+                f32::from_le_bytes(chunk)
+            } else {
+                f32::from_be_bytes(chunk)
+            }
+        })
+        .collect();
+
+    let mut canvas = Canvas::new(width, height);
+    for (i, pixel) in values.chunks(channels).enumerate() {
+        let x = i as u32 % width;
+        let y = height - 1 - i as u32 / width;
+        let color = if channels == 3 {
+            Color::new(pixel[0], pixel[1], pixel[2])
+        } else {
+            Color::new(pixel[0], pixel[0], pixel[0])
+        };
+        canvas.set_pixel(x, y, color);
+    }
+    Ok(canvas)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn to_string(canvas: &Canvas) -> String {
+        let mut bytes = Vec::new();
+        write_ppm(canvas, &mut bytes).unwrap();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    #[test]
+    fn ppm_header() {
+        let canvas = Canvas::new(5, 3);
+        let ppm = to_string(&canvas);
+        let lines: Vec<&str> = ppm.lines().take(3).collect();
+        assert_eq!(lines, ["P3", "5 3", "255"]);
+    }
+
+    #[test]
+    fn ppm_pixel_data() {
+        let mut canvas = Canvas::new(5, 3);
+        canvas.set_pixel(0, 0, Color::new(1.5, 0.0, 0.0));
+        canvas.set_pixel(2, 1, Color::new(0.0, 0.5, 0.0));
+        canvas.set_pixel(4, 2, Color::new(-0.5, 0.0, 1.0));
+        let ppm = to_string(&canvas);
+        let lines: Vec<&str> = ppm.lines().skip(3).collect();
+        assert_eq!(
+            lines,
+            [
+                "255 0 0 0 0 0 0 0 0 0 0 0 0 0 0",
+                "0 0 0 0 0 0 0 188 0 0 0 0 0 0 0",
+                "0 0 0 0 0 0 0 0 0 0 0 0 0 0 255",
+            ]
+        );
+    }
+
+    #[test]
+    fn ppm_splits_long_lines() {
+        let mut canvas = Canvas::new(10, 2);
+        for y in 0..2 {
+            for x in 0..10 {
+                canvas.set_pixel(x, y, Color::from_srgb8(255, 204, 153));
+            }
+        }
+        let ppm = to_string(&canvas);
+        let lines: Vec<&str> = ppm.lines().skip(3).collect();
+        assert_eq!(
+            lines,
+            [
+                "255 204 153 255 204 153 255 204 153 255 204 153 255 204 153 255 204",
+                "153 255 204 153 255 204 153 255 204 153 255 204 153",
+                "255 204 153 255 204 153 255 204 153 255 204 153 255 204 153 255 204",
+                "153 255 204 153 255 204 153 255 204 153 255 204 153",
+            ]
+        );
+    }
+
+    #[test]
+    fn ppm_ends_with_newline() {
+        let canvas = Canvas::new(5, 3);
+        assert!(to_string(&canvas).ends_with('\n'));
+    }
+
+    fn test_canvas() -> Canvas {
+        let mut canvas = Canvas::new(3, 2);
+        for y in 0..2 {
+            for x in 0..3 {
+                canvas.set_pixel(x, y, Color::from_srgb8(x as u8 * 100, y as u8 * 50, 7));
+            }
+        }
+        canvas
+    }
+
+    fn assert_same_output(a: &Canvas, b: &Canvas) {
+        assert_eq!(a.width(), b.width());
+        assert_eq!(a.height(), b.height());
+        for y in 0..a.height() {
+            for x in 0..a.width() {
+                assert_eq!(a.output_pixel(x, y), b.output_pixel(x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn plain_ppm_round_trip() {
+        let canvas = test_canvas();
+        let mut bytes = Vec::new();
+        write_ppm(&canvas, &mut bytes).unwrap();
+        assert_same_output(&read_ppm(bytes.as_slice()).unwrap(), &canvas);
+    }
+
+    #[test]
+    fn binary_ppm_round_trip() {
+        let canvas = test_canvas();
+        let mut bytes = Vec::new();
+        write_ppm_binary(&canvas, &mut bytes).unwrap();
+        assert!(bytes.starts_with(b"P6\n3 2\n255\n"));
+        assert_eq!(bytes.len(), 11 + 3 * 2 * 3);
+        assert_same_output(&read_ppm(bytes.as_slice()).unwrap(), &canvas);
+    }
+
+    #[test]
+    fn reading_ppm_with_comments_and_other_maximum() {
+        let ppm = "P3\n# made by hand\n2 1 # size\n100\n100 100 100\n0 50 100\n";
+        let canvas = read_ppm(ppm.as_bytes()).unwrap();
+        assert_eq!(canvas.pixel(0, 0), Color::new(1.0, 1.0, 1.0));
+        assert_eq!(canvas.output_pixel(1, 0), [0, 128, 255]);
+    }
+
+    #[test]
+    fn reading_broken_ppm_fails() {
+        assert!(read_ppm("P5\n1 1\n255\n0".as_bytes()).is_err());
+        assert!(read_ppm("P3\n2 1\n255\n0 0 0 0 0".as_bytes()).is_err());
+        assert!(read_ppm("P3\n1 1\n255\n0 256 0".as_bytes()).is_err());
+        assert!(read_ppm("P6\n2 2\n255\n\x00\x00".as_bytes()).is_err());
+    }
+
+    fn is_format_error<T>(result: Result<T, PpmError>) -> bool {
+        matches!(result, Err(PpmError::Format(_)))
+    }
+
+    #[test]
+    fn reading_ppm_with_huge_size_fails() {
+        //Number of pixels doesn't fit in 32 bits.
+        let overflowing = "P3\n4294967295 4294967295\n255\n0 0 0";
+        assert!(is_format_error(read_ppm(overflowing.as_bytes())));
+        //Fits, but is far more than the file holds.
+        let oversized = "P3\n60000 60000\n255\n0 0 0";
+        assert!(is_format_error(read_ppm(oversized.as_bytes())));
+        let oversized = "P6\n60000 60000\n65535\n\x00\x00";
+        assert!(is_format_error(read_ppm(oversized.as_bytes())));
+        let overflowing = "PF\n4294967295 4294967295\n-1.0\n";
+        assert!(is_format_error(read_pfm(overflowing.as_bytes())));
+        let oversized = "PF\n60000 60000\n-1.0\n\x00\x00\x00\x00";
+        assert!(is_format_error(read_pfm(oversized.as_bytes())));
+    }
+
+    #[test]
+    fn pfm_round_trip_keeps_floats() {
+        let mut canvas = Canvas::new(2, 2);
+        canvas.set_pixel(0, 0, Color::new(12.5, -0.25, 0.0));
+        canvas.set_pixel(1, 1, Color::new(0.1, 0.2, 1000.0));
+        let mut bytes = Vec::new();
+        write_pfm(&canvas, &mut bytes).unwrap();
+        assert!(bytes.starts_with(b"PF\n2 2\n-1.0\n"));
+        //Bottom row goes first.
+        assert_eq!(&bytes[24..28], &0.1f32.to_le_bytes());
+
+        let read = read_pfm(bytes.as_slice()).unwrap();
+        for y in 0..2 {
+            for x in 0..2 {
+                assert_eq!(read.pixel(x, y), canvas.pixel(x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn reading_big_endian_grayscale_pfm() {
+        let mut bytes = b"Pf\n2 1\n1.0\n".to_vec();
+        bytes.extend_from_slice(&0.5f32.to_be_bytes());
+        bytes.extend_from_slice(&4.0f32.to_be_bytes());
+        let canvas = read_pfm(bytes.as_slice()).unwrap();
+        assert_eq!(canvas.pixel(0, 0), Color::new(0.5, 0.5, 0.5));
+        assert_eq!(canvas.pixel(1, 0), Color::new(4.0, 4.0, 4.0));
+    }
+}
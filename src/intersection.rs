@@ -1,31 +1,50 @@
+use crate::light::reflection;
 use crate::math::*;
-use crate::objects::{normal_at, Ray, Sphere};
+use crate::objects::{normal_at, Ray, Shape};
 use std::cmp::Ordering;
 
-//Struct representing collision of ray and an object ( Sphere for now )
-#[derive(Debug, PartialEq, Copy, Clone)]
+//Struct representing collision of ray and an object
+#[derive(Debug, Copy, Clone)]
 pub struct Intersection<'a> {
     pub t: f32,
-    pub obj: &'a Sphere,
+    pub obj: &'a dyn Shape,
+    //Barycentric coordinates of the hit, set only by triangles.
+    pub u: f32,
+    pub v: f32,
 }
 
 impl<'a> Intersection<'a> {
-    pub fn new(t: f32, object: &'a Sphere) -> Intersection<'a> {
+    pub fn new(t: f32, object: &'a dyn Shape) -> Intersection<'a> {
+        Intersection::with_uv(t, object, 0.0, 0.0)
+    }
+
+    pub fn with_uv(t: f32, object: &'a dyn Shape, u: f32, v: f32) -> Intersection<'a> {
         if !t.is_finite() {
             panic!("Bad float!");
         }
-        Intersection { t, obj: object }
+        Intersection {
+            t,
+            obj: object,
+            u,
+            v,
+        }
     }
 }
 
-impl<'a> PartialOrd for Intersection<'a> {
+impl PartialEq for Intersection<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.t == other.t && self.obj.id() == other.obj.id()
+    }
+}
+
+impl PartialOrd for Intersection<'_> {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.t.partial_cmp(&other.t)
+        Some(self.cmp(other))
     }
 }
-impl<'a> Eq for Intersection<'a> {}
+impl Eq for Intersection<'_> {}
 
-impl<'a> Ord for Intersection<'a> {
+impl Ord for Intersection<'_> {
     fn cmp(&self, other: &Self) -> Ordering {
         self.t.partial_cmp(&other.t).unwrap()
     }
@@ -34,7 +53,7 @@ impl<'a> Ord for Intersection<'a> {
 //TODO: Figure out whether we need to group intersections object-wide.
 pub type Intersections<'a> = Vec<Intersection<'a>>;
 
-trait IntersectionInserter<'a> {
+pub trait IntersectionInserter<'a> {
     fn add(&mut self, intersection: Intersection<'a>);
 }
 
@@ -51,104 +70,151 @@ impl<'a> IntersectionInserter<'a> for Intersections<'a> {
 #[derive(Debug, Copy, Clone)]
 pub struct Precomputation<'a> {
     pub t: f32,
-    pub obj: &'a Sphere,
+    pub obj: &'a dyn Shape,
     pub point: Point4,
     pub eyev: Vec4,
     pub normalv: Vec4,
     pub inside: bool,
+    //Point moved slightly above the surface, rays leaving the surface start here
+    //so they don't hit it again because of float precision (shadow acne).
+    pub over_point: Point4,
+    //Direction of the ray reflected off the surface.
+    pub reflectv: Vec4,
+    //Point moved slightly below the surface, where refracted rays start.
+    pub under_point: Point4,
+    //Refractive indices of the materials the ray is leaving and entering.
+    pub n1: f32,
+    pub n2: f32,
 }
 
 impl<'a> Precomputation<'a> {
-    pub fn compute(intersection: &Intersection<'a>, ray: &Ray) -> Precomputation<'a> {
+    //`intersections` are all the intersections of the ray, sorted, including the one
+    //being computed. They tell which objects the ray is inside of at the hit.
+    pub fn compute(
+        intersection: &Intersection<'a>,
+        ray: &Ray,
+        intersections: &[Intersection<'a>],
+    ) -> Precomputation<'a> {
         let pos = ray.position(intersection.t);
-        let normalv = normal_at(intersection.obj, &pos);
+        let normalv = normal_at(intersection.obj, &pos, intersection);
         let eyev = -ray.direction;
-        let inside = normalv.dot(&eyev) < 0.0;
         //Are we inside the object?
+        let inside = normalv.dot(&eyev) < 0.0;
+        let normalv = if inside { -normalv } else { normalv };
+        let (n1, n2) = refractive_indices(intersection, intersections);
         Precomputation {
             t: intersection.t,
             obj: intersection.obj,
             point: pos,
             eyev,
-            normalv: {
-                if inside {
-                    -normalv
-                } else {
-                    normalv
-                }
-            },
+            normalv,
             inside,
+            over_point: pos + normalv * EPSILON,
+            reflectv: reflection(&ray.direction, &normalv),
+            under_point: pos - normalv * EPSILON,
+            n1,
+            n2,
+        }
+    }
+
+    //Schlick's approximation of Fresnel equations: the fraction of light that is
+    //reflected rather than refracted at the surface.
+    pub fn schlick(&self) -> f32 {
+        let mut cos = self.eyev.dot(&self.normalv);
+        if self.n1 > self.n2 {
+            let n = self.n1 / self.n2;
+            let sin2_t = n * n * (1.0 - cos * cos);
+            if sin2_t > 1.0 {
+                //Total internal reflection.
+                return 1.0;
+            }
+            cos = (1.0 - sin2_t).sqrt();
         }
+        let r0 = ((self.n1 - self.n2) / (self.n1 + self.n2)).powi(2);
+        r0 + (1.0 - r0) * (1.0 - cos).powi(5)
     }
 }
 
-//Calculate if ray is intersecting with a sphere
+//Walk the intersections up to the hit keeping track of objects the ray is inside of.
+//The most recently entered one is the material on the respective side of the hit.
+fn refractive_indices(hit: &Intersection, intersections: &[Intersection]) -> (f32, f32) {
+    let index_of = |containers: &[&dyn Shape]| {
+        containers
+            .last()
+            .map_or(1.0, |object| object.material().refractive_index)
+    };
+    let mut containers: Vec<&dyn Shape> = Vec::new();
+    let mut n1 = 1.0;
+    for intersection in intersections {
+        let is_hit = intersection == hit;
+        if is_hit {
+            n1 = index_of(&containers);
+        }
+        let id = intersection.obj.id();
+        match containers.iter().position(|object| object.id() == id) {
+            Some(position) => {
+                containers.remove(position);
+            }
+            None => containers.push(intersection.obj),
+        }
+        if is_hit {
+            return (n1, index_of(&containers));
+        }
+    }
+    (n1, index_of(&containers))
+}
+
+//Calculate if ray is intersecting with a shape
 //Returns Some(points of intersection) where there is a hit, or None otherwise
-pub fn intersect<'a>(ray: &Ray, sphere: &'a Sphere) -> Option<Intersections<'a>> {
-    //Sphere center to the origin.
-    let transformation = sphere
-        .get_transformation()
-        .clone_owned()
-        .try_inverse() // This will panic!
-        .expect("Unable to inverse transformation matrix for intersection!");
-    let ray_transformed = ray.transform(&transformation);
-    let sphere_to_ray = ray_transformed.origin - point!(0.0, 0.0, 0.0);
-
-    let a = ray_transformed.direction.dot(&ray_transformed.direction);
-    let b = 2.0 * ray_transformed.direction.dot(&sphere_to_ray);
-    let c = sphere_to_ray.dot(&sphere_to_ray) - 1.0;
-    let discriminant = b.powi(2) - 4.0 * a * c;
-    if discriminant < 0.0 {
+pub fn intersect<'a>(ray: &Ray, shape: &'a dyn Shape) -> Option<Intersections<'a>> {
+    let ray_transformed = ray.transform(shape.get_transformation().inverse());
+    let mut intersections = shape.local_intersect(&ray_transformed);
+    if intersections.is_empty() {
         None // NO HIT
     } else {
-        let sqrt_discriminant = discriminant.sqrt();
-        let denom = 2.0 * a;
-        let t1 = (-b - sqrt_discriminant) / denom;
-        let t2 = (-b + sqrt_discriminant) / denom;
-        let mut ts = vec![t1, t2];
-        ts.sort_by(|a, b| a.partial_cmp(b).unwrap());
-
-        let mut intersections = Intersections::new();
-        for t in ts.iter() {
-            intersections.add(Intersection::new(*t, &sphere));
-        }
+        intersections.sort();
         Some(intersections)
     }
 }
 
 //Return first visible hit from intersections hits.
 pub fn hit<'a>(intersections: &'a Intersections) -> Option<&'a Intersection<'a>> {
-    for intersect in intersections.into_iter() {
-        if intersect.t > 0.0 {
-            return Some(&intersect);
-        }
-    }
-    None
+    intersections.iter().find(|intersect| intersect.t > 0.0)
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::objects::SphereBuilder;
-    use std::path::PrefixComponent;
+    use crate::material::Material;
+    use crate::objects::{PlaneBuilder, Sphere, SphereBuilder};
 
     #[test]
     fn new_intersection() {
-        let sphere = SphereBuilder::new().create();
+        let sphere = SphereBuilder::new().create().unwrap();
         let inter = Intersection::new(3.5, &sphere);
-        assert_eq!(&sphere, inter.obj);
+        assert_eq!(sphere.id(), inter.obj.id());
+        assert_eq!(inter.t, 3.5);
+    }
+
+    #[test]
+    fn intersection_with_uv() {
+        let sphere = SphereBuilder::new().create().unwrap();
+        let inter = Intersection::with_uv(3.5, &sphere, 0.2, 0.4);
+        assert_eq!(inter.t, 3.5);
+        assert_eq!(inter.u, 0.2);
+        assert_eq!(inter.v, 0.4);
     }
 
     #[test]
     fn intersection_comparison() {
-        let sphere = SphereBuilder::new().create();
+        let sphere = SphereBuilder::new().create().unwrap();
         let i1 = Intersection::new(1.0, &sphere);
         let i2 = Intersection::new(-5.0, &sphere);
         assert!(i1 > i2);
     }
     #[test]
     fn intersection_order() {
-        let sphere = SphereBuilder::new().create();
+        let sphere = SphereBuilder::new().create().unwrap();
         let i0 = Intersection::new(5.0, &sphere);
         let i1 = Intersection::new(7.0, &sphere);
         let i2 = Intersection::new(-3.5, &sphere);
@@ -160,7 +226,7 @@ mod test {
         intersections.add(i2);
         intersections.add(i3);
 
-        assert_eq!(intersections.len(), 4 as usize);
+        assert_eq!(intersections.len(), 4);
         assert_eq!(intersections[0], i2);
         assert_eq!(intersections[1], i3);
         assert_eq!(intersections[2], i0);
@@ -169,7 +235,7 @@ mod test {
 
     #[test]
     fn test_ray_hit() {
-        let sphere = SphereBuilder::new().create();
+        let sphere = SphereBuilder::new().create().unwrap();
         let i1 = Intersection::new(1.0, &sphere);
         let i2 = Intersection::new(2.0, &sphere);
         let mut inters = Intersections::new();
@@ -180,7 +246,7 @@ mod test {
 
     #[test]
     fn test_ray_hit_with_negatives() {
-        let sphere = SphereBuilder::new().create();
+        let sphere = SphereBuilder::new().create().unwrap();
         let i1 = Intersection::new(-1.0, &sphere);
         let i2 = Intersection::new(0.1, &sphere);
         let mut inters = Intersections::new();
@@ -191,7 +257,7 @@ mod test {
 
     #[test]
     fn test_no_ray_hits() {
-        let sphere = SphereBuilder::new().create();
+        let sphere = SphereBuilder::new().create().unwrap();
         let i1 = Intersection::new(-1.0, &sphere);
         let i2 = Intersection::new(-0.1, &sphere);
         let mut inters = Intersections::new();
@@ -202,7 +268,7 @@ mod test {
 
     #[test]
     fn test_nearest_hit() {
-        let sphere = SphereBuilder::new().create();
+        let sphere = SphereBuilder::new().create().unwrap();
         let i1 = Intersection::new(5.0, &sphere);
         let i2 = Intersection::new(7.0, &sphere);
         let i3 = Intersection::new(-3.0, &sphere);
@@ -217,20 +283,20 @@ mod test {
 
     #[test]
     fn test_transformed_sphere_ray_hit() {
-        let mut sphere = SphereBuilder::new().create();
+        let mut sphere = SphereBuilder::new().create().unwrap();
         let r = Ray::new(point!(0.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0));
-        sphere.transform(&translation!(5.0, 0.0, 0.0));
+        sphere.transform(&translation!(5.0, 0.0, 0.0)).unwrap();
         assert_eq!(None, intersect(&r, &sphere));
     }
     #[test]
     fn test_scaled_sphere_ray_hit() {
-        let mut sphere = SphereBuilder::new().create();
+        let mut sphere = SphereBuilder::new().create().unwrap();
         let r = Ray::new(point!(0.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0));
-        sphere.transform(&scaling!(2.0, 2.0, 2.0));
+        sphere.transform(&scaling!(2.0, 2.0, 2.0)).unwrap();
         let intersects = intersect(&r, &sphere);
         assert_eq!(intersects.as_ref().map(|res| res.is_empty()), Some(false));
 
-        let expected = vec![
+        let expected = [
             Intersection::new(3.0, &sphere),
             Intersection::new(7.0, &sphere),
         ];
@@ -246,13 +312,13 @@ mod test {
         let ray = Ray::new(point!(0.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0));
         let shape = Sphere::default();
         let i = Intersection::new(4.0, &shape);
-        let comps = Precomputation::compute(&i, &ray);
-        assert_eq!(comps.obj, i.obj);
+        let comps = Precomputation::compute(&i, &ray, &[i]);
+        assert_eq!(comps.obj.id(), i.obj.id());
         assert_eq!(comps.t, i.t);
         matrix_eq!(comps.point, point!(0.0, 0.0, -1.0));
         matrix_eq!(comps.eyev, vector!(0.0, 0.0, -1.0));
         matrix_eq!(comps.normalv, vector!(0.0, 0.0, -1.0));
-        assert_eq!(comps.inside, false);
+        assert!(!comps.inside);
     }
 
     #[test]
@@ -260,12 +326,119 @@ mod test {
         let ray = Ray::new(point!(0.0, 0.0, 0.0), vector!(0.0, 0.0, 1.0));
         let shape = Sphere::default();
         let i = Intersection::new(1.0, &shape);
-        let comps = Precomputation::compute(&i, &ray);
-        assert_eq!(comps.obj, i.obj);
+        let comps = Precomputation::compute(&i, &ray, &[i]);
+        assert_eq!(comps.obj.id(), i.obj.id());
         assert_eq!(comps.t, i.t);
         matrix_eq!(comps.point, point!(0.0, 0.0, 1.0));
         matrix_eq!(comps.eyev, vector!(0.0, 0.0, -1.0));
         matrix_eq!(comps.normalv, vector!(0.0, 0.0, -1.0));
-        assert_eq!(comps.inside, true);
+        assert!(comps.inside);
+    }
+
+    #[test]
+    fn hit_offsets_point() {
+        let ray = Ray::new(point!(0.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0));
+        let shape = SphereBuilder::new()
+            .with_transformation(translation!(0.0, 0.0, 1.0))
+            .create()
+            .unwrap();
+        let i = Intersection::new(5.0, &shape);
+        let comps = Precomputation::compute(&i, &ray, &[i]);
+        assert!(comps.over_point.z < -EPSILON / 2.0);
+        assert!(comps.point.z > comps.over_point.z);
+    }
+
+    #[test]
+    fn precomputing_reflection_vector() {
+        let shape = PlaneBuilder::new().create().unwrap();
+        let sq = 2.0f32.sqrt() / 2.0;
+        let ray = Ray::new(point!(0.0, 1.0, -1.0), vector!(0.0, -sq, sq));
+        let i = Intersection::new(2.0f32.sqrt(), &shape);
+        let comps = Precomputation::compute(&i, &ray, &[i]);
+        matrix_eq!(comps.reflectv, vector!(0.0, sq, sq));
+    }
+
+    fn glass_sphere(transformation: Mat4, refractive_index: f32) -> Sphere {
+        SphereBuilder::new()
+            .with_transformation(transformation)
+            .with_material(Material {
+                transparency: 1.0,
+                refractive_index,
+                ..Default::default()
+            })
+            .create()
+            .unwrap()
+    }
+
+    #[test]
+    fn finding_n1_and_n2() {
+        let a = glass_sphere(scaling!(2.0, 2.0, 2.0), 1.5);
+        let b = glass_sphere(translation!(0.0, 0.0, -0.25), 2.0);
+        let c = glass_sphere(translation!(0.0, 0.0, 0.25), 2.5);
+        let ray = Ray::new(point!(0.0, 0.0, -4.0), vector!(0.0, 0.0, 1.0));
+        let xs = vec![
+            Intersection::new(2.0, &a),
+            Intersection::new(2.75, &b),
+            Intersection::new(3.25, &c),
+            Intersection::new(4.75, &b),
+            Intersection::new(5.25, &c),
+            Intersection::new(6.0, &a),
+        ];
+        let expected = [
+            (1.0, 1.5),
+            (1.5, 2.0),
+            (2.0, 2.5),
+            (2.5, 2.5),
+            (2.5, 1.5),
+            (1.5, 1.0),
+        ];
+        for (i, (n1, n2)) in expected.iter().enumerate() {
+            let comps = Precomputation::compute(&xs[i], &ray, &xs);
+            assert_eq!((comps.n1, comps.n2), (*n1, *n2), "intersection {}", i);
+        }
+    }
+
+    #[test]
+    fn under_point_is_below_surface() {
+        let ray = Ray::new(point!(0.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0));
+        let shape = glass_sphere(translation!(0.0, 0.0, 1.0), 1.5);
+        let i = Intersection::new(5.0, &shape);
+        let comps = Precomputation::compute(&i, &ray, &[i]);
+        assert!(comps.under_point.z > EPSILON / 2.0);
+        assert!(comps.point.z < comps.under_point.z);
+    }
+
+    #[test]
+    fn schlick_under_total_internal_reflection() {
+        let shape = glass_sphere(Mat4::identity(), 1.5);
+        let sq = 2.0f32.sqrt() / 2.0;
+        let ray = Ray::new(point!(0.0, 0.0, sq), vector!(0.0, 1.0, 0.0));
+        let xs = vec![
+            Intersection::new(-sq, &shape),
+            Intersection::new(sq, &shape),
+        ];
+        let comps = Precomputation::compute(&xs[1], &ray, &xs);
+        assert_eq!(comps.schlick(), 1.0);
+    }
+
+    #[test]
+    fn schlick_with_perpendicular_viewing_angle() {
+        let shape = glass_sphere(Mat4::identity(), 1.5);
+        let ray = Ray::new(point!(0.0, 0.0, 0.0), vector!(0.0, 1.0, 0.0));
+        let xs = vec![
+            Intersection::new(-1.0, &shape),
+            Intersection::new(1.0, &shape),
+        ];
+        let comps = Precomputation::compute(&xs[1], &ray, &xs);
+        assert!((comps.schlick() - 0.04).abs() < EPSILON);
+    }
+
+    #[test]
+    fn schlick_with_small_angle_and_n2_greater_than_n1() {
+        let shape = glass_sphere(Mat4::identity(), 1.5);
+        let ray = Ray::new(point!(0.0, 0.99, -2.0), vector!(0.0, 0.0, 1.0));
+        let xs = vec![Intersection::new(1.8589, &shape)];
+        let comps = Precomputation::compute(&xs[0], &ray, &xs);
+        assert!((comps.schlick() - 0.48873).abs() < 0.001);
     }
 }
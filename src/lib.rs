@@ -0,0 +1,19 @@
+#[macro_use]
+pub mod math;
+pub mod background;
+pub mod bounds;
+pub mod bvh;
+pub mod camera;
+pub mod canvas;
+pub mod intersection;
+pub mod light;
+pub mod material;
+pub mod noise;
+pub mod obj_file;
+pub mod objects;
+pub mod pattern;
+pub mod sampling;
+pub mod texture;
+pub mod transform;
+pub mod uv;
+pub mod world;
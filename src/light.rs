@@ -1,41 +1,60 @@
 use crate::intersection::Precomputation;
 use crate::material::Material;
 use crate::math::*;
+use crate::objects::{Ray, Shape};
 use crate::world::World;
 
-fn reflection(ray: &Vec4, normal: &Vec4) -> Vec4 {
+pub(crate) fn reflection(ray: &Vec4, normal: &Vec4) -> Vec4 {
     ray - normal * 2.0 * ray.dot(normal)
 }
 
 pub trait LightSource {
-    fn illuminate(&self, material: &Material, point: &Point4, eyev: &Vec4, normalv: &Vec4)
-        -> Color;
+    //Point the light comes from, shadow rays are cast towards it.
+    fn position(&self) -> Point4;
+
+    //Points in shadow get only the ambient part of the light. The object is needed
+    //to find where on its surface the point is, when the material has a pattern.
+    fn illuminate(
+        &self,
+        material: &Material,
+        object: &dyn Shape,
+        point: &Point4,
+        eyev: &Vec4,
+        normalv: &Vec4,
+        in_shadow: bool,
+    ) -> Color;
 }
 
 impl LightSource for PointLight {
+    fn position(&self) -> Point4 {
+        self.position
+    }
+
     fn illuminate(
         &self,
         material: &Material,
+        object: &dyn Shape,
         point: &Point4,
         eyev: &Vec4,
         normalv: &Vec4,
+        in_shadow: bool,
     ) -> Color {
-        let effecticve_color = material.color * self.intensity;
+        let effecticve_color = material.color_at(object, point) * self.intensity;
         let lightv = (self.position - point).normalize();
 
         let ambient = effecticve_color * material.ambient;
+        if in_shadow {
+            return ambient;
+        }
 
         let mut diffuse = Color::new(0.0, 0.0, 0.0);
         let mut specular = Color::new(0.0, 0.0, 0.0);
 
         let light_dot_normal = lightv.dot(normalv);
-        if light_dot_normal < 0.0 {
-            diffuse = Color::new(0.0, 0.0, 0.0);
-            specular = Color::new(0.0, 0.0, 0.0);
-        } else {
+        if light_dot_normal >= 0.0 {
             diffuse = effecticve_color * material.diffuse * light_dot_normal;
-            let reflectv = reflection(&-lightv, &normalv);
-            let reflect_dot_eye = reflectv.dot(&eyev);
+            let reflectv = reflection(&-lightv, normalv);
+            let reflect_dot_eye = reflectv.dot(eyev);
             if reflect_dot_eye > 0.0 {
                 let factor = reflect_dot_eye.powf(material.shininess);
                 specular = self.intensity * material.specular * factor;
@@ -45,14 +64,59 @@ impl LightSource for PointLight {
     }
 }
 
-pub fn shade_hit(world: &World, precomps: &Precomputation) -> Color {
-    let light = world.light_source(); // Single light source supported for now.
-    light.illuminate(
-        &precomps.obj.material,
-        &precomps.point,
-        &precomps.eyev,
-        &precomps.normalv,
-    )
+//Color of the surface at the hit, `remaining` limits how many more times
+//secondary rays (like reflections) can bounce.
+pub fn shade_hit(world: &World, precomps: &Precomputation, remaining: u32) -> Color {
+    let mut color = Color::default();
+    for light in world.lights_iter() {
+        let in_shadow = world.is_shadowed(light, &precomps.over_point);
+        color += light.illuminate(
+            precomps.obj.material(),
+            precomps.obj,
+            &precomps.over_point,
+            &precomps.eyev,
+            &precomps.normalv,
+            in_shadow,
+        );
+    }
+    let reflected = reflected_color(world, precomps, remaining);
+    let refracted = refracted_color(world, precomps, remaining);
+    let material = precomps.obj.material();
+    if material.reflective > 0.0 && material.transparency > 0.0 {
+        let reflectance = precomps.schlick();
+        color + reflected * reflectance + refracted * (1.0 - reflectance)
+    } else {
+        color + reflected + refracted
+    }
+}
+
+//Light coming from the direction of the mirror reflection, scaled by how reflective the surface is.
+pub fn reflected_color(world: &World, precomps: &Precomputation, remaining: u32) -> Color {
+    let reflective = precomps.obj.material().reflective;
+    if remaining == 0 || reflective == 0.0 {
+        return Color::default();
+    }
+    let ray = Ray::new(precomps.over_point, precomps.reflectv);
+    world.color_at_depth(&ray, remaining - 1) * reflective
+}
+
+//Light coming through a transparent surface, bent according to Snell's law.
+pub fn refracted_color(world: &World, precomps: &Precomputation, remaining: u32) -> Color {
+    let transparency = precomps.obj.material().transparency;
+    if remaining == 0 || transparency == 0.0 {
+        return Color::default();
+    }
+    let n_ratio = precomps.n1 / precomps.n2;
+    let cos_i = precomps.eyev.dot(&precomps.normalv);
+    let sin2_t = n_ratio * n_ratio * (1.0 - cos_i * cos_i);
+    if sin2_t > 1.0 {
+        //Total internal reflection, nothing gets through.
+        return Color::default();
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let direction = precomps.normalv * (n_ratio * cos_i - cos_t) - precomps.eyev * n_ratio;
+    let ray = Ray::new(precomps.under_point, direction);
+    world.color_at_depth(&ray, remaining - 1) * transparency
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -74,7 +138,10 @@ impl PointLight {
 mod test {
     use super::*;
     use crate::intersection::{Intersection, Precomputation};
-    use crate::objects::Ray;
+    use crate::objects::{PlaneBuilder, Sphere, SphereBuilder};
+    use crate::pattern::StripePattern;
+    use crate::world::DEFAULT_MAX_DEPTH;
+    use std::sync::Arc;
 
     #[test]
     fn reflect_45_deg() {
@@ -101,7 +168,7 @@ mod test {
         let eyev = vector!(0.0, 0.0, -1.0);
         let normalv = vector!(0.0, 0.0, -1.0);
         let light = PointLight::new(point!(0.0, 0.0, -10.0), Color::new(1.0, 1.0, 1.0));
-        let result = light.illuminate(&m, &position, &eyev, &normalv);
+        let result = light.illuminate(&m, &Sphere::default(), &position, &eyev, &normalv, false);
         assert_eq!(result, Color::new(1.9, 1.9, 1.9));
     }
 
@@ -114,7 +181,7 @@ mod test {
         let eyev = vector!(0.0, sq, -sq);
         let normalv = vector!(0.0, 0.0, -1.0);
         let light = PointLight::new(point!(0.0, 0.0, -10.0), Color::new(1.0, 1.0, 1.0));
-        let result = light.illuminate(&m, &position, &eyev, &normalv);
+        let result = light.illuminate(&m, &Sphere::default(), &position, &eyev, &normalv, false);
         assert_eq!(result, Color::new(1.0, 1.0, 1.0));
     }
 
@@ -126,8 +193,8 @@ mod test {
         let eyev = vector!(0.0, 0.0, -1.0);
         let normalv = vector!(0.0, 0.0, -1.0);
         let light = PointLight::new(point!(0.0, 10.0, -10.0), Color::new(1.0, 1.0, 1.0));
-        let result = light.illuminate(&m, &position, &eyev, &normalv);
-        assert_eq!(result, Color::new(0.7364, 0.7364, 0.7364));
+        let result = light.illuminate(&m, &Sphere::default(), &position, &eyev, &normalv, false);
+        matrix_eq!(result.as_array(), [0.7364, 0.7364, 0.7364], 0.0001);
     }
 
     #[test]
@@ -139,8 +206,8 @@ mod test {
         let eyev = vector!(0.0, -sq, -sq);
         let normalv = vector!(0.0, 0.0, -1.0);
         let light = PointLight::new(point!(0.0, 10.0, -10.0), Color::new(1.0, 1.0, 1.0));
-        let result = light.illuminate(&m, &position, &eyev, &normalv);
-        assert_eq!(result, Color::new(1.6364, 1.6364, 1.6364));
+        let result = light.illuminate(&m, &Sphere::default(), &position, &eyev, &normalv, false);
+        matrix_eq!(result.as_array(), [1.6364, 1.6364, 1.6364], 0.0001);
     }
 
     #[test]
@@ -151,7 +218,7 @@ mod test {
         let eyev = vector!(0.0, 0.0, -1.0);
         let normalv = vector!(0.0, 0.0, -1.0);
         let light = PointLight::new(point!(0.0, 0.0, 10.0), Color::new(1.0, 1.0, 1.0));
-        let result = light.illuminate(&m, &position, &eyev, &normalv);
+        let result = light.illuminate(&m, &Sphere::default(), &position, &eyev, &normalv, false);
         assert_eq!(result, Color::new(0.1, 0.1, 0.1));
     }
 
@@ -164,10 +231,265 @@ mod test {
             .next()
             .expect("Expected some shaped in the world!");
         let i = Intersection::new(4.0, shape);
-        let precomps = Precomputation::compute(&i, &r);
-        let c = shade_hit(&w, &precomps);
+        let precomps = Precomputation::compute(&i, &r, &[i]);
+        let c = shade_hit(&w, &precomps, 0);
         assert!((c.r() - 0.38066).abs() < 0.00001);
         assert!((c.g() - 0.47583).abs() < 0.00001);
         assert!((c.b() - 0.2855).abs() < 0.00001);
     }
+
+    #[test]
+    fn lighting_surface_in_shadow() {
+        let m = Material::default();
+        let position = point!(0.0, 0.0, 0.0);
+
+        let eyev = vector!(0.0, 0.0, -1.0);
+        let normalv = vector!(0.0, 0.0, -1.0);
+        let light = PointLight::new(point!(0.0, 0.0, -10.0), Color::new(1.0, 1.0, 1.0));
+        let result = light.illuminate(&m, &Sphere::default(), &position, &eyev, &normalv, true);
+        matrix_eq!(result.as_array(), [0.1, 0.1, 0.1]);
+    }
+
+    #[test]
+    fn shade_hit_in_shadow() {
+        let light = PointLight::new(point!(0.0, 0.0, -10.0), Color::new(1.0, 1.0, 1.0));
+        let mut w = World::new(Box::new(light));
+        w.add_object(Box::new(SphereBuilder::new().create().unwrap()));
+        let s2 = SphereBuilder::new()
+            .with_transformation(translation!(0.0, 0.0, 10.0))
+            .create()
+            .unwrap();
+        w.add_object(Box::new(s2));
+        let r = Ray::new(point!(0.0, 0.0, 5.0), vector!(0.0, 0.0, 1.0));
+        let shape = w.shapes_iter().nth(1).unwrap();
+        let i = Intersection::new(4.0, shape);
+        let precomps = Precomputation::compute(&i, &r, &[i]);
+        let c = shade_hit(&w, &precomps, 0);
+        matrix_eq!(c.as_array(), [0.1, 0.1, 0.1]);
+    }
+
+    fn world_with_mirror_floor() -> World {
+        let mut w = World::default();
+        let floor = PlaneBuilder::new()
+            .with_material(Material {
+                reflective: 0.5,
+                ..Default::default()
+            })
+            .with_transformation(translation!(0.0, -1.0, 0.0))
+            .create()
+            .unwrap();
+        w.add_object(Box::new(floor));
+        w
+    }
+
+    #[test]
+    fn reflected_color_for_nonreflective_material() {
+        let w = World::default();
+        let r = Ray::new(point!(0.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0));
+        let shape = w.shapes_iter().next().unwrap();
+        let i = Intersection::new(4.0, shape);
+        let precomps = Precomputation::compute(&i, &r, &[i]);
+        assert_eq!(
+            reflected_color(&w, &precomps, DEFAULT_MAX_DEPTH),
+            Color::new(0.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn reflected_color_for_reflective_material() {
+        let w = world_with_mirror_floor();
+        let sq = 2.0f32.sqrt() / 2.0;
+        let r = Ray::new(point!(0.0, 0.0, -3.0), vector!(0.0, -sq, sq));
+        let floor = w.shapes_iter().nth(2).unwrap();
+        let i = Intersection::new(2.0f32.sqrt(), floor);
+        let precomps = Precomputation::compute(&i, &r, &[i]);
+        let color = reflected_color(&w, &precomps, DEFAULT_MAX_DEPTH);
+        matrix_eq!(color.as_array(), [0.19032, 0.2379, 0.14274], 0.001);
+    }
+
+    #[test]
+    fn shade_hit_with_reflective_material() {
+        let w = world_with_mirror_floor();
+        let sq = 2.0f32.sqrt() / 2.0;
+        let r = Ray::new(point!(0.0, 0.0, -3.0), vector!(0.0, -sq, sq));
+        let floor = w.shapes_iter().nth(2).unwrap();
+        let i = Intersection::new(2.0f32.sqrt(), floor);
+        let precomps = Precomputation::compute(&i, &r, &[i]);
+        let color = shade_hit(&w, &precomps, DEFAULT_MAX_DEPTH);
+        matrix_eq!(color.as_array(), [0.87677, 0.92436, 0.82918], 0.001);
+    }
+
+    #[test]
+    fn reflected_color_at_maximum_depth() {
+        let w = world_with_mirror_floor();
+        let sq = 2.0f32.sqrt() / 2.0;
+        let r = Ray::new(point!(0.0, 0.0, -3.0), vector!(0.0, -sq, sq));
+        let floor = w.shapes_iter().nth(2).unwrap();
+        let i = Intersection::new(2.0f32.sqrt(), floor);
+        let precomps = Precomputation::compute(&i, &r, &[i]);
+        assert_eq!(reflected_color(&w, &precomps, 0), Color::new(0.0, 0.0, 0.0));
+    }
+
+    //Default world with its two spheres made of given materials.
+    fn default_world_with(outer: Material, inner: Material) -> World {
+        let light = PointLight::new(point!(-10.0, 10.0, -10.0), Color::new(1.0, 1.0, 1.0));
+        let mut w = World::new(Box::new(light));
+        let s1 = SphereBuilder::new().with_material(outer).create().unwrap();
+        let s2 = SphereBuilder::new()
+            .with_material(inner)
+            .with_transformation(scaling!(0.5, 0.5, 0.5))
+            .create()
+            .unwrap();
+        w.add_object(Box::new(s1));
+        w.add_object(Box::new(s2));
+        w
+    }
+
+    fn default_outer_material() -> Material {
+        Material::new(Color::new(0.8, 1.0, 0.6), 0.1, 0.7, 0.2, 200.0)
+    }
+
+    fn glass() -> Material {
+        Material {
+            transparency: 1.0,
+            refractive_index: 1.5,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn refracted_color_of_opaque_surface() {
+        let w = World::default();
+        let r = Ray::new(point!(0.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0));
+        let xs = w.ray_intersect(&r);
+        let precomps = Precomputation::compute(&xs[0], &r, &xs);
+        assert_eq!(
+            refracted_color(&w, &precomps, DEFAULT_MAX_DEPTH),
+            Color::new(0.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn refracted_color_at_maximum_depth() {
+        let w = default_world_with(glass(), Material::default());
+        let r = Ray::new(point!(0.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0));
+        let xs = w.ray_intersect(&r);
+        let precomps = Precomputation::compute(&xs[0], &r, &xs);
+        assert_eq!(refracted_color(&w, &precomps, 0), Color::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn refracted_color_under_total_internal_reflection() {
+        let w = default_world_with(glass(), Material::default());
+        let sq = 2.0f32.sqrt() / 2.0;
+        let r = Ray::new(point!(0.0, 0.0, sq), vector!(0.0, 1.0, 0.0));
+        let shape = w.shapes_iter().next().unwrap();
+        let xs = vec![Intersection::new(-sq, shape), Intersection::new(sq, shape)];
+        //Inside of the sphere, so the second intersection is the one to look at.
+        let precomps = Precomputation::compute(&xs[1], &r, &xs);
+        assert_eq!(
+            refracted_color(&w, &precomps, DEFAULT_MAX_DEPTH),
+            Color::new(0.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn refracted_color_sees_through_glass() {
+        //Red sphere in the middle of a glass one is visible through it.
+        let inner = Material {
+            ambient: 1.0,
+            ..Material::default_with_color(Color::red())
+        };
+        let w = default_world_with(glass(), inner);
+        let r = Ray::new(point!(0.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0));
+        let xs = w.ray_intersect(&r);
+        let precomps = Precomputation::compute(&xs[0], &r, &xs);
+        let color = refracted_color(&w, &precomps, DEFAULT_MAX_DEPTH);
+        //Glass casts a shadow on the inner sphere, so only its ambient color is left.
+        matrix_eq!(color.as_array(), [1.0, 0.0, 0.0], 0.0001);
+    }
+
+    #[test]
+    fn shade_hit_with_transparent_material() {
+        let mut w = default_world_with(default_outer_material(), Material::default());
+        let floor = PlaneBuilder::new()
+            .with_transformation(translation!(0.0, -1.0, 0.0))
+            .with_material(Material {
+                transparency: 0.5,
+                refractive_index: 1.5,
+                ..Default::default()
+            })
+            .create()
+            .unwrap();
+        let ball = SphereBuilder::new()
+            .with_transformation(translation!(0.0, -3.5, -0.5))
+            .with_material(Material {
+                ambient: 0.5,
+                ..Material::default_with_color(Color::red())
+            })
+            .create()
+            .unwrap();
+        w.add_object(Box::new(floor));
+        w.add_object(Box::new(ball));
+        let sq = 2.0f32.sqrt() / 2.0;
+        let r = Ray::new(point!(0.0, 0.0, -3.0), vector!(0.0, -sq, sq));
+        let floor = w.shapes_iter().nth(2).unwrap();
+        let xs = vec![Intersection::new(2.0f32.sqrt(), floor)];
+        let precomps = Precomputation::compute(&xs[0], &r, &xs);
+        let color = shade_hit(&w, &precomps, DEFAULT_MAX_DEPTH);
+        matrix_eq!(color.as_array(), [0.93642, 0.68642, 0.68642], 0.001);
+    }
+
+    #[test]
+    fn shade_hit_with_reflective_transparent_material() {
+        let mut w = default_world_with(default_outer_material(), Material::default());
+        let floor = PlaneBuilder::new()
+            .with_transformation(translation!(0.0, -1.0, 0.0))
+            .with_material(Material {
+                reflective: 0.5,
+                transparency: 0.5,
+                refractive_index: 1.5,
+                ..Default::default()
+            })
+            .create()
+            .unwrap();
+        let ball = SphereBuilder::new()
+            .with_transformation(translation!(0.0, -3.5, -0.5))
+            .with_material(Material {
+                ambient: 0.5,
+                ..Material::default_with_color(Color::red())
+            })
+            .create()
+            .unwrap();
+        w.add_object(Box::new(floor));
+        w.add_object(Box::new(ball));
+        let sq = 2.0f32.sqrt() / 2.0;
+        let r = Ray::new(point!(0.0, 0.0, -3.0), vector!(0.0, -sq, sq));
+        let floor = w.shapes_iter().nth(2).unwrap();
+        let xs = vec![Intersection::new(2.0f32.sqrt(), floor)];
+        let precomps = Precomputation::compute(&xs[0], &r, &xs);
+        let color = shade_hit(&w, &precomps, DEFAULT_MAX_DEPTH);
+        matrix_eq!(color.as_array(), [0.93391, 0.69643, 0.69243], 0.001);
+    }
+
+    #[test]
+    fn lighting_with_pattern() {
+        let m = Material {
+            ambient: 1.0,
+            diffuse: 0.0,
+            specular: 0.0,
+            ..Material::default_with_pattern(Arc::new(StripePattern::new(
+                Color::new(1.0, 1.0, 1.0),
+                Color::new(0.0, 0.0, 0.0),
+            )))
+        };
+        let eyev = vector!(0.0, 0.0, -1.0);
+        let normalv = vector!(0.0, 0.0, -1.0);
+        let light = PointLight::new(point!(0.0, 0.0, -10.0), Color::new(1.0, 1.0, 1.0));
+        let object = Sphere::default();
+        let c1 = light.illuminate(&m, &object, &point!(0.9, 0.0, 0.0), &eyev, &normalv, false);
+        let c2 = light.illuminate(&m, &object, &point!(1.1, 0.0, 0.0), &eyev, &normalv, false);
+        assert_eq!(c1, Color::new(1.0, 1.0, 1.0));
+        assert_eq!(c2, Color::new(0.0, 0.0, 0.0));
+    }
 }
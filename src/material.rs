@@ -1,23 +1,39 @@
 use crate::math::*;
+use crate::objects::Shape;
+use crate::pattern::Pattern;
+use std::sync::Arc;
 
 //TODO: Do Material Builder with defaults.
-#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+#[derive(Debug, Clone)]
 pub struct Material {
+    //Linear, use `Color::from_srgb` for colors picked in sRGB.
     pub color: Color,
+    //When set, used instead of `color`. Shared, so many shapes can use one pattern.
+    pub pattern: Option<Arc<dyn Pattern>>,
     pub ambient: f32,
     pub diffuse: f32,
     pub specular: f32,
     pub shininess: f32,
+    //0 for matte surfaces, 1 for a perfect mirror.
+    pub reflective: f32,
+    //0 for opaque surfaces, 1 lets all the light through.
+    pub transparency: f32,
+    //1 for vacuum, ~1.33 for water, ~1.5 for glass.
+    pub refractive_index: f32,
 }
 
 impl Default for Material {
     fn default() -> Self {
         Material {
             color: Color::new(1.0, 1.0, 1.0),
+            pattern: None,
             ambient: 0.1,
             diffuse: 0.9,
             specular: 0.9,
             shininess: 200.0,
+            reflective: 0.0,
+            transparency: 0.0,
+            refractive_index: 1.0,
         }
     }
 }
@@ -36,6 +52,7 @@ impl Material {
             diffuse,
             specular,
             shininess,
+            ..Default::default()
         }
     }
 
@@ -45,4 +62,51 @@ impl Material {
             ..Default::default()
         }
     }
+
+    pub fn default_with_pattern(pattern: Arc<dyn Pattern>) -> Material {
+        Material {
+            pattern: Some(pattern),
+            ..Default::default()
+        }
+    }
+
+    //Color of the material on the object at the point given in world space.
+    pub fn color_at(&self, object: &dyn Shape, world_point: &Point4) -> Color {
+        match &self.pattern {
+            Some(pattern) => pattern.pattern_at(object, world_point),
+            None => self.color,
+        }
+    }
+}
+
+//Patterns are compared by identity, there is no way to compare two different implementations.
+impl PartialEq for Material {
+    fn eq(&self, other: &Self) -> bool {
+        let same_pattern = match (&self.pattern, &other.pattern) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        };
+        same_pattern
+            && self.color == other.color
+            && self.ambient == other.ambient
+            && self.diffuse == other.diffuse
+            && self.specular == other.specular
+            && self.shininess == other.shininess
+            && self.reflective == other.reflective
+            && self.transparency == other.transparency
+            && self.refractive_index == other.refractive_index
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn material_with_pattern_can_cross_threads() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Arc<dyn Pattern>>();
+        assert_send_sync::<Material>();
+    }
 }
@@ -1,12 +1,16 @@
 use nalgebra as na;
-use std::ops::{Add, Mul, MulAssign};
+use std::ops::{Add, AddAssign, Mul, MulAssign, Sub};
 
 pub type Vec3 = na::Vector3<f32>;
 pub type Point4 = na::Point4<f32>;
 pub type Vec4 = na::Vector4<f32>;
 pub type Mat4 = na::Matrix4<f32>;
 
+//Tolerance for floating point comparisons in geometry calculations.
+pub const EPSILON: f32 = 0.0001;
+
 //Pouint in 3D space with w component = 0
+#[macro_export]
 macro_rules! point {
     ($x:expr, $y:expr, $z:expr) => {
         Point4::new($x, $y, $z, 1.0)
@@ -14,6 +18,7 @@ macro_rules! point {
 }
 
 //3D Vector with w component = 0
+#[macro_export]
 macro_rules! vector {
     ($x:expr, $y:expr, $z:expr) => {
         Vec4::new($x, $y, $z, 0.0)
@@ -21,24 +26,28 @@ macro_rules! vector {
 }
 
 //Translation matrix
+#[macro_export]
 macro_rules! translation {
     ($x:expr,$y:expr,$z:expr) => {
         nalgebra::Translation3::new($x, $y, $z).to_homogeneous()
     };
 }
 
+#[macro_export]
 macro_rules! scaling {
     ($x:expr,$y:expr,$z:expr) => {
         nalgebra::Matrix4::new_nonuniform_scaling(&Vec3::new($x, $y, $z))
     };
 }
 
+#[macro_export]
 macro_rules! rotation {
     ($x:expr,$y:expr,$z:expr) => {
         nalgebra::Rotation3::new(Vec3::new($x, $y, $z)).to_homogeneous()
     };
 }
 
+#[macro_export]
 macro_rules! shear {
     ($xy:expr,$xz:expr,$yx:expr,$yz:expr,$zx:expr,$zy:expr) => {
         nalgebra::Matrix4::new(
@@ -47,12 +56,16 @@ macro_rules! shear {
     };
 }
 
+#[cfg(test)]
 macro_rules! matrix_eq {
     ($mat_a:expr, $mat_b:expr) => {
+        matrix_eq!($mat_a, $mat_b, 0.00001)
+    };
+    ($mat_a:expr, $mat_b:expr, $eps:expr) => {
         let success = $mat_a
             .iter()
             .zip($mat_b.iter())
-            .all(|(a, b)| (a - b).abs() < 0.00001);
+            .all(|(a, b)| (a - b).abs() < $eps);
         if !success {
             panic!(
                 r#"assertion failed: `(left == right)`
@@ -64,6 +77,27 @@ macro_rules! matrix_eq {
     };
 }
 
+//sRGB transfer function: linear light in [0, 1] to the encoded value stored in
+//8-bit images and expected by displays.
+pub fn srgb_encode(linear: f32) -> f32 {
+    if linear <= 0.0031308 {
+        12.92 * linear
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+//Inverse of `srgb_encode`.
+pub fn srgb_decode(encoded: f32) -> f32 {
+    if encoded <= 0.04045 {
+        encoded / 12.92
+    } else {
+        ((encoded + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+//Linear RGB, all the lighting math is done in it. Colors picked in image editors
+//or color pickers are sRGB and should be converted with `from_srgb`/`from_srgb8`.
 #[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
 pub struct Color {
     rgb: Vec3,
@@ -92,6 +126,29 @@ impl Color {
         self.rgb.into()
     }
 
+    //Color given with sRGB encoded components in [0, 1].
+    pub fn from_srgb(r: f32, g: f32, b: f32) -> Color {
+        Color::new(srgb_decode(r), srgb_decode(g), srgb_decode(b))
+    }
+
+    //Color given the way it is stored in 8-bit images, like #RRGGBB.
+    pub fn from_srgb8(r: u8, g: u8, b: u8) -> Color {
+        Color::from_srgb(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0)
+    }
+
+    //sRGB encoded components, clamped to [0, 1].
+    pub fn to_srgb(&self) -> [f32; 3] {
+        let encode = |c: f32| srgb_encode(c.clamp(0.0, 1.0));
+        [encode(self.r()), encode(self.g()), encode(self.b())]
+    }
+
+    //sRGB encoded, rounded to the nearest 8-bit value.
+    pub fn to_srgb8(&self) -> [u8; 3] {
+        let [r, g, b] = self.to_srgb();
+        let quantize = |c: f32| (c * 255.0).round() as u8;
+        [quantize(r), quantize(g), quantize(b)]
+    }
+
     pub fn red() -> Color {
         Color::new(1.0, 0.0, 0.0)
     }
@@ -121,6 +178,22 @@ impl Add for Color {
     }
 }
 
+impl AddAssign for Color {
+    fn add_assign(&mut self, rhs: Self) {
+        self.rgb += rhs.rgb;
+    }
+}
+
+impl Sub for Color {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Color {
+            rgb: self.rgb - rhs.rgb,
+        }
+    }
+}
+
 impl Mul for Color {
     type Output = Self;
 
@@ -321,4 +394,32 @@ mod tests {
         let result = translation * scale * rotation * point;
         assert_eq!(result, point!(15.0, 0.0, 7.0));
     }
+
+    #[test]
+    fn srgb_transfer_functions() {
+        assert_eq!(srgb_encode(0.0), 0.0);
+        assert!((srgb_encode(1.0) - 1.0).abs() < 0.00001);
+        assert!((srgb_encode(0.5) - 0.73536).abs() < 0.0001);
+        //Linear segment near black.
+        assert!((srgb_encode(0.002) - 0.02584).abs() < 0.00001);
+        for &value in [0.0, 0.001, 0.02, 0.18, 0.5, 0.9, 1.0].iter() {
+            assert!((srgb_decode(srgb_encode(value)) - value).abs() < 0.00001);
+        }
+    }
+
+    #[test]
+    fn color_from_srgb() {
+        let color = Color::from_srgb8(255, 188, 0);
+        matrix_eq!(color.as_array(), [1.0, 0.50289, 0.0], 0.0001);
+        assert_eq!(Color::from_srgb(1.0, 0.0, 0.0), Color::red());
+    }
+
+    #[test]
+    fn color_to_srgb8_rounds_and_clamps() {
+        assert_eq!(Color::new(0.5, 1.9, -0.3).to_srgb8(), [188, 255, 0]);
+        for value in 0..=255u8 {
+            let color = Color::from_srgb8(value, value, value);
+            assert_eq!(color.to_srgb8(), [value, value, value]);
+        }
+    }
 }
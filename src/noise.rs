@@ -0,0 +1,238 @@
+use crate::math::*;
+
+//Seed used by `Perlin::default()`.
+pub const DEFAULT_SEED: u64 = 0;
+
+//Octaves of noise summed by the procedural patterns unless set otherwise.
+pub const DEFAULT_OCTAVES: u32 = 4;
+
+//Improved Perlin gradient noise. The permutation table is shuffled from the seed,
+//so the same seed always gives the same noise and renders are reproducible.
+#[derive(Clone)]
+pub struct Perlin {
+    seed: u64,
+    //Permutation of 0..256 repeated twice, so lookups never have to wrap.
+    permutation: [u8; 512],
+}
+
+impl std::fmt::Debug for Perlin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Perlin").field("seed", &self.seed).finish()
+    }
+}
+
+impl PartialEq for Perlin {
+    fn eq(&self, other: &Self) -> bool {
+        self.seed == other.seed
+    }
+}
+
+impl Default for Perlin {
+    fn default() -> Self {
+        Perlin::new(DEFAULT_SEED)
+    }
+}
+
+//SplitMix64, small and good enough for shuffling tables and jittering samples.
+pub(crate) fn next_random(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: f32, a: f32, b: f32) -> f32 {
+    a + t * (b - a)
+}
+
+//Dot product of the distance vector with one of 12 gradients chosen by the hash.
+fn gradient(hash: u8, x: f32, y: f32, z: f32) -> f32 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = if h < 4 {
+        y
+    } else if h == 12 || h == 14 {
+        x
+    } else {
+        z
+    };
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Perlin {
+        let mut table = [0u8; 256];
+        for (i, value) in table.iter_mut().enumerate() {
+            *value = i as u8;
+        }
+        //Fisher-Yates shuffle.
+        let mut state = seed;
+        for i in (1..table.len()).rev() {
+            let j = (next_random(&mut state) % (i as u64 + 1)) as usize;
+            table.swap(i, j);
+        }
+        let mut permutation = [0u8; 512];
+        for (i, value) in permutation.iter_mut().enumerate() {
+            *value = table[i & 255];
+        }
+        Perlin { seed, permutation }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    fn hash(&self, i: usize) -> usize {
+        self.permutation[i] as usize
+    }
+
+    //Noise value in roughly [-1, 1]. It is 0 at every point with integer coordinates.
+    pub fn noise(&self, point: &Point4) -> f32 {
+        let (fx, fy, fz) = (point.x.floor(), point.y.floor(), point.z.floor());
+        let xi = (fx as i64 & 255) as usize;
+        let yi = (fy as i64 & 255) as usize;
+        let zi = (fz as i64 & 255) as usize;
+        let (x, y, z) = (point.x - fx, point.y - fy, point.z - fz);
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+
+        let a = self.hash(xi) + yi;
+        let aa = self.hash(a) + zi;
+        let ab = self.hash(a + 1) + zi;
+        let b = self.hash(xi + 1) + yi;
+        let ba = self.hash(b) + zi;
+        let bb = self.hash(b + 1) + zi;
+
+        let p = &self.permutation;
+        lerp(
+            w,
+            lerp(
+                v,
+                lerp(u, gradient(p[aa], x, y, z), gradient(p[ba], x - 1.0, y, z)),
+                lerp(
+                    u,
+                    gradient(p[ab], x, y - 1.0, z),
+                    gradient(p[bb], x - 1.0, y - 1.0, z),
+                ),
+            ),
+            lerp(
+                v,
+                lerp(
+                    u,
+                    gradient(p[aa + 1], x, y, z - 1.0),
+                    gradient(p[ba + 1], x - 1.0, y, z - 1.0),
+                ),
+                lerp(
+                    u,
+                    gradient(p[ab + 1], x, y - 1.0, z - 1.0),
+                    gradient(p[bb + 1], x - 1.0, y - 1.0, z - 1.0),
+                ),
+            ),
+        )
+    }
+
+    //Fractional Brownian motion: octaves of noise, each at double the frequency
+    //and half the amplitude of the previous one. Normalized back to roughly [-1, 1].
+    pub fn fbm(&self, point: &Point4, octaves: u32) -> f32 {
+        self.octaves(point, octaves, |n| n)
+    }
+
+    //Like `fbm`, but sums absolute values, which gives sharp creases instead of
+    //smooth hills. Result is in roughly [0, 1].
+    pub fn turbulence(&self, point: &Point4, octaves: u32) -> f32 {
+        self.octaves(point, octaves, f32::abs)
+    }
+
+    fn octaves(&self, point: &Point4, octaves: u32, shape: impl Fn(f32) -> f32) -> f32 {
+        let mut sum = 0.0;
+        let mut total_amplitude = 0.0;
+        let mut amplitude = 1.0;
+        let mut frequency = 1.0;
+        for _ in 0..octaves.max(1) {
+            let scaled = point!(
+                point.x * frequency,
+                point.y * frequency,
+                point.z * frequency
+            );
+            sum += amplitude * shape(self.noise(&scaled));
+            total_amplitude += amplitude;
+            amplitude *= 0.5;
+            frequency *= 2.0;
+        }
+        sum / total_amplitude
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sample_points() -> Vec<Point4> {
+        (0..50)
+            .map(|i| {
+                let i = i as f32;
+                point!(i * 0.37 - 5.0, i * 0.61 + 0.2, i * -0.13 + 1.7)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn noise_is_zero_on_lattice_points() {
+        let noise = Perlin::default();
+        for &(x, y, z) in [(0.0, 0.0, 0.0), (1.0, 2.0, 3.0), (-4.0, 7.0, -1.0)].iter() {
+            assert_eq!(noise.noise(&point!(x, y, z)), 0.0);
+        }
+    }
+
+    #[test]
+    fn same_seed_gives_same_noise() {
+        let a = Perlin::new(42);
+        let b = Perlin::new(42);
+        for point in sample_points() {
+            assert_eq!(a.noise(&point), b.noise(&point));
+        }
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn different_seeds_give_different_noise() {
+        let a = Perlin::new(1);
+        let b = Perlin::new(2);
+        assert!(sample_points()
+            .iter()
+            .any(|point| a.noise(point) != b.noise(point)));
+    }
+
+    #[test]
+    fn noise_stays_in_range() {
+        let noise = Perlin::new(7);
+        for point in sample_points() {
+            let n = noise.noise(&point);
+            assert!((-1.0..=1.0).contains(&n));
+            let t = noise.turbulence(&point, 4);
+            assert!((0.0..=1.0).contains(&t));
+            let f = noise.fbm(&point, 4);
+            assert!((-1.0..=1.0).contains(&f));
+        }
+    }
+
+    #[test]
+    fn noise_is_continuous() {
+        let noise = Perlin::new(3);
+        let point = point!(0.3, 1.7, -2.2);
+        let nearby = point!(0.3001, 1.7, -2.2);
+        assert!((noise.noise(&point) - noise.noise(&nearby)).abs() < 0.001);
+    }
+
+    #[test]
+    fn single_octave_fbm_is_plain_noise() {
+        let noise = Perlin::new(5);
+        let point = point!(0.5, 0.25, 0.75);
+        assert_eq!(noise.fbm(&point, 1), noise.noise(&point));
+        assert_eq!(noise.turbulence(&point, 1), noise.noise(&point).abs());
+    }
+}
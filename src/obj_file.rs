@@ -0,0 +1,488 @@
+use crate::math::*;
+use crate::objects::{Group, GroupBuilder, Shape, SmoothTriangleBuilder, TriangleBuilder};
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+//Groups made from the file are never transformed, so creating them can't fail.
+const IDENTITY_IS_VALID: &str = "Identity transformation is always invertible";
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io(io::Error),
+    //Malformed statement, with 1-based line number in the source.
+    Parse { line: usize, message: String },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjError::Io(err) => write!(f, "Unable to read OBJ file: {}", err),
+            ObjError::Parse { line, message } => write!(f, "OBJ line {}: {}", line, message),
+        }
+    }
+}
+
+impl Error for ObjError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ObjError::Io(err) => Some(err),
+            ObjError::Parse { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for ObjError {
+    fn from(err: io::Error) -> Self {
+        ObjError::Io(err)
+    }
+}
+
+//Named set of triangles, created by `g` or `o` statements.
+//Faces before the first of them land in the default group with an empty name.
+#[derive(Debug)]
+pub struct ObjGroup {
+    name: String,
+    shapes: Vec<Box<dyn Shape>>,
+}
+
+impl ObjGroup {
+    fn new(name: &str) -> ObjGroup {
+        ObjGroup {
+            name: name.to_string(),
+            shapes: Vec::new(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn shapes(&self) -> &[Box<dyn Shape>] {
+        &self.shapes
+    }
+}
+
+//Geometry read from a Wavefront OBJ file.
+//Supported statements are v, vn, vt, f, g and o, everything else is skipped.
+//Faces with a `vt` on every vertex get the texture coordinates, use
+//`SurfaceUvPattern` to put a texture on them.
+#[derive(Debug)]
+pub struct ObjFile {
+    vertices: Vec<Point4>,
+    normals: Vec<Vec4>,
+    texture_coords: Vec<(f32, f32)>,
+    groups: Vec<ObjGroup>,
+    ignored_lines: usize,
+}
+
+//Indices of a single face vertex, already resolved to 0-based positions.
+struct FaceVertex {
+    vertex: usize,
+    texture: Option<usize>,
+    normal: Option<usize>,
+}
+
+impl ObjFile {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<ObjFile, ObjError> {
+        let file = File::open(path)?;
+        ObjFile::from_reader(BufReader::new(file))
+    }
+
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<ObjFile, ObjError> {
+        let mut obj = ObjFile {
+            vertices: Vec::new(),
+            normals: Vec::new(),
+            texture_coords: Vec::new(),
+            groups: vec![ObjGroup::new("")],
+            ignored_lines: 0,
+        };
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            obj.parse_line(&line).map_err(|message| ObjError::Parse {
+                line: index + 1,
+                message,
+            })?;
+        }
+        Ok(obj)
+    }
+
+    pub fn vertices(&self) -> &[Point4] {
+        &self.vertices
+    }
+
+    pub fn normals(&self) -> &[Vec4] {
+        &self.normals
+    }
+
+    pub fn texture_coords(&self) -> &[(f32, f32)] {
+        &self.texture_coords
+    }
+
+    pub fn groups(&self) -> &[ObjGroup] {
+        &self.groups
+    }
+
+    pub fn group(&self, name: &str) -> Option<&ObjGroup> {
+        self.groups.iter().find(|group| group.name == name)
+    }
+
+    //Number of lines that were not recognized and got skipped.
+    pub fn ignored_lines(&self) -> usize {
+        self.ignored_lines
+    }
+
+    //All the triangles from all the groups, ready to be added to the World.
+    pub fn into_shapes(self) -> Vec<Box<dyn Shape>> {
+        self.groups
+            .into_iter()
+            .flat_map(|group| group.shapes.into_iter())
+            .collect()
+    }
+
+    //Model as a single group, every non-empty OBJ group becomes one of its subgroups.
+    pub fn into_group(self) -> Group {
+        let mut builder = GroupBuilder::new();
+        for group in self.groups {
+            if group.shapes.is_empty() {
+                continue;
+            }
+            let mut subgroup = GroupBuilder::new();
+            for shape in group.shapes {
+                subgroup.with_child(shape);
+            }
+            builder.with_child(Box::new(subgroup.create().expect(IDENTITY_IS_VALID)));
+        }
+        builder.create().expect(IDENTITY_IS_VALID)
+    }
+
+    fn parse_line(&mut self, line: &str) -> Result<(), String> {
+        let line = line.split('#').next().unwrap_or("");
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => return Ok(()),
+        };
+        let args: Vec<&str> = tokens.collect();
+        match keyword {
+            "v" => {
+                let [x, y, z] = parse_floats::<3>(&args, "vertex")?;
+                self.vertices.push(point!(x, y, z));
+            }
+            "vn" => {
+                let [x, y, z] = parse_floats::<3>(&args, "normal")?;
+                self.normals.push(vector!(x, y, z));
+            }
+            "vt" => {
+                //v is optional for 1D textures.
+                let [u] = parse_floats::<1>(&args, "texture coordinate")?;
+                let v = match args.len() {
+                    1 => 0.0,
+                    _ => parse_floats::<2>(&args, "texture coordinate")?[1],
+                };
+                self.texture_coords.push((u, v));
+            }
+            "f" => self.parse_face(&args)?,
+            "g" | "o" => {
+                if args.is_empty() {
+                    return Err(format!("'{}' statement without a name", keyword));
+                }
+                self.groups.push(ObjGroup::new(&args.join(" ")));
+            }
+            _ => self.ignored_lines += 1,
+        }
+        Ok(())
+    }
+
+    fn parse_face(&mut self, args: &[&str]) -> Result<(), String> {
+        if args.len() < 3 {
+            return Err(format!(
+                "face needs at least 3 vertices, got {}",
+                args.len()
+            ));
+        }
+        let face = args
+            .iter()
+            .map(|arg| self.parse_face_vertex(arg))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        //Polygons are split into a fan of triangles around the first vertex.
+        let group = self.groups.last_mut().expect("Default group always exists");
+        for i in 1..face.len() - 1 {
+            let corners = [&face[0], &face[i], &face[i + 1]];
+            let [p1, p2, p3] = [
+                self.vertices[corners[0].vertex],
+                self.vertices[corners[1].vertex],
+                self.vertices[corners[2].vertex],
+            ];
+            let texture_coords = match (corners[0].texture, corners[1].texture, corners[2].texture)
+            {
+                (Some(t1), Some(t2), Some(t3)) => Some([
+                    self.texture_coords[t1],
+                    self.texture_coords[t2],
+                    self.texture_coords[t3],
+                ]),
+                _ => None,
+            };
+            let shape: Box<dyn Shape> =
+                match (corners[0].normal, corners[1].normal, corners[2].normal) {
+                    (Some(n1), Some(n2), Some(n3)) => {
+                        let mut builder = SmoothTriangleBuilder::new(
+                            p1,
+                            p2,
+                            p3,
+                            self.normals[n1],
+                            self.normals[n2],
+                            self.normals[n3],
+                        );
+                        if let Some([uv1, uv2, uv3]) = texture_coords {
+                            builder.with_texture_coords(uv1, uv2, uv3);
+                        }
+                        Box::new(builder.create().map_err(|e| e.to_string())?)
+                    }
+                    _ => {
+                        let mut builder = TriangleBuilder::new(p1, p2, p3);
+                        if let Some([uv1, uv2, uv3]) = texture_coords {
+                            builder.with_texture_coords(uv1, uv2, uv3);
+                        }
+                        Box::new(builder.create().map_err(|e| e.to_string())?)
+                    }
+                };
+            group.shapes.push(shape);
+        }
+        Ok(())
+    }
+
+    //Parses `v`, `v/vt`, `v//vn` or `v/vt/vn`.
+    fn parse_face_vertex(&self, arg: &str) -> Result<FaceVertex, String> {
+        let mut parts = arg.split('/');
+        let vertex = match parts.next() {
+            Some(index) => resolve_index(index, self.vertices.len(), "vertex")?,
+            None => return Err(format!("empty face vertex '{}'", arg)),
+        };
+        let texture = match parts.next().filter(|index| !index.is_empty()) {
+            Some(index) => Some(resolve_index(
+                index,
+                self.texture_coords.len(),
+                "texture coordinate",
+            )?),
+            None => None,
+        };
+        let normal = match parts.next().filter(|index| !index.is_empty()) {
+            Some(index) => Some(resolve_index(index, self.normals.len(), "normal")?),
+            None => None,
+        };
+        if parts.next().is_some() {
+            return Err(format!("malformed face vertex '{}'", arg));
+        }
+        Ok(FaceVertex {
+            vertex,
+            texture,
+            normal,
+        })
+    }
+}
+
+//Reads the first N numbers of a statement, any extra (like w) is ignored.
+fn parse_floats<const N: usize>(args: &[&str], what: &str) -> Result<[f32; N], String> {
+    if args.len() < N {
+        return Err(format!(
+            "{} needs {} coordinates, got {}",
+            what,
+            N,
+            args.len()
+        ));
+    }
+    let mut result = [0.0; N];
+    for (value, arg) in result.iter_mut().zip(args) {
+        *value = arg
+            .parse()
+            .map_err(|_| format!("invalid {} coordinate '{}'", what, arg))?;
+    }
+    Ok(result)
+}
+
+//OBJ indices are 1-based, negative ones count back from the last defined element.
+fn resolve_index(index: &str, count: usize, what: &str) -> Result<usize, String> {
+    let value: i64 = index
+        .parse()
+        .map_err(|_| format!("invalid {} index '{}'", what, index))?;
+    let resolved = if value > 0 {
+        value - 1
+    } else {
+        count as i64 + value
+    };
+    if value == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(format!(
+            "{} index {} out of range, {} defined so far",
+            what, value, count
+        ));
+    }
+    Ok(resolved as usize)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intersection::Intersection;
+
+    fn parse(input: &str) -> Result<ObjFile, ObjError> {
+        ObjFile::from_reader(input.as_bytes())
+    }
+
+    fn parse_error_line(input: &str) -> usize {
+        match parse(input) {
+            Err(ObjError::Parse { line, .. }) => line,
+            other => panic!("Expected a parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn ignoring_unrecognized_lines() {
+        let input = "There was a young lady named Bright\n\
+                     who traveled much faster than light.\n\
+                     usemtl shiny\n";
+        let obj = parse(input).unwrap();
+        assert_eq!(obj.ignored_lines(), 3);
+        assert!(obj.vertices().is_empty());
+    }
+
+    #[test]
+    fn vertex_records() {
+        let input = "v -1 1 0\nv -1.0000 0.5000 0.0000\nv 1 0 0\nv 1 1 0\n";
+        let obj = parse(input).unwrap();
+        assert_eq!(obj.vertices()[0], point!(-1.0, 1.0, 0.0));
+        assert_eq!(obj.vertices()[1], point!(-1.0, 0.5, 0.0));
+        assert_eq!(obj.vertices()[2], point!(1.0, 0.0, 0.0));
+        assert_eq!(obj.vertices()[3], point!(1.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn normal_and_texture_records() {
+        let input = "vn 0 0 1\nvn 0.707 0 -0.707\nvt 0.5 0.25\nvt 1 0 0\nvt 0.75\n";
+        let obj = parse(input).unwrap();
+        assert_eq!(obj.normals()[0], vector!(0.0, 0.0, 1.0));
+        assert_eq!(obj.normals()[1], vector!(0.707, 0.0, -0.707));
+        assert_eq!(
+            obj.texture_coords(),
+            &[(0.5, 0.25), (1.0, 0.0), (0.75, 0.0)]
+        );
+    }
+
+    #[test]
+    fn parsing_triangle_faces() {
+        let input = "v -1 1 0\nv -1 0 0\nv 1 0 0\nv 1 1 0\n\nf 1 2 3\nf 1 3 4\n";
+        let obj = parse(input).unwrap();
+        let group = &obj.groups()[0];
+        assert_eq!(group.name(), "");
+        assert_eq!(group.shapes().len(), 2);
+    }
+
+    #[test]
+    fn triangulating_polygons() {
+        let input = "v -1 1 0\nv -1 0 0\nv 1 0 0\nv 1 1 0\nv 0 2 0\n\nf 1 2 3 4 5\n";
+        let obj = parse(input).unwrap();
+        assert_eq!(obj.groups()[0].shapes().len(), 3);
+    }
+
+    #[test]
+    fn triangles_in_groups() {
+        let input = "v -1 1 0\nv -1 0 0\nv 1 0 0\nv 1 1 0\n\
+                     g FirstGroup\nf 1 2 3\n\
+                     o SecondGroup\nf 1 3 4\nf 1 2 4\n";
+        let obj = parse(input).unwrap();
+        assert_eq!(obj.groups().len(), 3);
+        assert_eq!(obj.group("FirstGroup").unwrap().shapes().len(), 1);
+        assert_eq!(obj.group("SecondGroup").unwrap().shapes().len(), 2);
+        assert_eq!(obj.into_shapes().len(), 3);
+    }
+
+    #[test]
+    fn converting_obj_to_group() {
+        let input = "v -1 1 0\nv -1 0 0\nv 1 0 0\nv 1 1 0\n\
+                     g FirstGroup\nf 1 2 3\n\
+                     g SecondGroup\nf 1 3 4\n";
+        let group = parse(input).unwrap().into_group();
+        assert_eq!(group.children().len(), 2);
+    }
+
+    #[test]
+    fn faces_with_normals_are_smooth() {
+        let input = "v 0 1 0\nv -1 0 0\nv 1 0 0\n\
+                     vn -1 0 0\nvn 1 0 0\nvn 0 1 0\nvt 0 0\n\
+                     f 1//3 2//1 3//2\nf 1/1/3 2/1/1 3/1/2\n";
+        let obj = parse(input).unwrap();
+        let shapes = obj.groups()[0].shapes();
+        assert_eq!(shapes.len(), 2);
+        //Interpolated normal in the middle of the p1-p2 edge.
+        let hit = Intersection::with_uv(1.0, shapes[0].as_ref(), 0.5, 0.0);
+        let normal = shapes[0].local_normal_at(&point!(-0.5, 0.5, 0.0), &hit);
+        matrix_eq!(normal, vector!(-0.5, 0.5, 0.0));
+    }
+
+    #[test]
+    fn faces_keep_texture_coords() {
+        let input = "v 0 1 0\nv -1 0 0\nv 1 0 0\nvn 0 0 -1\n\
+                     vt 0.5 1\nvt 0 0\nvt 1 0\n\
+                     f 1/1 2/2 3/3\nf 1/1/1 2/2/1 3/3/1\nf 1 2 3\nf 1/1 2 3/3\n";
+        let obj = parse(input).unwrap();
+        let shapes = obj.groups()[0].shapes();
+        for shape in &shapes[..2] {
+            assert_eq!(
+                shape.texture_coords_at(&point!(-1.0, 0.0, 0.0)),
+                Some((0.0, 0.0))
+            );
+            assert_eq!(
+                shape.texture_coords_at(&point!(0.0, 1.0, 0.0)),
+                Some((0.5, 1.0))
+            );
+        }
+        //Coordinates are used only when every vertex of the face has one.
+        assert_eq!(shapes[2].texture_coords_at(&point!(0.0, 1.0, 0.0)), None);
+        assert_eq!(shapes[3].texture_coords_at(&point!(0.0, 1.0, 0.0)), None);
+    }
+
+    #[test]
+    fn negative_indices_are_relative() {
+        let input = "v -1 1 0\nv -1 0 0\nv 1 0 0\nf -3 -2 -1\n";
+        let obj = parse(input).unwrap();
+        assert_eq!(obj.groups()[0].shapes().len(), 1);
+    }
+
+    #[test]
+    fn out_of_range_index_reports_line() {
+        assert_eq!(
+            parse_error_line("v 0 1 0\nv -1 0 0\nv 1 0 0\n\nf 1 2 4\n"),
+            5
+        );
+        assert_eq!(parse_error_line("v 0 1 0\nv -1 0 0\nf 0 1 2\n"), 3);
+        assert_eq!(
+            parse_error_line("v 0 1 0\nv -1 0 0\nv 1 0 0\nf 1//1 2 3\n"),
+            4
+        );
+        assert_eq!(
+            parse_error_line("v 0 1 0\nv -1 0 0\nv 1 0 0\nf 1/2 2 3\n"),
+            4
+        );
+    }
+
+    #[test]
+    fn malformed_statements_report_line() {
+        assert_eq!(parse_error_line("v 0 1\n"), 1);
+        assert_eq!(parse_error_line("# comment\nv 0 one 0\n"), 2);
+        assert_eq!(parse_error_line("v 0 1 0\nv -1 0 0\nf 1 2\n"), 3);
+        assert_eq!(parse_error_line("v 0 1 0\nv -1 0 0\nv 1 0 0\nf 1 x 3\n"), 4);
+        assert_eq!(parse_error_line("\n\ng\n"), 3);
+    }
+
+    #[test]
+    fn error_message_contains_line() {
+        let err = parse("v 0 1 0\nf 1 2 3\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "OBJ line 2: vertex index 2 out of range, 1 defined so far"
+        );
+    }
+}
@@ -1,93 +1,155 @@
+use crate::bounds::Bounds;
+use crate::intersection::{Intersection, Intersections};
 use crate::material::Material;
 use crate::math::*;
+use crate::transform::{Transform, TransformError};
+use std::fmt::Debug;
 use std::sync::atomic::{AtomicU32, Ordering};
 
-///TODO: Create Object trait and implement for Sphere
+mod cone;
+mod csg;
+mod cube;
+mod cylinder;
+mod group;
+mod plane;
+mod smooth_triangle;
+mod sphere;
+mod triangle;
+
+pub use cone::{Cone, ConeBuilder};
+pub use csg::{Csg, CsgBuilder, CsgOperation};
+pub use cube::{Cube, CubeBuilder};
+pub use cylinder::{Cylinder, CylinderBuilder};
+pub use group::{Group, GroupBuilder};
+pub use plane::{Plane, PlaneBuilder};
+pub use smooth_triangle::{SmoothTriangle, SmoothTriangleBuilder};
+pub use sphere::{Sphere, SphereBuilder};
+pub use triangle::{Triangle, TriangleBuilder};
+
+//Unique id for every created shape, 0 is reserved for defaults.
+pub(crate) fn next_shape_id() -> u32 {
+    static COUNTER: AtomicU32 = AtomicU32::new(1);
+    COUNTER.fetch_add(1, Ordering::Relaxed)
+}
 
-pub struct SphereBuilder {
-    transformation: Option<Mat4>,
-    material: Option<Material>,
+//Data shared by all the shapes: identity, placement in the world and surface.
+//Default properties have the reserved id 0 and identity transformations.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct ShapeProperties {
+    id: u32,
+    transformation: Transform,
+    //Composed transformations of all the groups above the shape, identity at the top level.
+    parent_transformation: Transform,
+    //parent_transformation * transformation, kept up to date when any of them changes.
+    world_transformation: Transform,
+    material: Material,
 }
 
-impl SphereBuilder {
-    pub fn new() -> SphereBuilder {
-        SphereBuilder {
-            transformation: None,
-            material: None,
-        }
+impl ShapeProperties {
+    //Fails if the transformation can't be inverted.
+    pub fn new(
+        transformation: Mat4,
+        material: Material,
+    ) -> Result<ShapeProperties, TransformError> {
+        let transformation = Transform::new(transformation)?;
+        Ok(ShapeProperties {
+            id: next_shape_id(),
+            transformation,
+            parent_transformation: Transform::identity(),
+            world_transformation: transformation,
+            material,
+        })
     }
 
-    pub fn with_transformation(&mut self, transformation: Mat4) -> &mut SphereBuilder {
-        self.transformation.replace(transformation);
-        self
+    fn set_transformation(&mut self, transformation: Transform) {
+        self.transformation = transformation;
+        self.world_transformation = self.parent_transformation * transformation;
     }
 
-    pub fn with_material(&mut self, material: Material) -> &mut SphereBuilder {
-        self.material.replace(material);
-        self
+    fn set_parent_transformation(&mut self, parent_transformation: Transform) {
+        self.parent_transformation = parent_transformation;
+        self.world_transformation = parent_transformation * self.transformation;
     }
+}
 
-    pub fn create(&mut self) -> Sphere {
-        static COUNTER: AtomicU32 = AtomicU32::new(1);
-        let result = Sphere::new(
-            COUNTER.fetch_add(1, Ordering::Relaxed),
-            self.transformation.unwrap_or_else(Mat4::identity),
-            self.material.unwrap_or_default(),
-        );
-        self.transformation = None;
-        self.material = None;
-        result
+/// Every object that can be placed in the World.
+/// Implementors work in object space only, moving rays and normals between
+/// world and object space is done once for all of them in `intersect` and `normal_at`.
+pub trait Shape: Debug {
+    fn properties(&self) -> &ShapeProperties;
+
+    fn properties_mut(&mut self) -> &mut ShapeProperties;
+
+    //Intersections of the shape with a ray that is already in object space.
+    fn local_intersect<'a>(&'a self, ray: &Ray) -> Intersections<'a>;
+
+    //Normal at the point given in object space. The hit that produced the point
+    //carries extra information (like u/v on triangles) some shapes need.
+    fn local_normal_at(&self, point: &Point4, hit: &Intersection) -> Vec4;
+
+    //Box enclosing the shape in object space, may be infinite in some directions.
+    fn bounds(&self) -> Bounds;
+
+    //Texture coordinates authored for the surface at the point given in object space,
+    //like the ones OBJ meshes carry. Most shapes have none.
+    fn texture_coords_at(&self, _point: &Point4) -> Option<(f32, f32)> {
+        None
     }
-}
 
-//Empty sphere that is placed in the center of the screen and has a radius of 1.
-#[derive(Copy, Clone, PartialEq, Debug)]
-pub struct Sphere {
-    id: u32,
-    transformation: Mat4,
-    pub material: Material,
-}
+    //Bounds in the space of the parent group (or the world for top level shapes).
+    fn parent_space_bounds(&self) -> Bounds {
+        self.bounds().transform(self.get_transformation().matrix())
+    }
 
-impl Default for Sphere {
-    fn default() -> Self {
-        Sphere {
-            id: 0,
-            transformation: Mat4::identity(),
-            material: Material::default(),
-        }
+    fn id(&self) -> u32 {
+        self.properties().id
     }
-}
 
-impl Sphere {
-    pub(self) fn new(id: u32, transformation: Mat4, material: Material) -> Sphere {
-        Sphere {
-            id,
-            transformation,
-            material,
-        }
+    //Transformation relative to the parent group (or the world for top level shapes).
+    fn get_transformation(&self) -> &Transform {
+        &self.properties().transformation
+    }
+
+    //Object to world transformation, through all the parent groups.
+    fn world_transformation(&self) -> &Transform {
+        &self.properties().world_transformation
+    }
+
+    /// This expects homogeneous matrix, a singular one is rejected and leaves the shape as it was.
+    fn transform(&mut self, transformation: &Mat4) -> Result<(), TransformError> {
+        let transformation = *self.get_transformation() * Transform::new(*transformation)?;
+        self.properties_mut().set_transformation(transformation);
+        Ok(())
+    }
+
+    //Called by the group owning the shape whenever its placement in the world changes.
+    fn set_parent_transformation(&mut self, parent_transformation: &Transform) {
+        self.properties_mut()
+            .set_parent_transformation(*parent_transformation);
     }
 
-    /// This expects homogeneous matrix
-    pub fn transform(&mut self, transformation: &Mat4) -> &mut Self {
-        self.transformation *= transformation;
-        self
+    //Is the shape with given id this shape or one of its descendants.
+    fn includes(&self, id: u32) -> bool {
+        self.id() == id
     }
 
-    pub fn get_transformation(&self) -> &Mat4 {
-        &self.transformation
+    fn material(&self) -> &Material {
+        &self.properties().material
+    }
+
+    fn set_material(&mut self, material: Material) {
+        self.properties_mut().material = material;
     }
 }
 
-pub fn normal_at(sphere: &Sphere, world_point: &Point4) -> Vec4 {
-    let inversed_sphere_transform = sphere
-        .get_transformation()
-        .try_inverse()
-        .expect("Can't inverse transformation matrix for sphere!");
+//Normal in world space. Shapes nested in groups are converted through the whole hierarchy.
+pub fn normal_at(shape: &dyn Shape, world_point: &Point4, hit: &Intersection) -> Vec4 {
+    let transformation = shape.world_transformation();
 
-    let object_point = inversed_sphere_transform * world_point;
-    let object_normal = object_point - point!(0.0, 0.0, 0.0);
+    let object_point = transformation.inverse() * world_point;
+    let object_normal = shape.local_normal_at(&object_point, hit);
 
-    let mut world_normal = inversed_sphere_transform.transpose() * object_normal;
+    let mut world_normal = transformation.inverse_transpose() * object_normal;
     world_normal.w = 0.0;
     world_normal.normalize()
 }
@@ -118,7 +180,7 @@ impl Ray {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::intersection::*;
+
     #[test]
     fn ray_position() {
         let ray = Ray::new(point!(2.0, 3.0, 4.0), vector!(1.0, 0.0, 0.0));
@@ -130,95 +192,6 @@ mod test {
         assert_eq!(point!(1.0, 3.0, 4.0), p3);
     }
 
-    #[test]
-    fn sphere_creation() {
-        let mut sb = SphereBuilder::new();
-        let s1 = sb.create();
-        let s2 = sb.create();
-        assert_ne!(s1.id, s2.id);
-    }
-
-    #[test]
-    fn ray_sphere_intersection_two_points() {
-        let ray = Ray::new(point!(0.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0));
-        let sphere = SphereBuilder::new().create();
-
-        let intersects = intersect(&ray, &sphere);
-        assert_eq!(intersects.as_ref().map(|res| res.is_empty()), Some(false));
-
-        let expected = vec![
-            Intersection::new(4.0, &sphere),
-            Intersection::new(6.0, &sphere),
-        ];
-        assert!(intersects
-            .unwrap()
-            .iter()
-            .zip(&expected)
-            .all(|(a, b)| a == b));
-    }
-
-    #[test]
-    fn ray_sphere_intersection_tangent() {
-        let ray = Ray::new(point!(0.0, 1.0, -5.0), vector!(0.0, 0.0, 1.0));
-        let sphere = SphereBuilder::new().create();
-        let intersects = intersect(&ray, &sphere);
-        assert_eq!(intersects.as_ref().map(|res| res.is_empty()), Some(false));
-
-        let expected = vec![
-            Intersection::new(5.0, &sphere),
-            Intersection::new(5.0, &sphere),
-        ];
-        assert!(intersects
-            .unwrap()
-            .iter()
-            .zip(&expected)
-            .all(|(a, b)| a == b));
-    }
-
-    #[test]
-    fn ray_sphere_intersection_none() {
-        let ray = Ray::new(point!(0.0, 2.0, -5.0), vector!(0.0, 0.0, 1.0));
-        let sphere = SphereBuilder::new().create();
-        let inter = intersect(&ray, &sphere);
-        assert_eq!(inter.is_none(), true);
-    }
-
-    #[test]
-    fn ray_origin_inside_sphere() {
-        let ray = Ray::new(point!(0.0, 0.0, 0.0), vector!(0.0, 0.0, 1.0));
-        let sphere = SphereBuilder::new().create();
-        let intersects = intersect(&ray, &sphere);
-        assert_eq!(intersects.as_ref().map(|res| res.is_empty()), Some(false));
-
-        let expected = vec![
-            Intersection::new(-1.0, &sphere),
-            Intersection::new(1.0, &sphere),
-        ];
-        assert!(intersects
-            .unwrap()
-            .iter()
-            .zip(&expected)
-            .all(|(a, b)| a == b));
-    }
-
-    #[test]
-    fn ray_origin_behind_sphere() {
-        let ray = Ray::new(point!(0.0, 0.0, 5.0), vector!(0.0, 0.0, 1.0));
-        let sphere = SphereBuilder::new().create();
-        let intersects = intersect(&ray, &sphere);
-        assert_eq!(intersects.as_ref().map(|res| res.is_empty()), Some(false));
-
-        let expected = vec![
-            Intersection::new(-6.0, &sphere),
-            Intersection::new(-4.0, &sphere),
-        ];
-        assert!(intersects
-            .unwrap()
-            .iter()
-            .zip(&expected)
-            .all(|(a, b)| a == b));
-    }
-
     #[test]
     fn ray_translation() {
         let r = Ray::new(point!(1.0, 2.0, 3.0), vector!(0.0, 1.0, 0.0));
@@ -238,57 +211,55 @@ mod test {
     }
 
     #[test]
-    fn sphere_default_transformation_matrix() {
-        let sphere = SphereBuilder::new().create();
-        assert_eq!(sphere.get_transformation(), &Mat4::identity());
-    }
-
-    #[test]
-    fn shpere_translate() {
-        let t = translation!(2.0, 3.0, 4.0);
-        let sphere = SphereBuilder::new().with_transformation(t).create();
-        assert_eq!(&t, sphere.get_transformation());
+    fn shape_ids_are_unique() {
+        let p1 = ShapeProperties::new(Mat4::identity(), Material::default()).unwrap();
+        let p2 = ShapeProperties::new(Mat4::identity(), Material::default()).unwrap();
+        assert_ne!(p1.id, p2.id);
     }
 
     #[test]
-    fn normal_at_sphere() {
-        let sphere = SphereBuilder::new().create();
-        let point = point!(1.0, 0.0, 0.0);
-        let normal = normal_at(&sphere, &point);
-        assert_eq!(normal, vector!(1.0, 0.0, 0.0));
-        let point = point!(0.0, 1.0, 0.0);
-        let normal = normal_at(&sphere, &point);
-        assert_eq!(normal, Vec4::new(0.0, 1.0, 0.0, 0.0));
-        let sq = 3f32.sqrt() / 3.0;
-        let point = point!(sq, sq, sq);
-        let normal = normal_at(&sphere, &point);
-        matrix_eq!(normal, vector!(sq, sq, sq));
+    fn shape_set_material() {
+        let mut sphere = SphereBuilder::new().create().unwrap();
+        let material = Material::default_with_color(Color::red());
+        sphere.set_material(material.clone());
+        assert_eq!(sphere.material(), &material);
     }
 
     #[test]
-    fn normal_is_normalized() {
-        let sphere = SphereBuilder::new().create();
-        let sq = 3f32.sqrt() / 3.0;
-        let point = point!(sq, sq, sq);
-        let normal = normal_at(&sphere, &point);
-        matrix_eq!(normal, normal.normalize());
+    fn singular_transformation_is_rejected_by_builder() {
+        let singular = scaling!(0.0, 1.0, 1.0);
+        let result = SphereBuilder::new().with_transformation(singular).create();
+        assert_eq!(result.unwrap_err(), TransformError::Singular(singular));
+        let result = GroupBuilder::new().with_transformation(singular).create();
+        assert!(result.is_err());
     }
 
     #[test]
-    fn normal_on_translated_sphere() {
-        use std::f32::consts::FRAC_1_SQRT_2;
-        let mut sphere = SphereBuilder::new().create();
-        sphere.transform(&translation!(0.0, 1.0, 0.0));
-        let normal = normal_at(&sphere, &point!(0.0, 1.70711, -FRAC_1_SQRT_2));
-        matrix_eq!(normal, vector!(0.0, FRAC_1_SQRT_2, -FRAC_1_SQRT_2));
+    fn singular_transformation_leaves_shape_unchanged() {
+        let mut sphere = SphereBuilder::new()
+            .with_transformation(translation!(1.0, 0.0, 0.0))
+            .create()
+            .unwrap();
+        assert!(sphere.transform(&scaling!(1.0, 1.0, 0.0)).is_err());
+        assert_eq!(
+            sphere.get_transformation().matrix(),
+            &translation!(1.0, 0.0, 0.0)
+        );
     }
 
     #[test]
-    fn normal_on_scaled_rotated_sphere() {
-        let m = scaling!(1.0, 0.5, 1.0) * rotation!(0.0, 0.0, std::f32::consts::PI / 5.0);
-        let sphere = SphereBuilder::new().with_transformation(m).create();
-        let sq = 2.0f32.sqrt() / 2.0;
-        let normal = normal_at(&sphere, &point!(0.0, sq, -sq));
-        matrix_eq!(normal, vector!(0.0, 0.97014, -0.24254));
+    fn world_transformation_follows_parent() {
+        let mut sphere = SphereBuilder::new()
+            .with_transformation(scaling!(2.0, 2.0, 2.0))
+            .create()
+            .unwrap();
+        let parent = Transform::new(translation!(0.0, 5.0, 0.0)).unwrap();
+        sphere.set_parent_transformation(&parent);
+        let expected = translation!(0.0, 5.0, 0.0) * scaling!(2.0, 2.0, 2.0);
+        matrix_eq!(sphere.world_transformation().matrix(), expected);
+        matrix_eq!(
+            sphere.world_transformation().inverse(),
+            expected.try_inverse().unwrap()
+        );
     }
 }
@@ -0,0 +1,341 @@
+use crate::bounds::Bounds;
+use crate::intersection::{Intersection, Intersections};
+use crate::material::Material;
+use crate::math::*;
+use crate::objects::{Ray, Shape, ShapeProperties};
+use crate::transform::TransformError;
+
+pub struct ConeBuilder {
+    transformation: Option<Mat4>,
+    material: Option<Material>,
+    minimum: Option<f32>,
+    maximum: Option<f32>,
+    closed: bool,
+}
+
+impl ConeBuilder {
+    pub fn new() -> ConeBuilder {
+        ConeBuilder {
+            transformation: None,
+            material: None,
+            minimum: None,
+            maximum: None,
+            closed: false,
+        }
+    }
+
+    pub fn with_transformation(&mut self, transformation: Mat4) -> &mut ConeBuilder {
+        self.transformation.replace(transformation);
+        self
+    }
+
+    pub fn with_material(&mut self, material: Material) -> &mut ConeBuilder {
+        self.material.replace(material);
+        self
+    }
+
+    //Lower y bound (exclusive) of the cone in object space.
+    pub fn with_minimum(&mut self, minimum: f32) -> &mut ConeBuilder {
+        self.minimum.replace(minimum);
+        self
+    }
+
+    //Upper y bound (exclusive) of the cone in object space.
+    pub fn with_maximum(&mut self, maximum: f32) -> &mut ConeBuilder {
+        self.maximum.replace(maximum);
+        self
+    }
+
+    //Whether the truncated ends are capped.
+    pub fn with_closed(&mut self, closed: bool) -> &mut ConeBuilder {
+        self.closed = closed;
+        self
+    }
+
+    pub fn create(&mut self) -> Result<Cone, TransformError> {
+        let result = Cone::new(
+            ShapeProperties::new(
+                self.transformation.unwrap_or_else(Mat4::identity),
+                self.material.clone().unwrap_or_default(),
+            )?,
+            self.minimum.unwrap_or(f32::NEG_INFINITY),
+            self.maximum.unwrap_or(f32::INFINITY),
+            self.closed,
+        );
+        self.transformation = None;
+        self.material = None;
+        self.minimum = None;
+        self.maximum = None;
+        self.closed = false;
+        Ok(result)
+    }
+}
+
+impl Default for ConeBuilder {
+    fn default() -> Self {
+        ConeBuilder::new()
+    }
+}
+
+//Double-napped cone around the y axis with its tip in the origin,
+//radius at any height y is |y|. Optionally truncated and capped.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Cone {
+    properties: ShapeProperties,
+    minimum: f32,
+    maximum: f32,
+    closed: bool,
+}
+
+impl Default for Cone {
+    fn default() -> Self {
+        Cone {
+            properties: ShapeProperties::default(),
+            minimum: f32::NEG_INFINITY,
+            maximum: f32::INFINITY,
+            closed: false,
+        }
+    }
+}
+
+impl Cone {
+    fn new(properties: ShapeProperties, minimum: f32, maximum: f32, closed: bool) -> Cone {
+        Cone {
+            properties,
+            minimum,
+            maximum,
+            closed,
+        }
+    }
+
+    pub fn minimum(&self) -> f32 {
+        self.minimum
+    }
+
+    pub fn maximum(&self) -> f32 {
+        self.maximum
+    }
+
+    pub fn closed(&self) -> bool {
+        self.closed
+    }
+
+    fn intersect_caps<'a>(&'a self, ray: &Ray, intersections: &mut Intersections<'a>) {
+        //Caps matter only for closed cones that the ray is not parallel to.
+        if !self.closed || ray.direction.y.abs() < EPSILON {
+            return;
+        }
+        //An unbounded end has no cap.
+        for cap in [self.minimum, self.maximum]
+            .iter()
+            .filter(|cap| cap.is_finite())
+        {
+            let t = (cap - ray.origin.y) / ray.direction.y;
+            if check_cap(ray, t, cap.abs()) {
+                intersections.push(Intersection::new(t, self));
+            }
+        }
+    }
+
+    fn push_if_within_bounds<'a>(
+        &'a self,
+        ray: &Ray,
+        t: f32,
+        intersections: &mut Intersections<'a>,
+    ) {
+        let y = ray.origin.y + t * ray.direction.y;
+        if self.minimum < y && y < self.maximum {
+            intersections.push(Intersection::new(t, self));
+        }
+    }
+}
+
+//Is the point at t within the given cap radius.
+fn check_cap(ray: &Ray, t: f32, radius: f32) -> bool {
+    let x = ray.origin.x + t * ray.direction.x;
+    let z = ray.origin.z + t * ray.direction.z;
+    x.powi(2) + z.powi(2) <= radius.powi(2) + EPSILON
+}
+
+impl Shape for Cone {
+    fn properties(&self) -> &ShapeProperties {
+        &self.properties
+    }
+
+    fn properties_mut(&mut self) -> &mut ShapeProperties {
+        &mut self.properties
+    }
+
+    fn local_intersect<'a>(&'a self, ray: &Ray) -> Intersections<'a> {
+        let mut intersections = Intersections::new();
+        let (o, d) = (&ray.origin, &ray.direction);
+        let a = d.x.powi(2) - d.y.powi(2) + d.z.powi(2);
+        let b = 2.0 * o.x * d.x - 2.0 * o.y * d.y + 2.0 * o.z * d.z;
+        let c = o.x.powi(2) - o.y.powi(2) + o.z.powi(2);
+
+        if a.abs() < EPSILON {
+            //Ray parallel to one of the halves hits the other one only once.
+            if b.abs() >= EPSILON {
+                self.push_if_within_bounds(ray, -c / (2.0 * b), &mut intersections);
+            }
+        } else {
+            let discriminant = b.powi(2) - 4.0 * a * c;
+            //Tolerate rounding errors for rays grazing the surface.
+            if discriminant < -EPSILON {
+                return intersections;
+            }
+            let sqrt_discriminant = discriminant.max(0.0).sqrt();
+            let t0 = (-b - sqrt_discriminant) / (2.0 * a);
+            let t1 = (-b + sqrt_discriminant) / (2.0 * a);
+            let (t0, t1) = if t0 > t1 { (t1, t0) } else { (t0, t1) };
+            self.push_if_within_bounds(ray, t0, &mut intersections);
+            self.push_if_within_bounds(ray, t1, &mut intersections);
+        }
+        self.intersect_caps(ray, &mut intersections);
+        intersections
+    }
+
+    fn local_normal_at(&self, point: &Point4, _hit: &Intersection) -> Vec4 {
+        let dist = point.x.powi(2) + point.z.powi(2);
+        if dist < self.maximum.powi(2) && point.y >= self.maximum - EPSILON {
+            vector!(0.0, 1.0, 0.0)
+        } else if dist < self.minimum.powi(2) && point.y <= self.minimum + EPSILON {
+            vector!(0.0, -1.0, 0.0)
+        } else {
+            let y = dist.sqrt();
+            let y = if point.y > 0.0 { -y } else { y };
+            vector!(point.x, y, point.z)
+        }
+    }
+
+    //Radius of the cone at y is |y|, so the widest part is at the end further from the tip.
+    fn bounds(&self) -> Bounds {
+        let limit = self.minimum.abs().max(self.maximum.abs());
+        Bounds::new(
+            point!(-limit, self.minimum, -limit),
+            point!(limit, self.maximum, limit),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ray_strikes_cone() {
+        let cone = ConeBuilder::new().create().unwrap();
+        let cases = [
+            (point!(0.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0), 5.0, 5.0),
+            (
+                point!(0.0, 0.0, -5.0),
+                vector!(1.0, 1.0, 1.0),
+                8.66025,
+                8.66025,
+            ),
+            (
+                point!(1.0, 1.0, -5.0),
+                vector!(-0.5, -1.0, 1.0),
+                4.55006,
+                49.44994,
+            ),
+        ];
+        for (origin, direction, t0, t1) in cases.iter() {
+            let ray = Ray::new(*origin, direction.normalize());
+            let xs = cone.local_intersect(&ray);
+            assert_eq!(xs.len(), 2);
+            assert!((xs[0].t - t0).abs() < 0.001);
+            assert!((xs[1].t - t1).abs() < 0.001);
+        }
+    }
+
+    #[test]
+    fn ray_parallel_to_cone_half() {
+        let cone = ConeBuilder::new().create().unwrap();
+        let ray = Ray::new(point!(0.0, 0.0, -1.0), vector!(0.0, 1.0, 1.0).normalize());
+        let xs = cone.local_intersect(&ray);
+        assert_eq!(xs.len(), 1);
+        assert!((xs[0].t - 0.35355).abs() < EPSILON);
+    }
+
+    #[test]
+    fn intersecting_cone_caps() {
+        let cone = ConeBuilder::new()
+            .with_minimum(-0.5)
+            .with_maximum(0.5)
+            .with_closed(true)
+            .create()
+            .unwrap();
+        let cases = [
+            (point!(0.0, 0.0, -5.0), vector!(0.0, 1.0, 0.0), 0),
+            (point!(0.0, 0.0, -0.25), vector!(0.0, 1.0, 1.0), 2),
+            (point!(0.0, 0.0, -0.25), vector!(0.0, 1.0, 0.0), 4),
+        ];
+        for (origin, direction, count) in cases.iter() {
+            let ray = Ray::new(*origin, direction.normalize());
+            assert_eq!(cone.local_intersect(&ray).len(), *count);
+        }
+    }
+
+    #[test]
+    fn closed_unbounded_cone_has_no_caps() {
+        let cone = ConeBuilder::new()
+            .with_minimum(-1.0)
+            .with_closed(true)
+            .create()
+            .unwrap();
+        let ray = Ray::new(point!(0.0, -3.0, -0.5), vector!(0.0, 1.0, 0.0));
+        let xs = cone.local_intersect(&ray);
+        assert_eq!(xs.len(), 3);
+        assert!(xs.iter().any(|x| x.t == 2.0));
+        let cone = ConeBuilder::new().with_closed(true).create().unwrap();
+        assert_eq!(cone.local_intersect(&ray).len(), 2);
+    }
+
+    #[test]
+    fn normal_on_cone() {
+        let cone = ConeBuilder::new().create().unwrap();
+        let cases = [
+            (point!(0.0, 0.0, 0.0), vector!(0.0, 0.0, 0.0)),
+            (point!(1.0, 1.0, 1.0), vector!(1.0, -(2.0f32.sqrt()), 1.0)),
+            (point!(-1.0, -1.0, 0.0), vector!(-1.0, 1.0, 0.0)),
+        ];
+        for (point, normal) in cases.iter() {
+            matrix_eq!(
+                cone.local_normal_at(point, &Intersection::new(0.0, &cone)),
+                normal
+            );
+        }
+    }
+
+    #[test]
+    fn normal_on_cone_caps() {
+        let cone = ConeBuilder::new()
+            .with_minimum(-1.0)
+            .with_maximum(1.0)
+            .with_closed(true)
+            .create()
+            .unwrap();
+        matrix_eq!(
+            cone.local_normal_at(&point!(0.5, 1.0, 0.0), &Intersection::new(0.0, &cone)),
+            vector!(0.0, 1.0, 0.0)
+        );
+        matrix_eq!(
+            cone.local_normal_at(&point!(0.0, -1.0, 0.5), &Intersection::new(0.0, &cone)),
+            vector!(0.0, -1.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn cone_bounds() {
+        let cone = ConeBuilder::new().create().unwrap();
+        assert!(!cone.bounds().is_finite());
+        let cone = ConeBuilder::new()
+            .with_minimum(-5.0)
+            .with_maximum(3.0)
+            .create()
+            .unwrap();
+        assert_eq!(cone.bounds().min, point!(-5.0, -5.0, -5.0));
+        assert_eq!(cone.bounds().max, point!(5.0, 3.0, 5.0));
+    }
+}
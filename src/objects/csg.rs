@@ -0,0 +1,393 @@
+use crate::bounds::Bounds;
+use crate::intersection::{intersect, Intersection, Intersections};
+use crate::material::Material;
+use crate::math::*;
+use crate::objects::{Ray, Shape, ShapeProperties};
+use crate::transform::{Transform, TransformError};
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    Difference,
+}
+
+impl CsgOperation {
+    //Decide whether a hit on the boundary of one side is a hit on the combined shape.
+    //`left_hit` tells which side was hit, `inside_left` and `inside_right` whether
+    //the ray was inside of each side just before this hit.
+    pub fn allows(self, left_hit: bool, inside_left: bool, inside_right: bool) -> bool {
+        match self {
+            CsgOperation::Union => (left_hit && !inside_right) || (!left_hit && !inside_left),
+            CsgOperation::Intersection => (left_hit && inside_right) || (!left_hit && inside_left),
+            CsgOperation::Difference => (left_hit && !inside_right) || (!left_hit && inside_left),
+        }
+    }
+}
+
+//Takes the builder by value, unlike the other builders, because the operands are
+//moved into the created shape. A used up builder can't be created from again.
+pub struct CsgBuilder {
+    operation: CsgOperation,
+    left: Box<dyn Shape>,
+    right: Box<dyn Shape>,
+    transformation: Option<Mat4>,
+    material: Option<Material>,
+}
+
+impl CsgBuilder {
+    pub fn new(operation: CsgOperation, left: Box<dyn Shape>, right: Box<dyn Shape>) -> CsgBuilder {
+        CsgBuilder {
+            operation,
+            left,
+            right,
+            transformation: None,
+            material: None,
+        }
+    }
+
+    pub fn with_transformation(mut self, transformation: Mat4) -> CsgBuilder {
+        self.transformation.replace(transformation);
+        self
+    }
+
+    pub fn with_material(mut self, material: Material) -> CsgBuilder {
+        self.material.replace(material);
+        self
+    }
+
+    pub fn create(self) -> Result<Csg, TransformError> {
+        let mut result = Csg {
+            properties: ShapeProperties::new(
+                self.transformation.unwrap_or_else(Mat4::identity),
+                self.material.unwrap_or_default(),
+            )?,
+            operation: self.operation,
+            left: self.left,
+            right: self.right,
+        };
+        result.update_children();
+        Ok(result)
+    }
+}
+
+//Constructive solid geometry: two shapes combined with a boolean operation.
+#[derive(Debug)]
+pub struct Csg {
+    properties: ShapeProperties,
+    operation: CsgOperation,
+    left: Box<dyn Shape>,
+    right: Box<dyn Shape>,
+}
+
+impl Csg {
+    pub fn operation(&self) -> CsgOperation {
+        self.operation
+    }
+
+    pub fn left(&self) -> &dyn Shape {
+        self.left.as_ref()
+    }
+
+    pub fn right(&self) -> &dyn Shape {
+        self.right.as_ref()
+    }
+
+    //Keep only the intersections that lie on the surface of the combined shape.
+    //Expects intersections sorted by t.
+    pub fn filter_intersections<'a>(&self, intersections: Intersections<'a>) -> Intersections<'a> {
+        let mut inside_left = false;
+        let mut inside_right = false;
+        let mut result = Intersections::new();
+        for intersection in intersections {
+            let left_hit = self.left.includes(intersection.obj.id());
+            if self.operation.allows(left_hit, inside_left, inside_right) {
+                result.push(intersection);
+            }
+            if left_hit {
+                inside_left = !inside_left;
+            } else {
+                inside_right = !inside_right;
+            }
+        }
+        result
+    }
+
+    fn update_children(&mut self) {
+        let world_transformation = *self.world_transformation();
+        self.left.set_parent_transformation(&world_transformation);
+        self.right.set_parent_transformation(&world_transformation);
+    }
+}
+
+impl Shape for Csg {
+    fn properties(&self) -> &ShapeProperties {
+        &self.properties
+    }
+
+    fn properties_mut(&mut self) -> &mut ShapeProperties {
+        &mut self.properties
+    }
+
+    fn local_intersect<'a>(&'a self, ray: &Ray) -> Intersections<'a> {
+        let mut intersections = intersect(ray, self.left.as_ref()).unwrap_or_default();
+        if let Some(mut right) = intersect(ray, self.right.as_ref()) {
+            intersections.append(&mut right);
+        }
+        intersections.sort();
+        self.filter_intersections(intersections)
+    }
+
+    fn local_normal_at(&self, _point: &Point4, _hit: &Intersection) -> Vec4 {
+        unreachable!("Intersections are always reported for the operands, not the CSG!")
+    }
+
+    fn bounds(&self) -> Bounds {
+        let mut bounds = self.left.parent_space_bounds();
+        bounds.merge(&self.right.parent_space_bounds());
+        bounds
+    }
+
+    fn includes(&self, id: u32) -> bool {
+        self.id() == id || self.left.includes(id) || self.right.includes(id)
+    }
+
+    fn transform(&mut self, transformation: &Mat4) -> Result<(), TransformError> {
+        let transformation = self.properties.transformation * Transform::new(*transformation)?;
+        self.properties.set_transformation(transformation);
+        self.update_children();
+        Ok(())
+    }
+
+    fn set_parent_transformation(&mut self, parent_transformation: &Transform) {
+        self.properties
+            .set_parent_transformation(*parent_transformation);
+        self.update_children();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::objects::{CubeBuilder, CylinderBuilder, GroupBuilder, SphereBuilder};
+
+    #[test]
+    fn csg_created_from_two_shapes() {
+        let s1 = SphereBuilder::new().create().unwrap();
+        let s2 = CubeBuilder::new().create().unwrap();
+        let (id1, id2) = (s1.id(), s2.id());
+        let csg = CsgBuilder::new(CsgOperation::Union, Box::new(s1), Box::new(s2))
+            .create()
+            .unwrap();
+        assert_eq!(csg.operation(), CsgOperation::Union);
+        assert_eq!(csg.left().id(), id1);
+        assert_eq!(csg.right().id(), id2);
+        assert!(csg.includes(id1));
+        assert!(csg.includes(id2));
+    }
+
+    #[test]
+    fn csg_builder_with_material() {
+        let material = Material {
+            ambient: 0.5,
+            ..Material::default()
+        };
+        let csg = CsgBuilder::new(
+            CsgOperation::Union,
+            Box::new(SphereBuilder::new().create().unwrap()),
+            Box::new(CubeBuilder::new().create().unwrap()),
+        )
+        .with_material(material.clone())
+        .create()
+        .unwrap();
+        assert_eq!(csg.material(), &material);
+    }
+
+    #[test]
+    fn csg_operation_rules() {
+        use CsgOperation::*;
+        let cases = [
+            (Union, true, true, true, false),
+            (Union, true, true, false, true),
+            (Union, true, false, true, false),
+            (Union, true, false, false, true),
+            (Union, false, true, true, false),
+            (Union, false, true, false, false),
+            (Union, false, false, true, true),
+            (Union, false, false, false, true),
+            (Intersection, true, true, true, true),
+            (Intersection, true, true, false, false),
+            (Intersection, true, false, true, true),
+            (Intersection, true, false, false, false),
+            (Intersection, false, true, true, true),
+            (Intersection, false, true, false, true),
+            (Intersection, false, false, true, false),
+            (Intersection, false, false, false, false),
+            (Difference, true, true, true, false),
+            (Difference, true, true, false, true),
+            (Difference, true, false, true, false),
+            (Difference, true, false, false, true),
+            (Difference, false, true, true, true),
+            (Difference, false, true, false, true),
+            (Difference, false, false, true, false),
+            (Difference, false, false, false, false),
+        ];
+        for (operation, left_hit, inside_left, inside_right, expected) in cases.iter() {
+            assert_eq!(
+                operation.allows(*left_hit, *inside_left, *inside_right),
+                *expected,
+                "{:?} {} {} {}",
+                operation,
+                left_hit,
+                inside_left,
+                inside_right
+            );
+        }
+    }
+
+    #[test]
+    fn filtering_intersections() {
+        let cases = [
+            (CsgOperation::Union, 0, 3),
+            (CsgOperation::Intersection, 1, 2),
+            (CsgOperation::Difference, 0, 1),
+        ];
+        for (operation, x0, x1) in cases.iter() {
+            let s1 = SphereBuilder::new().create().unwrap();
+            let s2 = CubeBuilder::new().create().unwrap();
+            let csg = CsgBuilder::new(*operation, Box::new(s1), Box::new(s2))
+                .create()
+                .unwrap();
+            let (left, right) = (csg.left(), csg.right());
+            let xs = vec![
+                Intersection::new(1.0, left),
+                Intersection::new(2.0, right),
+                Intersection::new(3.0, left),
+                Intersection::new(4.0, right),
+            ];
+            let result = csg.filter_intersections(xs.clone());
+            assert_eq!(result.len(), 2);
+            assert_eq!(result[0], xs[*x0]);
+            assert_eq!(result[1], xs[*x1]);
+        }
+    }
+
+    #[test]
+    fn ray_misses_csg() {
+        let csg = CsgBuilder::new(
+            CsgOperation::Union,
+            Box::new(SphereBuilder::new().create().unwrap()),
+            Box::new(CubeBuilder::new().create().unwrap()),
+        )
+        .create()
+        .unwrap();
+        let ray = Ray::new(point!(0.0, 2.0, -5.0), vector!(0.0, 0.0, 1.0));
+        assert!(csg.local_intersect(&ray).is_empty());
+    }
+
+    #[test]
+    fn ray_hits_csg() {
+        let s1 = SphereBuilder::new().create().unwrap();
+        let s2 = SphereBuilder::new()
+            .with_transformation(translation!(0.0, 0.0, 0.5))
+            .create()
+            .unwrap();
+        let (id1, id2) = (s1.id(), s2.id());
+        let csg = CsgBuilder::new(CsgOperation::Union, Box::new(s1), Box::new(s2))
+            .create()
+            .unwrap();
+        let ray = Ray::new(point!(0.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0));
+        let xs = csg.local_intersect(&ray);
+        assert_eq!(xs.len(), 2);
+        assert_eq!(xs[0].t, 4.0);
+        assert_eq!(xs[0].obj.id(), id1);
+        assert_eq!(xs[1].t, 6.5);
+        assert_eq!(xs[1].obj.id(), id2);
+    }
+
+    #[test]
+    fn sphere_with_cylindrical_hole() {
+        let sphere = SphereBuilder::new().create().unwrap();
+        let drill = CylinderBuilder::new()
+            .with_transformation(scaling!(0.5, 1.0, 0.5))
+            .with_minimum(-2.0)
+            .with_maximum(2.0)
+            .with_closed(true)
+            .create()
+            .unwrap();
+        let drill_id = drill.id();
+        let csg = CsgBuilder::new(CsgOperation::Difference, Box::new(sphere), Box::new(drill))
+            .create()
+            .unwrap();
+        //Straight through the hole there is nothing to hit.
+        let ray = Ray::new(point!(0.0, -5.0, 0.0), vector!(0.0, 1.0, 0.0));
+        assert!(intersect(&ray, &csg).is_none());
+        //From the side the ray crosses the sphere, the hole wall twice and the sphere again.
+        let ray = Ray::new(point!(-5.0, 0.0, 0.0), vector!(1.0, 0.0, 0.0));
+        let xs = intersect(&ray, &csg).expect("Expected to hit the drilled sphere!");
+        let ts: Vec<f32> = xs.iter().map(|x| x.t).collect();
+        assert_eq!(ts, vec![4.0, 4.5, 5.5, 6.0]);
+        assert_eq!(xs[1].obj.id(), drill_id);
+    }
+
+    #[test]
+    fn nested_csg() {
+        //(sphere - cube) with a second sphere added on top of it.
+        let inner = CsgBuilder::new(
+            CsgOperation::Difference,
+            Box::new(SphereBuilder::new().create().unwrap()),
+            Box::new(
+                CubeBuilder::new()
+                    .with_transformation(translation!(0.0, 0.0, -1.0))
+                    .create()
+                    .unwrap(),
+            ),
+        )
+        .create()
+        .unwrap();
+        let outer = CsgBuilder::new(
+            CsgOperation::Union,
+            Box::new(inner),
+            Box::new(
+                SphereBuilder::new()
+                    .with_transformation(translation!(0.0, 0.0, 3.0))
+                    .create()
+                    .unwrap(),
+            ),
+        )
+        .with_transformation(translation!(0.0, 0.0, 1.0))
+        .create()
+        .unwrap();
+        let ray = Ray::new(point!(0.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0));
+        let xs = intersect(&ray, &outer).expect("Expected to hit the nested CSG!");
+        let ts: Vec<f32> = xs.iter().map(|x| x.t).collect();
+        //Front half of the first sphere is cut away by the cube.
+        assert_eq!(ts, vec![6.0, 7.0, 8.0, 10.0]);
+    }
+
+    #[test]
+    fn csg_inside_group() {
+        let csg = CsgBuilder::new(
+            CsgOperation::Intersection,
+            Box::new(SphereBuilder::new().create().unwrap()),
+            Box::new(
+                SphereBuilder::new()
+                    .with_transformation(translation!(0.0, 0.0, 1.0))
+                    .create()
+                    .unwrap(),
+            ),
+        )
+        .create()
+        .unwrap();
+        let group = GroupBuilder::new()
+            .with_transformation(translation!(0.0, 0.0, 10.0))
+            .with_child(Box::new(csg))
+            .create()
+            .unwrap();
+        let ray = Ray::new(point!(0.0, 0.0, 0.0), vector!(0.0, 0.0, 1.0));
+        let xs = intersect(&ray, &group).expect("Expected to hit the lens!");
+        let ts: Vec<f32> = xs.iter().map(|x| x.t).collect();
+        assert_eq!(ts, vec![10.0, 11.0]);
+        assert!(group.includes(xs[0].obj.id()));
+    }
+}
@@ -0,0 +1,202 @@
+use crate::bounds::Bounds;
+use crate::intersection::{Intersection, Intersections};
+use crate::material::Material;
+use crate::math::*;
+use crate::objects::{Ray, Shape, ShapeProperties};
+use crate::transform::TransformError;
+
+pub struct CubeBuilder {
+    transformation: Option<Mat4>,
+    material: Option<Material>,
+}
+
+impl CubeBuilder {
+    pub fn new() -> CubeBuilder {
+        CubeBuilder {
+            transformation: None,
+            material: None,
+        }
+    }
+
+    pub fn with_transformation(&mut self, transformation: Mat4) -> &mut CubeBuilder {
+        self.transformation.replace(transformation);
+        self
+    }
+
+    pub fn with_material(&mut self, material: Material) -> &mut CubeBuilder {
+        self.material.replace(material);
+        self
+    }
+
+    pub fn create(&mut self) -> Result<Cube, TransformError> {
+        let result = Cube::new(ShapeProperties::new(
+            self.transformation.unwrap_or_else(Mat4::identity),
+            self.material.clone().unwrap_or_default(),
+        )?);
+        self.transformation = None;
+        self.material = None;
+        Ok(result)
+    }
+}
+
+impl Default for CubeBuilder {
+    fn default() -> Self {
+        CubeBuilder::new()
+    }
+}
+
+//Axis aligned cube centered at the origin, spanning from -1 to 1 on every axis.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Cube {
+    properties: ShapeProperties,
+}
+
+impl Cube {
+    fn new(properties: ShapeProperties) -> Cube {
+        Cube { properties }
+    }
+}
+
+impl Shape for Cube {
+    fn properties(&self) -> &ShapeProperties {
+        &self.properties
+    }
+
+    fn properties_mut(&mut self) -> &mut ShapeProperties {
+        &mut self.properties
+    }
+
+    fn local_intersect<'a>(&'a self, ray: &Ray) -> Intersections<'a> {
+        let (xtmin, xtmax) = check_axis(ray.origin.x, ray.direction.x);
+        let (ytmin, ytmax) = check_axis(ray.origin.y, ray.direction.y);
+        let (ztmin, ztmax) = check_axis(ray.origin.z, ray.direction.z);
+
+        let tmin = xtmin.max(ytmin).max(ztmin);
+        let tmax = xtmax.min(ytmax).min(ztmax);
+        if tmin > tmax {
+            return Intersections::new();
+        }
+        vec![Intersection::new(tmin, self), Intersection::new(tmax, self)]
+    }
+
+    fn local_normal_at(&self, point: &Point4, _hit: &Intersection) -> Vec4 {
+        let (x, y, z) = (point.x.abs(), point.y.abs(), point.z.abs());
+        let maxc = x.max(y).max(z);
+        if (maxc - x).abs() < f32::EPSILON {
+            vector!(point.x, 0.0, 0.0)
+        } else if (maxc - y).abs() < f32::EPSILON {
+            vector!(0.0, point.y, 0.0)
+        } else {
+            vector!(0.0, 0.0, point.z)
+        }
+    }
+
+    fn bounds(&self) -> Bounds {
+        Bounds::new(point!(-1.0, -1.0, -1.0), point!(1.0, 1.0, 1.0))
+    }
+}
+
+//Distances at which the ray enters and leaves the slab between -1 and 1 on one axis.
+fn check_axis(origin: f32, direction: f32) -> (f32, f32) {
+    let tmin_numerator = -1.0 - origin;
+    let tmax_numerator = 1.0 - origin;
+
+    //Parallel rays get infinities of the right sign, so the slab test still works.
+    let (tmin, tmax) = if direction.abs() >= EPSILON {
+        (tmin_numerator / direction, tmax_numerator / direction)
+    } else {
+        (
+            tmin_numerator * f32::INFINITY,
+            tmax_numerator * f32::INFINITY,
+        )
+    };
+    if tmin > tmax {
+        (tmax, tmin)
+    } else {
+        (tmin, tmax)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intersection::intersect;
+    use crate::objects::normal_at;
+
+    #[test]
+    fn ray_intersects_cube() {
+        let cube = CubeBuilder::new().create().unwrap();
+        let cases = [
+            (point!(5.0, 0.5, 0.0), vector!(-1.0, 0.0, 0.0), 4.0, 6.0),
+            (point!(-5.0, 0.5, 0.0), vector!(1.0, 0.0, 0.0), 4.0, 6.0),
+            (point!(0.5, 5.0, 0.0), vector!(0.0, -1.0, 0.0), 4.0, 6.0),
+            (point!(0.5, -5.0, 0.0), vector!(0.0, 1.0, 0.0), 4.0, 6.0),
+            (point!(0.5, 0.0, 5.0), vector!(0.0, 0.0, -1.0), 4.0, 6.0),
+            (point!(0.5, 0.0, -5.0), vector!(0.0, 0.0, 1.0), 4.0, 6.0),
+            (point!(0.0, 0.5, 0.0), vector!(0.0, 0.0, 1.0), -1.0, 1.0),
+        ];
+        for (origin, direction, t1, t2) in cases.iter() {
+            let ray = Ray::new(*origin, *direction);
+            let xs = cube.local_intersect(&ray);
+            assert_eq!(xs.len(), 2);
+            assert_eq!(xs[0].t, *t1);
+            assert_eq!(xs[1].t, *t2);
+        }
+    }
+
+    #[test]
+    fn ray_misses_cube() {
+        let cube = CubeBuilder::new().create().unwrap();
+        let cases = [
+            (point!(-2.0, 0.0, 0.0), vector!(0.2673, 0.5345, 0.8018)),
+            (point!(0.0, -2.0, 0.0), vector!(0.8018, 0.2673, 0.5345)),
+            (point!(0.0, 0.0, -2.0), vector!(0.5345, 0.8018, 0.2673)),
+            (point!(2.0, 0.0, 2.0), vector!(0.0, 0.0, -1.0)),
+            (point!(0.0, 2.0, 2.0), vector!(0.0, -1.0, 0.0)),
+            (point!(2.0, 2.0, 0.0), vector!(-1.0, 0.0, 0.0)),
+        ];
+        for (origin, direction) in cases.iter() {
+            let ray = Ray::new(*origin, *direction);
+            assert!(cube.local_intersect(&ray).is_empty());
+        }
+    }
+
+    #[test]
+    fn normal_on_cube_surface() {
+        let cube = CubeBuilder::new().create().unwrap();
+        let cases = [
+            (point!(1.0, 0.5, -0.8), vector!(1.0, 0.0, 0.0)),
+            (point!(-1.0, -0.2, 0.9), vector!(-1.0, 0.0, 0.0)),
+            (point!(-0.4, 1.0, -0.1), vector!(0.0, 1.0, 0.0)),
+            (point!(0.3, -1.0, -0.7), vector!(0.0, -1.0, 0.0)),
+            (point!(-0.6, 0.3, 1.0), vector!(0.0, 0.0, 1.0)),
+            (point!(0.4, 0.4, -1.0), vector!(0.0, 0.0, -1.0)),
+            (point!(1.0, 1.0, 1.0), vector!(1.0, 0.0, 0.0)),
+            (point!(-1.0, -1.0, -1.0), vector!(-1.0, 0.0, 0.0)),
+        ];
+        for (point, normal) in cases.iter() {
+            assert_eq!(
+                cube.local_normal_at(point, &Intersection::new(0.0, &cube)),
+                *normal
+            );
+        }
+    }
+
+    #[test]
+    fn transformed_cube() {
+        let cube = CubeBuilder::new()
+            .with_transformation(translation!(0.0, 0.0, 5.0) * scaling!(2.0, 2.0, 2.0))
+            .create()
+            .unwrap();
+        let ray = Ray::new(point!(0.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0));
+        let xs = intersect(&ray, &cube).expect("Expected a hit with the cube!");
+        assert_eq!(xs[0].t, 8.0);
+        assert_eq!(xs[1].t, 12.0);
+        let normal = normal_at(
+            &cube,
+            &ray.position(xs[0].t),
+            &Intersection::new(0.0, &cube),
+        );
+        matrix_eq!(normal, vector!(0.0, 0.0, -1.0));
+    }
+}
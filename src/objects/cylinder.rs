@@ -0,0 +1,359 @@
+use crate::bounds::Bounds;
+use crate::intersection::{Intersection, Intersections};
+use crate::material::Material;
+use crate::math::*;
+use crate::objects::{Ray, Shape, ShapeProperties};
+use crate::transform::TransformError;
+
+pub struct CylinderBuilder {
+    transformation: Option<Mat4>,
+    material: Option<Material>,
+    minimum: Option<f32>,
+    maximum: Option<f32>,
+    closed: bool,
+}
+
+impl CylinderBuilder {
+    pub fn new() -> CylinderBuilder {
+        CylinderBuilder {
+            transformation: None,
+            material: None,
+            minimum: None,
+            maximum: None,
+            closed: false,
+        }
+    }
+
+    pub fn with_transformation(&mut self, transformation: Mat4) -> &mut CylinderBuilder {
+        self.transformation.replace(transformation);
+        self
+    }
+
+    pub fn with_material(&mut self, material: Material) -> &mut CylinderBuilder {
+        self.material.replace(material);
+        self
+    }
+
+    //Lower y bound (exclusive) of the cylinder in object space.
+    pub fn with_minimum(&mut self, minimum: f32) -> &mut CylinderBuilder {
+        self.minimum.replace(minimum);
+        self
+    }
+
+    //Upper y bound (exclusive) of the cylinder in object space.
+    pub fn with_maximum(&mut self, maximum: f32) -> &mut CylinderBuilder {
+        self.maximum.replace(maximum);
+        self
+    }
+
+    //Whether the truncated ends are capped.
+    pub fn with_closed(&mut self, closed: bool) -> &mut CylinderBuilder {
+        self.closed = closed;
+        self
+    }
+
+    pub fn create(&mut self) -> Result<Cylinder, TransformError> {
+        let result = Cylinder::new(
+            ShapeProperties::new(
+                self.transformation.unwrap_or_else(Mat4::identity),
+                self.material.clone().unwrap_or_default(),
+            )?,
+            self.minimum.unwrap_or(f32::NEG_INFINITY),
+            self.maximum.unwrap_or(f32::INFINITY),
+            self.closed,
+        );
+        self.transformation = None;
+        self.material = None;
+        self.minimum = None;
+        self.maximum = None;
+        self.closed = false;
+        Ok(result)
+    }
+}
+
+impl Default for CylinderBuilder {
+    fn default() -> Self {
+        CylinderBuilder::new()
+    }
+}
+
+//Cylinder of radius 1 around the y axis, optionally truncated and capped.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Cylinder {
+    properties: ShapeProperties,
+    minimum: f32,
+    maximum: f32,
+    closed: bool,
+}
+
+impl Default for Cylinder {
+    fn default() -> Self {
+        Cylinder {
+            properties: ShapeProperties::default(),
+            minimum: f32::NEG_INFINITY,
+            maximum: f32::INFINITY,
+            closed: false,
+        }
+    }
+}
+
+impl Cylinder {
+    fn new(properties: ShapeProperties, minimum: f32, maximum: f32, closed: bool) -> Cylinder {
+        Cylinder {
+            properties,
+            minimum,
+            maximum,
+            closed,
+        }
+    }
+
+    pub fn minimum(&self) -> f32 {
+        self.minimum
+    }
+
+    pub fn maximum(&self) -> f32 {
+        self.maximum
+    }
+
+    pub fn closed(&self) -> bool {
+        self.closed
+    }
+
+    fn intersect_caps<'a>(&'a self, ray: &Ray, intersections: &mut Intersections<'a>) {
+        //Caps matter only for closed cylinders that the ray is not parallel to.
+        if !self.closed || ray.direction.y.abs() < EPSILON {
+            return;
+        }
+        //An unbounded end has no cap.
+        for cap in [self.minimum, self.maximum]
+            .iter()
+            .filter(|cap| cap.is_finite())
+        {
+            let t = (cap - ray.origin.y) / ray.direction.y;
+            if check_cap(ray, t) {
+                intersections.push(Intersection::new(t, self));
+            }
+        }
+    }
+}
+
+//Is the point at t within the radius of the unit cylinder (with some tolerance for the rim).
+fn check_cap(ray: &Ray, t: f32) -> bool {
+    let x = ray.origin.x + t * ray.direction.x;
+    let z = ray.origin.z + t * ray.direction.z;
+    x.powi(2) + z.powi(2) <= 1.0 + EPSILON
+}
+
+impl Shape for Cylinder {
+    fn properties(&self) -> &ShapeProperties {
+        &self.properties
+    }
+
+    fn properties_mut(&mut self) -> &mut ShapeProperties {
+        &mut self.properties
+    }
+
+    fn local_intersect<'a>(&'a self, ray: &Ray) -> Intersections<'a> {
+        let mut intersections = Intersections::new();
+        let a = ray.direction.x.powi(2) + ray.direction.z.powi(2);
+        //Ray parallel to the y axis can only hit the caps.
+        if a.abs() >= EPSILON {
+            let b = 2.0 * ray.origin.x * ray.direction.x + 2.0 * ray.origin.z * ray.direction.z;
+            let c = ray.origin.x.powi(2) + ray.origin.z.powi(2) - 1.0;
+            let discriminant = b.powi(2) - 4.0 * a * c;
+            if discriminant < 0.0 {
+                return intersections;
+            }
+            let sqrt_discriminant = discriminant.sqrt();
+            let t0 = (-b - sqrt_discriminant) / (2.0 * a);
+            let t1 = (-b + sqrt_discriminant) / (2.0 * a);
+            let (t0, t1) = if t0 > t1 { (t1, t0) } else { (t0, t1) };
+            for t in [t0, t1].iter() {
+                let y = ray.origin.y + t * ray.direction.y;
+                if self.minimum < y && y < self.maximum {
+                    intersections.push(Intersection::new(*t, self));
+                }
+            }
+        }
+        self.intersect_caps(ray, &mut intersections);
+        intersections
+    }
+
+    fn local_normal_at(&self, point: &Point4, _hit: &Intersection) -> Vec4 {
+        let dist = point.x.powi(2) + point.z.powi(2);
+        if dist < 1.0 && point.y >= self.maximum - EPSILON {
+            vector!(0.0, 1.0, 0.0)
+        } else if dist < 1.0 && point.y <= self.minimum + EPSILON {
+            vector!(0.0, -1.0, 0.0)
+        } else {
+            vector!(point.x, 0.0, point.z)
+        }
+    }
+
+    fn bounds(&self) -> Bounds {
+        Bounds::new(
+            point!(-1.0, self.minimum, -1.0),
+            point!(1.0, self.maximum, 1.0),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ray_misses_cylinder() {
+        let cylinder = CylinderBuilder::new().create().unwrap();
+        let cases = [
+            (point!(1.0, 0.0, 0.0), vector!(0.0, 1.0, 0.0)),
+            (point!(0.0, 0.0, 0.0), vector!(0.0, 1.0, 0.0)),
+            (point!(0.0, 0.0, -5.0), vector!(1.0, 1.0, 1.0)),
+        ];
+        for (origin, direction) in cases.iter() {
+            let ray = Ray::new(*origin, direction.normalize());
+            assert!(cylinder.local_intersect(&ray).is_empty());
+        }
+    }
+
+    #[test]
+    fn ray_strikes_cylinder() {
+        let cylinder = CylinderBuilder::new().create().unwrap();
+        let cases = [
+            (point!(1.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0), 5.0, 5.0),
+            (point!(0.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0), 4.0, 6.0),
+            (
+                point!(0.5, 0.0, -5.0),
+                vector!(0.1, 1.0, 1.0),
+                6.80798,
+                7.08872,
+            ),
+        ];
+        for (origin, direction, t0, t1) in cases.iter() {
+            let ray = Ray::new(*origin, direction.normalize());
+            let xs = cylinder.local_intersect(&ray);
+            assert_eq!(xs.len(), 2);
+            assert!((xs[0].t - t0).abs() < EPSILON);
+            assert!((xs[1].t - t1).abs() < EPSILON);
+        }
+    }
+
+    #[test]
+    fn normal_on_cylinder() {
+        let cylinder = CylinderBuilder::new().create().unwrap();
+        let cases = [
+            (point!(1.0, 0.0, 0.0), vector!(1.0, 0.0, 0.0)),
+            (point!(0.0, 5.0, -1.0), vector!(0.0, 0.0, -1.0)),
+            (point!(0.0, -2.0, 1.0), vector!(0.0, 0.0, 1.0)),
+            (point!(-1.0, 1.0, 0.0), vector!(-1.0, 0.0, 0.0)),
+        ];
+        for (point, normal) in cases.iter() {
+            assert_eq!(
+                cylinder.local_normal_at(point, &Intersection::new(0.0, &cylinder)),
+                *normal
+            );
+        }
+    }
+
+    #[test]
+    fn default_cylinder_is_infinite_and_open() {
+        let cylinder = CylinderBuilder::new().create().unwrap();
+        assert_eq!(cylinder.minimum(), f32::NEG_INFINITY);
+        assert_eq!(cylinder.maximum(), f32::INFINITY);
+        assert!(!cylinder.closed());
+    }
+
+    #[test]
+    fn intersecting_truncated_cylinder() {
+        let cylinder = CylinderBuilder::new()
+            .with_minimum(1.0)
+            .with_maximum(2.0)
+            .create()
+            .unwrap();
+        let cases = [
+            (point!(0.0, 1.5, 0.0), vector!(0.1, 1.0, 0.0), 0),
+            (point!(0.0, 3.0, -5.0), vector!(0.0, 0.0, 1.0), 0),
+            (point!(0.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0), 0),
+            (point!(0.0, 2.0, -5.0), vector!(0.0, 0.0, 1.0), 0),
+            (point!(0.0, 1.0, -5.0), vector!(0.0, 0.0, 1.0), 0),
+            (point!(0.0, 1.5, -2.0), vector!(0.0, 0.0, 1.0), 2),
+        ];
+        for (origin, direction, count) in cases.iter() {
+            let ray = Ray::new(*origin, direction.normalize());
+            assert_eq!(cylinder.local_intersect(&ray).len(), *count);
+        }
+    }
+
+    #[test]
+    fn intersecting_caps_of_closed_cylinder() {
+        let cylinder = CylinderBuilder::new()
+            .with_minimum(1.0)
+            .with_maximum(2.0)
+            .with_closed(true)
+            .create()
+            .unwrap();
+        let cases = [
+            (point!(0.0, 3.0, 0.0), vector!(0.0, -1.0, 0.0), 2),
+            (point!(0.0, 3.0, -2.0), vector!(0.0, -1.0, 2.0), 2),
+            (point!(0.0, 4.0, -2.0), vector!(0.0, -1.0, 1.0), 2),
+            (point!(0.0, 0.0, -2.0), vector!(0.0, 1.0, 2.0), 2),
+            (point!(0.0, -1.0, -2.0), vector!(0.0, 1.0, 1.0), 2),
+        ];
+        for (origin, direction, count) in cases.iter() {
+            let ray = Ray::new(*origin, direction.normalize());
+            assert_eq!(cylinder.local_intersect(&ray).len(), *count);
+        }
+    }
+
+    #[test]
+    fn closed_unbounded_cylinder_has_no_caps() {
+        let cylinder = CylinderBuilder::new()
+            .with_maximum(2.0)
+            .with_closed(true)
+            .create()
+            .unwrap();
+        let ray = Ray::new(point!(0.0, 3.0, 0.0), vector!(0.0, -1.0, 0.0));
+        let xs = cylinder.local_intersect(&ray);
+        assert_eq!(xs.len(), 1);
+        assert_eq!(xs[0].t, 1.0);
+        let cylinder = CylinderBuilder::new().with_closed(true).create().unwrap();
+        assert_eq!(cylinder.local_intersect(&ray).len(), 0);
+    }
+
+    #[test]
+    fn normal_on_cylinder_caps() {
+        let cylinder = CylinderBuilder::new()
+            .with_minimum(1.0)
+            .with_maximum(2.0)
+            .with_closed(true)
+            .create()
+            .unwrap();
+        let cases = [
+            (point!(0.0, 1.0, 0.0), vector!(0.0, -1.0, 0.0)),
+            (point!(0.5, 1.0, 0.0), vector!(0.0, -1.0, 0.0)),
+            (point!(0.0, 1.0, 0.5), vector!(0.0, -1.0, 0.0)),
+            (point!(0.0, 2.0, 0.0), vector!(0.0, 1.0, 0.0)),
+            (point!(0.5, 2.0, 0.0), vector!(0.0, 1.0, 0.0)),
+            (point!(0.0, 2.0, 0.5), vector!(0.0, 1.0, 0.0)),
+        ];
+        for (point, normal) in cases.iter() {
+            assert_eq!(
+                cylinder.local_normal_at(point, &Intersection::new(0.0, &cylinder)),
+                *normal
+            );
+        }
+    }
+
+    #[test]
+    fn cylinder_bounds() {
+        let cylinder = CylinderBuilder::new().create().unwrap();
+        assert!(!cylinder.bounds().is_finite());
+        let cylinder = CylinderBuilder::new()
+            .with_minimum(-5.0)
+            .with_maximum(3.0)
+            .create()
+            .unwrap();
+        assert_eq!(cylinder.bounds().min, point!(-1.0, -5.0, -1.0));
+        assert_eq!(cylinder.bounds().max, point!(1.0, 3.0, 1.0));
+    }
+}
@@ -0,0 +1,331 @@
+use crate::bounds::Bounds;
+use crate::bvh::Bvh;
+use crate::intersection::{Intersection, Intersections};
+use crate::material::Material;
+use crate::math::*;
+use crate::objects::{Ray, Shape, ShapeProperties};
+use crate::transform::{Transform, TransformError};
+use std::cell::OnceCell;
+
+pub struct GroupBuilder {
+    transformation: Option<Mat4>,
+    material: Option<Material>,
+    children: Vec<Box<dyn Shape>>,
+}
+
+impl GroupBuilder {
+    pub fn new() -> GroupBuilder {
+        GroupBuilder {
+            transformation: None,
+            material: None,
+            children: Vec::new(),
+        }
+    }
+
+    pub fn with_transformation(&mut self, transformation: Mat4) -> &mut GroupBuilder {
+        self.transformation.replace(transformation);
+        self
+    }
+
+    pub fn with_material(&mut self, material: Material) -> &mut GroupBuilder {
+        self.material.replace(material);
+        self
+    }
+
+    pub fn with_child(&mut self, child: Box<dyn Shape>) -> &mut GroupBuilder {
+        self.children.push(child);
+        self
+    }
+
+    pub fn create(&mut self) -> Result<Group, TransformError> {
+        let mut result = Group::new(ShapeProperties::new(
+            self.transformation.unwrap_or_else(Mat4::identity),
+            self.material.clone().unwrap_or_default(),
+        )?);
+        for child in self.children.drain(..) {
+            result.add_child(child);
+        }
+        self.transformation = None;
+        self.material = None;
+        Ok(result)
+    }
+}
+
+impl Default for GroupBuilder {
+    fn default() -> Self {
+        GroupBuilder::new()
+    }
+}
+
+//Node of the scene graph. Owns its children and places them with its own transformation,
+//groups can be nested to build more complex models out of simple parts.
+#[derive(Debug, Default)]
+pub struct Group {
+    properties: ShapeProperties,
+    children: Vec<Box<dyn Shape>>,
+    //Built on the first intersection, children are placed relative to the group,
+    //so it has to be rebuilt only when they change.
+    bvh: OnceCell<Bvh>,
+}
+
+impl Group {
+    fn new(properties: ShapeProperties) -> Group {
+        Group {
+            properties,
+            children: Vec::new(),
+            bvh: OnceCell::new(),
+        }
+    }
+
+    pub fn add_child(&mut self, mut child: Box<dyn Shape>) {
+        child.set_parent_transformation(self.world_transformation());
+        self.children.push(child);
+        self.bvh = OnceCell::new();
+    }
+
+    pub fn children(&self) -> &[Box<dyn Shape>] {
+        &self.children
+    }
+
+    //Pass the new placement of the group down the hierarchy.
+    fn update_children(&mut self) {
+        let world_transformation = *self.world_transformation();
+        for child in self.children.iter_mut() {
+            child.set_parent_transformation(&world_transformation);
+        }
+    }
+}
+
+impl Shape for Group {
+    fn properties(&self) -> &ShapeProperties {
+        &self.properties
+    }
+
+    fn properties_mut(&mut self) -> &mut ShapeProperties {
+        &mut self.properties
+    }
+
+    fn local_intersect<'a>(&'a self, ray: &Ray) -> Intersections<'a> {
+        self.bvh
+            .get_or_init(|| Bvh::build(&self.children))
+            .intersect(&self.children, ray)
+    }
+
+    fn local_normal_at(&self, _point: &Point4, _hit: &Intersection) -> Vec4 {
+        unreachable!("Intersections are always reported for the children, not the group!")
+    }
+
+    fn bounds(&self) -> Bounds {
+        let mut bounds = Bounds::empty();
+        for child in &self.children {
+            bounds.merge(&child.parent_space_bounds());
+        }
+        bounds
+    }
+
+    fn includes(&self, id: u32) -> bool {
+        self.id() == id || self.children.iter().any(|child| child.includes(id))
+    }
+
+    fn transform(&mut self, transformation: &Mat4) -> Result<(), TransformError> {
+        let transformation = self.properties.transformation * Transform::new(*transformation)?;
+        self.properties.set_transformation(transformation);
+        self.update_children();
+        Ok(())
+    }
+
+    fn set_parent_transformation(&mut self, parent_transformation: &Transform) {
+        self.properties
+            .set_parent_transformation(*parent_transformation);
+        self.update_children();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intersection::intersect;
+    use crate::objects::{normal_at, CylinderBuilder, SphereBuilder};
+    use std::f32::consts::FRAC_PI_2;
+
+    #[test]
+    fn empty_group() {
+        let group = GroupBuilder::new().create().unwrap();
+        assert_eq!(group.get_transformation().matrix(), &Mat4::identity());
+        assert!(group.children().is_empty());
+        let ray = Ray::new(point!(0.0, 0.0, 0.0), vector!(0.0, 0.0, 1.0));
+        assert!(group.local_intersect(&ray).is_empty());
+    }
+
+    #[test]
+    fn adding_child_to_group() {
+        let mut group = GroupBuilder::new()
+            .with_transformation(translation!(1.0, 0.0, 0.0))
+            .create()
+            .unwrap();
+        let sphere = SphereBuilder::new().create().unwrap();
+        let id = sphere.id();
+        group.add_child(Box::new(sphere));
+        assert_eq!(group.children().len(), 1);
+        assert_eq!(group.children()[0].id(), id);
+        assert_eq!(
+            *group.children()[0].world_transformation().matrix(),
+            translation!(1.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn intersecting_group_with_children() {
+        let s1 = SphereBuilder::new().create().unwrap();
+        let s2 = SphereBuilder::new()
+            .with_transformation(translation!(0.0, 0.0, -3.0))
+            .create()
+            .unwrap();
+        let s3 = SphereBuilder::new()
+            .with_transformation(translation!(5.0, 0.0, 0.0))
+            .create()
+            .unwrap();
+        let (id1, id2) = (s1.id(), s2.id());
+        let group = GroupBuilder::new()
+            .with_child(Box::new(s1))
+            .with_child(Box::new(s2))
+            .with_child(Box::new(s3))
+            .create()
+            .unwrap();
+        let ray = Ray::new(point!(0.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0));
+        let xs = group.local_intersect(&ray);
+        assert_eq!(xs.len(), 4);
+        assert_eq!(xs[0].obj.id(), id2);
+        assert_eq!(xs[1].obj.id(), id2);
+        assert_eq!(xs[2].obj.id(), id1);
+        assert_eq!(xs[3].obj.id(), id1);
+    }
+
+    #[test]
+    fn intersecting_transformed_group() {
+        let sphere = SphereBuilder::new()
+            .with_transformation(translation!(5.0, 0.0, 0.0))
+            .create()
+            .unwrap();
+        let group = GroupBuilder::new()
+            .with_transformation(scaling!(2.0, 2.0, 2.0))
+            .with_child(Box::new(sphere))
+            .create()
+            .unwrap();
+        let ray = Ray::new(point!(10.0, 0.0, -10.0), vector!(0.0, 0.0, 1.0));
+        let xs = intersect(&ray, &group).expect("Expected to hit the child!");
+        assert_eq!(xs.len(), 2);
+    }
+
+    fn nested_sphere() -> Group {
+        let sphere = SphereBuilder::new()
+            .with_transformation(translation!(5.0, 0.0, 0.0))
+            .create()
+            .unwrap();
+        let inner = GroupBuilder::new()
+            .with_transformation(scaling!(1.0, 2.0, 3.0))
+            .with_child(Box::new(sphere))
+            .create()
+            .unwrap();
+        GroupBuilder::new()
+            .with_transformation(rotation!(0.0, FRAC_PI_2, 0.0))
+            .with_child(Box::new(inner))
+            .create()
+            .unwrap()
+    }
+
+    //The nested sphere ends up centered at (0, 0, -5) in world space.
+    fn first_leaf(group: &Group) -> &dyn Shape {
+        let ray = Ray::new(point!(0.0, 0.0, -20.0), vector!(0.0, 0.0, 1.0));
+        let xs = intersect(&ray, group).expect("Expected the nested sphere to be hit!");
+        xs[0].obj
+    }
+
+    #[test]
+    fn converting_point_from_world_to_nested_object_space() {
+        let group = nested_sphere();
+        let sphere = first_leaf(&group);
+        let inverse = sphere.world_transformation().inverse();
+        let point = inverse * point!(-2.0, 0.0, -10.0);
+        matrix_eq!(point, point!(5.0, 0.0, -2.0 / 3.0), 0.0001);
+    }
+
+    #[test]
+    fn normal_on_nested_child() {
+        let group = nested_sphere();
+        let sphere = first_leaf(&group);
+        let hit = Intersection::new(0.0, sphere);
+        let normal = normal_at(sphere, &point!(1.7321, 1.1547, -5.5774), &hit);
+        matrix_eq!(normal, vector!(0.2857, 0.4286, -0.8571), 0.0001);
+    }
+
+    #[test]
+    fn group_includes_nested_children() {
+        let group = nested_sphere();
+        let sphere = first_leaf(&group);
+        let other = SphereBuilder::new().create().unwrap();
+        assert!(group.includes(group.id()));
+        assert!(group.includes(sphere.id()));
+        assert!(!group.includes(other.id()));
+    }
+
+    #[test]
+    fn transforming_group_moves_children() {
+        let mut group = nested_sphere();
+        group.transform(&translation!(0.0, 1.0, 0.0)).unwrap();
+        let sphere = first_leaf(&group);
+        let expected = translation!(0.0, 1.0, 0.0)
+            * rotation!(0.0, FRAC_PI_2, 0.0)
+            * scaling!(1.0, 2.0, 3.0)
+            * translation!(5.0, 0.0, 0.0);
+        matrix_eq!(sphere.world_transformation().matrix(), expected);
+    }
+
+    #[test]
+    fn group_bounds_contain_children() {
+        let sphere = SphereBuilder::new()
+            .with_transformation(translation!(2.0, 5.0, -3.0) * scaling!(2.0, 2.0, 2.0))
+            .create()
+            .unwrap();
+        let cylinder = CylinderBuilder::new()
+            .with_transformation(translation!(-4.0, -1.0, 4.0) * scaling!(0.5, 1.0, 0.5))
+            .with_minimum(-2.0)
+            .with_maximum(2.0)
+            .create()
+            .unwrap();
+        let group = GroupBuilder::new()
+            .with_child(Box::new(sphere))
+            .with_child(Box::new(cylinder))
+            .create()
+            .unwrap();
+        let bounds = group.bounds();
+        matrix_eq!(bounds.min, point!(-4.5, -3.0, -5.0));
+        matrix_eq!(bounds.max, point!(4.0, 7.0, 4.5));
+    }
+
+    #[test]
+    fn group_with_many_children_finds_each_of_them() {
+        let mut group = GroupBuilder::new().create().unwrap();
+        for i in 0..50 {
+            let sphere = SphereBuilder::new()
+                .with_transformation(translation!(i as f32 * 3.0, 0.0, 0.0))
+                .create()
+                .unwrap();
+            group.add_child(Box::new(sphere));
+        }
+        for i in 0..50 {
+            let ray = Ray::new(point!(i as f32 * 3.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0));
+            let xs = group.local_intersect(&ray);
+            assert_eq!(xs.len(), 2);
+            assert_eq!(xs[0].obj.id(), group.children()[i].id());
+        }
+        //Children added after the first intersection are found as well.
+        let sphere = SphereBuilder::new()
+            .with_transformation(translation!(0.0, 10.0, 0.0))
+            .create()
+            .unwrap();
+        group.add_child(Box::new(sphere));
+        let ray = Ray::new(point!(0.0, 10.0, -5.0), vector!(0.0, 0.0, 1.0));
+        assert_eq!(group.local_intersect(&ray).len(), 2);
+    }
+}
@@ -0,0 +1,175 @@
+use crate::bounds::Bounds;
+use crate::intersection::{Intersection, Intersections};
+use crate::material::Material;
+use crate::math::*;
+use crate::objects::{Ray, Shape, ShapeProperties};
+use crate::transform::TransformError;
+
+pub struct PlaneBuilder {
+    transformation: Option<Mat4>,
+    material: Option<Material>,
+}
+
+impl PlaneBuilder {
+    pub fn new() -> PlaneBuilder {
+        PlaneBuilder {
+            transformation: None,
+            material: None,
+        }
+    }
+
+    pub fn with_transformation(&mut self, transformation: Mat4) -> &mut PlaneBuilder {
+        self.transformation.replace(transformation);
+        self
+    }
+
+    pub fn with_material(&mut self, material: Material) -> &mut PlaneBuilder {
+        self.material.replace(material);
+        self
+    }
+
+    pub fn create(&mut self) -> Result<Plane, TransformError> {
+        let result = Plane::new(ShapeProperties::new(
+            self.transformation.unwrap_or_else(Mat4::identity),
+            self.material.clone().unwrap_or_default(),
+        )?);
+        self.transformation = None;
+        self.material = None;
+        Ok(result)
+    }
+}
+
+impl Default for PlaneBuilder {
+    fn default() -> Self {
+        PlaneBuilder::new()
+    }
+}
+
+//Infinite xz plane going through the origin.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Plane {
+    properties: ShapeProperties,
+}
+
+impl Plane {
+    fn new(properties: ShapeProperties) -> Plane {
+        Plane { properties }
+    }
+}
+
+impl Shape for Plane {
+    fn properties(&self) -> &ShapeProperties {
+        &self.properties
+    }
+
+    fn properties_mut(&mut self) -> &mut ShapeProperties {
+        &mut self.properties
+    }
+
+    fn local_intersect<'a>(&'a self, ray: &Ray) -> Intersections<'a> {
+        //Ray parallel to the plane (or coplanar with it) never hits it.
+        if ray.direction.y.abs() < EPSILON {
+            return Intersections::new();
+        }
+        let t = -ray.origin.y / ray.direction.y;
+        vec![Intersection::new(t, self)]
+    }
+
+    fn local_normal_at(&self, _point: &Point4, _hit: &Intersection) -> Vec4 {
+        vector!(0.0, 1.0, 0.0)
+    }
+
+    fn bounds(&self) -> Bounds {
+        Bounds::new(
+            point!(f32::NEG_INFINITY, 0.0, f32::NEG_INFINITY),
+            point!(f32::INFINITY, 0.0, f32::INFINITY),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intersection::{intersect, Precomputation};
+    use crate::light::{shade_hit, PointLight};
+    use crate::objects::normal_at;
+    use crate::world::World;
+
+    #[test]
+    fn plane_normal_is_constant() {
+        let plane = PlaneBuilder::new().create().unwrap();
+        let n1 = plane.local_normal_at(&point!(0.0, 0.0, 0.0), &Intersection::new(0.0, &plane));
+        let n2 = plane.local_normal_at(&point!(10.0, 0.0, -10.0), &Intersection::new(0.0, &plane));
+        let n3 = plane.local_normal_at(&point!(-5.0, 0.0, 150.0), &Intersection::new(0.0, &plane));
+        assert_eq!(n1, vector!(0.0, 1.0, 0.0));
+        assert_eq!(n2, vector!(0.0, 1.0, 0.0));
+        assert_eq!(n3, vector!(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn ray_parallel_to_plane() {
+        let plane = PlaneBuilder::new().create().unwrap();
+        let ray = Ray::new(point!(0.0, 10.0, 0.0), vector!(0.0, 0.0, 1.0));
+        assert!(plane.local_intersect(&ray).is_empty());
+    }
+
+    #[test]
+    fn ray_coplanar_with_plane() {
+        let plane = PlaneBuilder::new().create().unwrap();
+        let ray = Ray::new(point!(0.0, 0.0, 0.0), vector!(0.0, 0.0, 1.0));
+        assert!(plane.local_intersect(&ray).is_empty());
+    }
+
+    #[test]
+    fn ray_intersecting_plane_from_above() {
+        let plane = PlaneBuilder::new().create().unwrap();
+        let ray = Ray::new(point!(0.0, 1.0, 0.0), vector!(0.0, -1.0, 0.0));
+        let xs = plane.local_intersect(&ray);
+        assert_eq!(xs.len(), 1);
+        assert_eq!(xs[0].t, 1.0);
+        assert_eq!(xs[0].obj.id(), plane.id());
+    }
+
+    #[test]
+    fn ray_intersecting_plane_from_below() {
+        let plane = PlaneBuilder::new().create().unwrap();
+        let ray = Ray::new(point!(0.0, -1.0, 0.0), vector!(0.0, 1.0, 0.0));
+        let xs = plane.local_intersect(&ray);
+        assert_eq!(xs.len(), 1);
+        assert_eq!(xs[0].t, 1.0);
+    }
+
+    #[test]
+    fn transformed_plane() {
+        use std::f32::consts::FRAC_PI_2;
+        //Wall standing 5 units in front of the origin, facing -z.
+        let plane = PlaneBuilder::new()
+            .with_transformation(translation!(0.0, 0.0, 5.0) * rotation!(-FRAC_PI_2, 0.0, 0.0))
+            .create()
+            .unwrap();
+        let ray = Ray::new(point!(0.0, 0.0, 0.0), vector!(0.0, 0.0, 1.0));
+        let xs = intersect(&ray, &plane).expect("Expected a hit with the wall!");
+        assert!((xs[0].t - 5.0).abs() < EPSILON);
+        let normal = normal_at(
+            &plane,
+            &point!(0.0, 0.0, 5.0),
+            &Intersection::new(0.0, &plane),
+        );
+        matrix_eq!(normal, vector!(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn shade_plane_in_world() {
+        let light = PointLight::new(point!(0.0, 10.0, 0.0), Color::new(1.0, 1.0, 1.0));
+        let mut world = World::new(Box::new(light));
+        world.add_object(Box::new(PlaneBuilder::new().create().unwrap()));
+        let ray = Ray::new(point!(0.0, 1.0, 0.0), vector!(0.0, -1.0, 0.0));
+        let xs = world.ray_intersect(&ray);
+        assert_eq!(xs.len(), 1);
+        let precomps = Precomputation::compute(&xs[0], &ray, &xs);
+        matrix_eq!(precomps.normalv, vector!(0.0, 1.0, 0.0));
+        let color = shade_hit(&world, &precomps, 0);
+        //Ambient + full diffuse + full specular of the default material.
+        matrix_eq!(color.as_array(), [1.9, 1.9, 1.9], 0.0001);
+    }
+}
@@ -0,0 +1,252 @@
+use crate::bounds::Bounds;
+use crate::intersection::{Intersection, Intersections};
+use crate::material::Material;
+use crate::math::*;
+use crate::objects::triangle::{barycentric, interpolate_texture_coords, intersect_triangle};
+use crate::objects::{Ray, Shape, ShapeProperties};
+use crate::transform::TransformError;
+
+pub struct SmoothTriangleBuilder {
+    vertices: [Point4; 3],
+    normals: [Vec4; 3],
+    texture_coords: Option<[(f32, f32); 3]>,
+    transformation: Option<Mat4>,
+    material: Option<Material>,
+}
+
+impl SmoothTriangleBuilder {
+    pub fn new(
+        p1: Point4,
+        p2: Point4,
+        p3: Point4,
+        n1: Vec4,
+        n2: Vec4,
+        n3: Vec4,
+    ) -> SmoothTriangleBuilder {
+        SmoothTriangleBuilder {
+            vertices: [p1, p2, p3],
+            normals: [n1, n2, n3],
+            texture_coords: None,
+            transformation: None,
+            material: None,
+        }
+    }
+
+    //Texture coordinates of p1, p2 and p3, interpolated over the face.
+    pub fn with_texture_coords(
+        &mut self,
+        uv1: (f32, f32),
+        uv2: (f32, f32),
+        uv3: (f32, f32),
+    ) -> &mut SmoothTriangleBuilder {
+        self.texture_coords.replace([uv1, uv2, uv3]);
+        self
+    }
+
+    pub fn with_transformation(&mut self, transformation: Mat4) -> &mut SmoothTriangleBuilder {
+        self.transformation.replace(transformation);
+        self
+    }
+
+    pub fn with_material(&mut self, material: Material) -> &mut SmoothTriangleBuilder {
+        self.material.replace(material);
+        self
+    }
+
+    pub fn create(&mut self) -> Result<SmoothTriangle, TransformError> {
+        let mut result = SmoothTriangle::new(
+            ShapeProperties::new(
+                self.transformation.unwrap_or_else(Mat4::identity),
+                self.material.clone().unwrap_or_default(),
+            )?,
+            self.vertices,
+            self.normals,
+        );
+        result.texture_coords = self.texture_coords;
+        self.transformation = None;
+        self.material = None;
+        Ok(result)
+    }
+}
+
+//Triangle with a normal in every vertex, the normal of a hit is interpolated
+//between them using barycentric coordinates of the hit.
+#[derive(Clone, PartialEq, Debug)]
+pub struct SmoothTriangle {
+    properties: ShapeProperties,
+    p1: Point4,
+    p2: Point4,
+    p3: Point4,
+    n1: Vec4,
+    n2: Vec4,
+    n3: Vec4,
+    e1: Vec3,
+    e2: Vec3,
+    texture_coords: Option<[(f32, f32); 3]>,
+}
+
+impl SmoothTriangle {
+    fn new(
+        properties: ShapeProperties,
+        vertices: [Point4; 3],
+        normals: [Vec4; 3],
+    ) -> SmoothTriangle {
+        let [p1, p2, p3] = vertices;
+        let [n1, n2, n3] = normals;
+        SmoothTriangle {
+            properties,
+            p1,
+            p2,
+            p3,
+            n1,
+            n2,
+            n3,
+            e1: (p2 - p1).xyz(),
+            e2: (p3 - p1).xyz(),
+            texture_coords: None,
+        }
+    }
+
+    pub fn p1(&self) -> &Point4 {
+        &self.p1
+    }
+
+    pub fn p2(&self) -> &Point4 {
+        &self.p2
+    }
+
+    pub fn p3(&self) -> &Point4 {
+        &self.p3
+    }
+
+    pub fn n1(&self) -> &Vec4 {
+        &self.n1
+    }
+
+    pub fn n2(&self) -> &Vec4 {
+        &self.n2
+    }
+
+    pub fn n3(&self) -> &Vec4 {
+        &self.n3
+    }
+
+    pub fn texture_coords(&self) -> Option<&[(f32, f32); 3]> {
+        self.texture_coords.as_ref()
+    }
+}
+
+impl Shape for SmoothTriangle {
+    fn properties(&self) -> &ShapeProperties {
+        &self.properties
+    }
+
+    fn properties_mut(&mut self) -> &mut ShapeProperties {
+        &mut self.properties
+    }
+
+    fn local_intersect<'a>(&'a self, ray: &Ray) -> Intersections<'a> {
+        match intersect_triangle(ray, &self.p1, &self.e1, &self.e2) {
+            Some((t, u, v)) => vec![Intersection::with_uv(t, self, u, v)],
+            None => Intersections::new(),
+        }
+    }
+
+    fn local_normal_at(&self, _point: &Point4, hit: &Intersection) -> Vec4 {
+        self.n2 * hit.u + self.n3 * hit.v + self.n1 * (1.0 - hit.u - hit.v)
+    }
+
+    fn texture_coords_at(&self, point: &Point4) -> Option<(f32, f32)> {
+        let coords = self.texture_coords.as_ref()?;
+        let (u, v) = barycentric(point, &self.p1, &self.e1, &self.e2);
+        Some(interpolate_texture_coords(coords, u, v))
+    }
+
+    fn bounds(&self) -> Bounds {
+        let mut bounds = Bounds::empty();
+        bounds.add_point(&self.p1);
+        bounds.add_point(&self.p2);
+        bounds.add_point(&self.p3);
+        bounds
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intersection::Precomputation;
+    use crate::objects::normal_at;
+
+    fn test_triangle() -> SmoothTriangle {
+        SmoothTriangleBuilder::new(
+            point!(0.0, 1.0, 0.0),
+            point!(-1.0, 0.0, 0.0),
+            point!(1.0, 0.0, 0.0),
+            vector!(0.0, 1.0, 0.0),
+            vector!(-1.0, 0.0, 0.0),
+            vector!(1.0, 0.0, 0.0),
+        )
+        .create()
+        .unwrap()
+    }
+
+    #[test]
+    fn smooth_triangle_construction() {
+        let t = test_triangle();
+        assert_eq!(t.p1(), &point!(0.0, 1.0, 0.0));
+        assert_eq!(t.p2(), &point!(-1.0, 0.0, 0.0));
+        assert_eq!(t.p3(), &point!(1.0, 0.0, 0.0));
+        assert_eq!(t.n1(), &vector!(0.0, 1.0, 0.0));
+        assert_eq!(t.n2(), &vector!(-1.0, 0.0, 0.0));
+        assert_eq!(t.n3(), &vector!(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn smooth_triangle_hit_stores_uv() {
+        let t = test_triangle();
+        let ray = Ray::new(point!(-0.2, 0.3, -2.0), vector!(0.0, 0.0, 1.0));
+        let xs = t.local_intersect(&ray);
+        assert_eq!(xs.len(), 1);
+        assert!((xs[0].u - 0.45).abs() < EPSILON);
+        assert!((xs[0].v - 0.25).abs() < EPSILON);
+    }
+
+    #[test]
+    fn smooth_triangle_interpolates_normal() {
+        let t = test_triangle();
+        let hit = Intersection::with_uv(1.0, &t, 0.45, 0.25);
+        let normal = normal_at(&t, &point!(0.0, 0.0, 0.0), &hit);
+        matrix_eq!(normal, vector!(-0.5547, 0.83205, 0.0), 0.0001);
+    }
+
+    #[test]
+    fn smooth_triangle_texture_coords() {
+        let t = SmoothTriangleBuilder::new(
+            *test_triangle().p1(),
+            *test_triangle().p2(),
+            *test_triangle().p3(),
+            vector!(0.0, 1.0, 0.0),
+            vector!(-1.0, 0.0, 0.0),
+            vector!(1.0, 0.0, 0.0),
+        )
+        .with_texture_coords((0.0, 1.0), (0.0, 0.0), (1.0, 0.0))
+        .create()
+        .unwrap();
+        let (u, v) = t.texture_coords_at(&point!(0.5, 0.0, 0.0)).unwrap();
+        assert!((u - 0.75).abs() < EPSILON);
+        assert!(v.abs() < EPSILON);
+        assert_eq!(
+            test_triangle().texture_coords_at(&point!(0.5, 0.0, 0.0)),
+            None
+        );
+    }
+
+    #[test]
+    fn smooth_triangle_precomputation() {
+        let t = test_triangle();
+        let hit = Intersection::with_uv(1.0, &t, 0.45, 0.25);
+        let ray = Ray::new(point!(-0.2, 0.3, -2.0), vector!(0.0, 0.0, 1.0));
+        let comps = Precomputation::compute(&hit, &ray, &[hit]);
+        matrix_eq!(comps.normalv, vector!(-0.5547, 0.83205, 0.0), 0.0001);
+    }
+}
@@ -0,0 +1,272 @@
+use crate::bounds::Bounds;
+use crate::intersection::{Intersection, Intersections};
+use crate::material::Material;
+use crate::math::*;
+use crate::objects::{Ray, Shape, ShapeProperties};
+use crate::transform::TransformError;
+
+pub struct SphereBuilder {
+    transformation: Option<Mat4>,
+    material: Option<Material>,
+}
+
+impl SphereBuilder {
+    pub fn new() -> SphereBuilder {
+        SphereBuilder {
+            transformation: None,
+            material: None,
+        }
+    }
+
+    pub fn with_transformation(&mut self, transformation: Mat4) -> &mut SphereBuilder {
+        self.transformation.replace(transformation);
+        self
+    }
+
+    pub fn with_material(&mut self, material: Material) -> &mut SphereBuilder {
+        self.material.replace(material);
+        self
+    }
+
+    pub fn create(&mut self) -> Result<Sphere, TransformError> {
+        let result = Sphere::new(ShapeProperties::new(
+            self.transformation.unwrap_or_else(Mat4::identity),
+            self.material.clone().unwrap_or_default(),
+        )?);
+        self.transformation = None;
+        self.material = None;
+        Ok(result)
+    }
+}
+
+impl Default for SphereBuilder {
+    fn default() -> Self {
+        SphereBuilder::new()
+    }
+}
+
+//Empty sphere that is placed in the center of the screen and has a radius of 1.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Sphere {
+    properties: ShapeProperties,
+}
+
+impl Sphere {
+    fn new(properties: ShapeProperties) -> Sphere {
+        Sphere { properties }
+    }
+}
+
+impl Shape for Sphere {
+    fn properties(&self) -> &ShapeProperties {
+        &self.properties
+    }
+
+    fn properties_mut(&mut self) -> &mut ShapeProperties {
+        &mut self.properties
+    }
+
+    fn local_intersect<'a>(&'a self, ray: &Ray) -> Intersections<'a> {
+        //Sphere center to the origin.
+        let sphere_to_ray = ray.origin - point!(0.0, 0.0, 0.0);
+
+        let a = ray.direction.dot(&ray.direction);
+        let b = 2.0 * ray.direction.dot(&sphere_to_ray);
+        let c = sphere_to_ray.dot(&sphere_to_ray) - 1.0;
+        let discriminant = b.powi(2) - 4.0 * a * c;
+        if discriminant < 0.0 {
+            return Intersections::new(); // NO HIT
+        }
+        let sqrt_discriminant = discriminant.sqrt();
+        let denom = 2.0 * a;
+        let t1 = (-b - sqrt_discriminant) / denom;
+        let t2 = (-b + sqrt_discriminant) / denom;
+        let (t1, t2) = if t1 > t2 { (t2, t1) } else { (t1, t2) };
+        vec![Intersection::new(t1, self), Intersection::new(t2, self)]
+    }
+
+    fn local_normal_at(&self, point: &Point4, _hit: &Intersection) -> Vec4 {
+        point - point!(0.0, 0.0, 0.0)
+    }
+
+    fn bounds(&self) -> Bounds {
+        Bounds::new(point!(-1.0, -1.0, -1.0), point!(1.0, 1.0, 1.0))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intersection::*;
+    use crate::objects::normal_at;
+
+    #[test]
+    fn sphere_creation() {
+        let mut sb = SphereBuilder::new();
+        let s1 = sb.create().unwrap();
+        let s2 = sb.create().unwrap();
+        assert_ne!(s1.id(), s2.id());
+    }
+
+    #[test]
+    fn ray_sphere_intersection_two_points() {
+        let ray = Ray::new(point!(0.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0));
+        let sphere = SphereBuilder::new().create().unwrap();
+
+        let intersects = intersect(&ray, &sphere);
+        assert_eq!(intersects.as_ref().map(|res| res.is_empty()), Some(false));
+
+        let expected = [
+            Intersection::new(4.0, &sphere),
+            Intersection::new(6.0, &sphere),
+        ];
+        assert!(intersects
+            .unwrap()
+            .iter()
+            .zip(&expected)
+            .all(|(a, b)| a == b));
+    }
+
+    #[test]
+    fn ray_sphere_intersection_tangent() {
+        let ray = Ray::new(point!(0.0, 1.0, -5.0), vector!(0.0, 0.0, 1.0));
+        let sphere = SphereBuilder::new().create().unwrap();
+        let intersects = intersect(&ray, &sphere);
+        assert_eq!(intersects.as_ref().map(|res| res.is_empty()), Some(false));
+
+        let expected = [
+            Intersection::new(5.0, &sphere),
+            Intersection::new(5.0, &sphere),
+        ];
+        assert!(intersects
+            .unwrap()
+            .iter()
+            .zip(&expected)
+            .all(|(a, b)| a == b));
+    }
+
+    #[test]
+    fn ray_sphere_intersection_none() {
+        let ray = Ray::new(point!(0.0, 2.0, -5.0), vector!(0.0, 0.0, 1.0));
+        let sphere = SphereBuilder::new().create().unwrap();
+        let inter = intersect(&ray, &sphere);
+        assert!(inter.is_none());
+    }
+
+    #[test]
+    fn ray_origin_inside_sphere() {
+        let ray = Ray::new(point!(0.0, 0.0, 0.0), vector!(0.0, 0.0, 1.0));
+        let sphere = SphereBuilder::new().create().unwrap();
+        let intersects = intersect(&ray, &sphere);
+        assert_eq!(intersects.as_ref().map(|res| res.is_empty()), Some(false));
+
+        let expected = [
+            Intersection::new(-1.0, &sphere),
+            Intersection::new(1.0, &sphere),
+        ];
+        assert!(intersects
+            .unwrap()
+            .iter()
+            .zip(&expected)
+            .all(|(a, b)| a == b));
+    }
+
+    #[test]
+    fn ray_origin_behind_sphere() {
+        let ray = Ray::new(point!(0.0, 0.0, 5.0), vector!(0.0, 0.0, 1.0));
+        let sphere = SphereBuilder::new().create().unwrap();
+        let intersects = intersect(&ray, &sphere);
+        assert_eq!(intersects.as_ref().map(|res| res.is_empty()), Some(false));
+
+        let expected = [
+            Intersection::new(-6.0, &sphere),
+            Intersection::new(-4.0, &sphere),
+        ];
+        assert!(intersects
+            .unwrap()
+            .iter()
+            .zip(&expected)
+            .all(|(a, b)| a == b));
+    }
+
+    #[test]
+    fn sphere_default_transformation_matrix() {
+        let sphere = SphereBuilder::new().create().unwrap();
+        assert_eq!(sphere.get_transformation().matrix(), &Mat4::identity());
+    }
+
+    #[test]
+    fn shpere_translate() {
+        let t = translation!(2.0, 3.0, 4.0);
+        let sphere = SphereBuilder::new()
+            .with_transformation(t)
+            .create()
+            .unwrap();
+        assert_eq!(&t, sphere.get_transformation().matrix());
+    }
+
+    #[test]
+    fn normal_at_sphere() {
+        let sphere = SphereBuilder::new().create().unwrap();
+        let hit = Intersection::new(0.0, &sphere);
+        let point = point!(1.0, 0.0, 0.0);
+        let normal = normal_at(&sphere, &point, &hit);
+        assert_eq!(normal, vector!(1.0, 0.0, 0.0));
+        let point = point!(0.0, 1.0, 0.0);
+        let normal = normal_at(&sphere, &point, &hit);
+        assert_eq!(normal, Vec4::new(0.0, 1.0, 0.0, 0.0));
+        let sq = 3f32.sqrt() / 3.0;
+        let point = point!(sq, sq, sq);
+        let normal = normal_at(&sphere, &point, &hit);
+        matrix_eq!(normal, vector!(sq, sq, sq));
+    }
+
+    #[test]
+    fn normal_is_normalized() {
+        let sphere = SphereBuilder::new().create().unwrap();
+        let sq = 3f32.sqrt() / 3.0;
+        let point = point!(sq, sq, sq);
+        let normal = normal_at(&sphere, &point, &Intersection::new(0.0, &sphere));
+        matrix_eq!(normal, normal.normalize());
+    }
+
+    #[test]
+    fn normal_on_translated_sphere() {
+        use std::f32::consts::FRAC_1_SQRT_2;
+        let mut sphere = SphereBuilder::new().create().unwrap();
+        sphere.transform(&translation!(0.0, 1.0, 0.0)).unwrap();
+        let normal = normal_at(
+            &sphere,
+            &point!(0.0, 1.70711, -FRAC_1_SQRT_2),
+            &Intersection::new(0.0, &sphere),
+        );
+        matrix_eq!(normal, vector!(0.0, FRAC_1_SQRT_2, -FRAC_1_SQRT_2));
+    }
+
+    #[test]
+    fn normal_on_scaled_rotated_sphere() {
+        let m = scaling!(1.0, 0.5, 1.0) * rotation!(0.0, 0.0, std::f32::consts::PI / 5.0);
+        let sphere = SphereBuilder::new()
+            .with_transformation(m)
+            .create()
+            .unwrap();
+        let sq = 2.0f32.sqrt() / 2.0;
+        let normal = normal_at(
+            &sphere,
+            &point!(0.0, sq, -sq),
+            &Intersection::new(0.0, &sphere),
+        );
+        matrix_eq!(normal, vector!(0.0, 0.97014, -0.24254));
+    }
+
+    #[test]
+    fn sphere_bounds_in_parent_space() {
+        let sphere = SphereBuilder::new()
+            .with_transformation(translation!(1.0, -3.0, 5.0) * scaling!(0.5, 2.0, 4.0))
+            .create()
+            .unwrap();
+        assert_eq!(sphere.bounds().min, point!(-1.0, -1.0, -1.0));
+        assert_eq!(sphere.parent_space_bounds().min, point!(0.5, -5.0, 1.0));
+        assert_eq!(sphere.parent_space_bounds().max, point!(1.5, -1.0, 9.0));
+    }
+}
@@ -0,0 +1,322 @@
+use crate::bounds::Bounds;
+use crate::intersection::{Intersection, Intersections};
+use crate::material::Material;
+use crate::math::*;
+use crate::objects::{Ray, Shape, ShapeProperties};
+use crate::transform::TransformError;
+
+pub struct TriangleBuilder {
+    vertices: [Point4; 3],
+    texture_coords: Option<[(f32, f32); 3]>,
+    transformation: Option<Mat4>,
+    material: Option<Material>,
+}
+
+impl TriangleBuilder {
+    pub fn new(p1: Point4, p2: Point4, p3: Point4) -> TriangleBuilder {
+        TriangleBuilder {
+            vertices: [p1, p2, p3],
+            texture_coords: None,
+            transformation: None,
+            material: None,
+        }
+    }
+
+    //Texture coordinates of p1, p2 and p3, interpolated over the face.
+    pub fn with_texture_coords(
+        &mut self,
+        uv1: (f32, f32),
+        uv2: (f32, f32),
+        uv3: (f32, f32),
+    ) -> &mut TriangleBuilder {
+        self.texture_coords.replace([uv1, uv2, uv3]);
+        self
+    }
+
+    pub fn with_transformation(&mut self, transformation: Mat4) -> &mut TriangleBuilder {
+        self.transformation.replace(transformation);
+        self
+    }
+
+    pub fn with_material(&mut self, material: Material) -> &mut TriangleBuilder {
+        self.material.replace(material);
+        self
+    }
+
+    pub fn create(&mut self) -> Result<Triangle, TransformError> {
+        let [p1, p2, p3] = self.vertices;
+        let mut result = Triangle::new(
+            ShapeProperties::new(
+                self.transformation.unwrap_or_else(Mat4::identity),
+                self.material.clone().unwrap_or_default(),
+            )?,
+            p1,
+            p2,
+            p3,
+        );
+        result.texture_coords = self.texture_coords;
+        self.transformation = None;
+        self.material = None;
+        Ok(result)
+    }
+}
+
+//Flat triangle with a single normal for the whole face.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Triangle {
+    properties: ShapeProperties,
+    p1: Point4,
+    p2: Point4,
+    p3: Point4,
+    e1: Vec3,
+    e2: Vec3,
+    normal: Vec4,
+    texture_coords: Option<[(f32, f32); 3]>,
+}
+
+impl Triangle {
+    fn new(properties: ShapeProperties, p1: Point4, p2: Point4, p3: Point4) -> Triangle {
+        let e1 = (p2 - p1).xyz();
+        let e2 = (p3 - p1).xyz();
+        let normal = e2.cross(&e1).normalize();
+        Triangle {
+            properties,
+            p1,
+            p2,
+            p3,
+            e1,
+            e2,
+            normal: vector!(normal.x, normal.y, normal.z),
+            texture_coords: None,
+        }
+    }
+
+    pub fn p1(&self) -> &Point4 {
+        &self.p1
+    }
+
+    pub fn p2(&self) -> &Point4 {
+        &self.p2
+    }
+
+    pub fn p3(&self) -> &Point4 {
+        &self.p3
+    }
+
+    pub fn normal(&self) -> &Vec4 {
+        &self.normal
+    }
+
+    pub fn texture_coords(&self) -> Option<&[(f32, f32); 3]> {
+        self.texture_coords.as_ref()
+    }
+}
+
+//Barycentric u and v (weights of p2 and p3) of a point in the plane of the triangle
+//p1 with edges e1 and e2, the same ones `intersect_triangle` gives for a hit there.
+pub(super) fn barycentric(point: &Point4, p1: &Point4, e1: &Vec3, e2: &Vec3) -> (f32, f32) {
+    let w = (point - p1).xyz();
+    let (d11, d12, d22) = (e1.dot(e1), e1.dot(e2), e2.dot(e2));
+    let (dw1, dw2) = (w.dot(e1), w.dot(e2));
+    let denominator = d11 * d22 - d12 * d12;
+    (
+        (d22 * dw1 - d12 * dw2) / denominator,
+        (d11 * dw2 - d12 * dw1) / denominator,
+    )
+}
+
+//Texture coordinates of the vertices blended with barycentric u and v.
+pub(super) fn interpolate_texture_coords(coords: &[(f32, f32); 3], u: f32, v: f32) -> (f32, f32) {
+    let [(u1, v1), (u2, v2), (u3, v3)] = *coords;
+    let w = 1.0 - u - v;
+    (u1 * w + u2 * u + u3 * v, v1 * w + v2 * u + v3 * v)
+}
+
+//Möller–Trumbore ray/triangle test for triangle p1 with edges e1 and e2.
+//Returns t of the hit together with barycentric u and v.
+pub(super) fn intersect_triangle(
+    ray: &Ray,
+    p1: &Point4,
+    e1: &Vec3,
+    e2: &Vec3,
+) -> Option<(f32, f32, f32)> {
+    let direction = ray.direction.xyz();
+    let dir_cross_e2 = direction.cross(e2);
+    let det = e1.dot(&dir_cross_e2);
+    //Ray parallel to the triangle plane. The threshold scales with the edges,
+    //so that tiny triangles of dense meshes are still hit.
+    if det.abs() <= EPSILON * e1.norm() * dir_cross_e2.norm() {
+        return None;
+    }
+
+    let f = 1.0 / det;
+    let p1_to_origin = (ray.origin - p1).xyz();
+    let u = f * p1_to_origin.dot(&dir_cross_e2);
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+
+    let origin_cross_e1 = p1_to_origin.cross(e1);
+    let v = f * direction.dot(&origin_cross_e1);
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let t = f * e2.dot(&origin_cross_e1);
+    Some((t, u, v))
+}
+
+impl Shape for Triangle {
+    fn properties(&self) -> &ShapeProperties {
+        &self.properties
+    }
+
+    fn properties_mut(&mut self) -> &mut ShapeProperties {
+        &mut self.properties
+    }
+
+    fn local_intersect<'a>(&'a self, ray: &Ray) -> Intersections<'a> {
+        match intersect_triangle(ray, &self.p1, &self.e1, &self.e2) {
+            Some((t, u, v)) => vec![Intersection::with_uv(t, self, u, v)],
+            None => Intersections::new(),
+        }
+    }
+
+    fn local_normal_at(&self, _point: &Point4, _hit: &Intersection) -> Vec4 {
+        self.normal
+    }
+
+    fn texture_coords_at(&self, point: &Point4) -> Option<(f32, f32)> {
+        let coords = self.texture_coords.as_ref()?;
+        let (u, v) = barycentric(point, &self.p1, &self.e1, &self.e2);
+        Some(interpolate_texture_coords(coords, u, v))
+    }
+
+    fn bounds(&self) -> Bounds {
+        let mut bounds = Bounds::empty();
+        bounds.add_point(&self.p1);
+        bounds.add_point(&self.p2);
+        bounds.add_point(&self.p3);
+        bounds
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn test_triangle() -> Triangle {
+        TriangleBuilder::new(
+            point!(0.0, 1.0, 0.0),
+            point!(-1.0, 0.0, 0.0),
+            point!(1.0, 0.0, 0.0),
+        )
+        .create()
+        .unwrap()
+    }
+
+    #[test]
+    fn triangle_construction() {
+        let t = test_triangle();
+        assert_eq!(t.p1(), &point!(0.0, 1.0, 0.0));
+        assert_eq!(t.p2(), &point!(-1.0, 0.0, 0.0));
+        assert_eq!(t.p3(), &point!(1.0, 0.0, 0.0));
+        assert_eq!(t.e1, Vec3::new(-1.0, -1.0, 0.0));
+        assert_eq!(t.e2, Vec3::new(1.0, -1.0, 0.0));
+        assert_eq!(t.normal(), &vector!(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn triangle_normal_is_constant() {
+        let t = test_triangle();
+        let hit = Intersection::new(0.0, &t);
+        assert_eq!(t.local_normal_at(&point!(0.0, 0.5, 0.0), &hit), t.normal);
+        assert_eq!(t.local_normal_at(&point!(-0.5, 0.75, 0.0), &hit), t.normal);
+        assert_eq!(t.local_normal_at(&point!(0.5, 0.25, 0.0), &hit), t.normal);
+    }
+
+    #[test]
+    fn ray_parallel_to_triangle() {
+        let t = test_triangle();
+        let ray = Ray::new(point!(0.0, -1.0, -2.0), vector!(0.0, 1.0, 0.0));
+        assert!(t.local_intersect(&ray).is_empty());
+    }
+
+    #[test]
+    fn ray_misses_triangle_edges() {
+        let t = test_triangle();
+        let origins = [
+            point!(1.0, 1.0, -2.0),
+            point!(-1.0, 1.0, -2.0),
+            point!(0.0, -1.0, -2.0),
+        ];
+        for origin in origins.iter() {
+            let ray = Ray::new(*origin, vector!(0.0, 0.0, 1.0));
+            assert!(t.local_intersect(&ray).is_empty());
+        }
+    }
+
+    #[test]
+    fn ray_strikes_triangle() {
+        let t = test_triangle();
+        let ray = Ray::new(point!(0.0, 0.5, -2.0), vector!(0.0, 0.0, 1.0));
+        let xs = t.local_intersect(&ray);
+        assert_eq!(xs.len(), 1);
+        assert_eq!(xs[0].t, 2.0);
+    }
+
+    #[test]
+    fn ray_strikes_small_triangle() {
+        let t = TriangleBuilder::new(
+            point!(0.0, 0.005, 0.0),
+            point!(-0.005, 0.0, 0.0),
+            point!(0.005, 0.0, 0.0),
+        )
+        .create()
+        .unwrap();
+        let ray = Ray::new(point!(0.0, 0.0025, -2.0), vector!(0.0, 0.0, 1.0));
+        let xs = t.local_intersect(&ray);
+        assert_eq!(xs.len(), 1);
+        assert_eq!(xs[0].t, 2.0);
+    }
+
+    #[test]
+    fn triangle_hit_stores_uv() {
+        let t = test_triangle();
+        let ray = Ray::new(point!(-0.2, 0.3, -2.0), vector!(0.0, 0.0, 1.0));
+        let xs = t.local_intersect(&ray);
+        assert_eq!(xs.len(), 1);
+        assert!((xs[0].u - 0.45).abs() < EPSILON);
+        assert!((xs[0].v - 0.25).abs() < EPSILON);
+    }
+
+    #[test]
+    fn texture_coords_are_interpolated() {
+        let mut t = test_triangle();
+        assert_eq!(t.texture_coords_at(&point!(0.0, 0.5, 0.0)), None);
+        t = TriangleBuilder::new(*t.p1(), *t.p2(), *t.p3())
+            .with_texture_coords((0.5, 1.0), (0.0, 0.0), (1.0, 0.0))
+            .create()
+            .unwrap();
+        let uv = |point| t.texture_coords_at(&point).unwrap();
+        assert_eq!(uv(point!(0.0, 1.0, 0.0)), (0.5, 1.0));
+        assert_eq!(uv(point!(-1.0, 0.0, 0.0)), (0.0, 0.0));
+        assert_eq!(uv(point!(1.0, 0.0, 0.0)), (1.0, 0.0));
+        let (u, v) = uv(point!(0.0, 0.25, 0.0));
+        assert!((u - 0.5).abs() < EPSILON);
+        assert!((v - 0.25).abs() < EPSILON);
+    }
+
+    #[test]
+    fn triangle_bounds() {
+        let t = TriangleBuilder::new(
+            point!(-3.0, 7.0, 2.0),
+            point!(6.0, 2.0, -4.0),
+            point!(2.0, -1.0, -1.0),
+        )
+        .create()
+        .unwrap();
+        assert_eq!(t.bounds().min, point!(-3.0, -1.0, -4.0));
+        assert_eq!(t.bounds().max, point!(6.0, 7.0, 2.0));
+    }
+}
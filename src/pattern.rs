@@ -0,0 +1,609 @@
+use crate::math::*;
+use crate::noise::{Perlin, DEFAULT_OCTAVES};
+use crate::objects::Shape;
+use crate::transform::Transform;
+use std::f32::consts::PI;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+/// Color that changes over the surface of a shape.
+/// Implementors work in pattern space: object space of the shape moved by the
+/// transformation of the pattern, so the same pattern can be scaled or rotated
+/// independently of the shape it is applied to.
+/// Send and Sync, so materials sharing a pattern can be rendered from many threads.
+pub trait Pattern: Debug + Send + Sync {
+    fn transformation(&self) -> &Transform;
+
+    fn set_transformation(&mut self, transformation: Transform);
+
+    //Color at the point given in pattern space.
+    fn local_pattern_at(&self, point: &Point4) -> Color;
+
+    //Color of the pattern on the shape at the point given in world space.
+    fn pattern_at(&self, object: &dyn Shape, world_point: &Point4) -> Color {
+        let object_point = object.world_transformation().inverse() * world_point;
+        let pattern_point = self.transformation().inverse() * object_point;
+        self.local_pattern_at(&pattern_point)
+    }
+}
+
+//Is the integer part of the coordinate even. Works for negative values too, as -0.5 floors to -1.
+fn is_even(value: f32) -> bool {
+    value.floor().rem_euclid(2.0) == 0.0
+}
+
+//Linear blend, `a` for t = 0 and `b` for t = 1.
+fn blend(a: Color, b: Color, t: f32) -> Color {
+    a + (b - a) * t
+}
+
+//Stripes alternating along x, every one of them is 1 unit wide.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct StripePattern {
+    a: Color,
+    b: Color,
+    transformation: Transform,
+}
+
+impl StripePattern {
+    pub fn new(a: Color, b: Color) -> StripePattern {
+        StripePattern {
+            a,
+            b,
+            transformation: Transform::identity(),
+        }
+    }
+
+    pub fn a(&self) -> Color {
+        self.a
+    }
+
+    pub fn b(&self) -> Color {
+        self.b
+    }
+}
+
+impl Pattern for StripePattern {
+    fn transformation(&self) -> &Transform {
+        &self.transformation
+    }
+
+    fn set_transformation(&mut self, transformation: Transform) {
+        self.transformation = transformation;
+    }
+
+    fn local_pattern_at(&self, point: &Point4) -> Color {
+        if is_even(point.x) {
+            self.a
+        } else {
+            self.b
+        }
+    }
+}
+
+//Linear blend from `a` at x = 0 to `b` at x = 1, repeating every unit.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GradientPattern {
+    a: Color,
+    b: Color,
+    transformation: Transform,
+}
+
+impl GradientPattern {
+    pub fn new(a: Color, b: Color) -> GradientPattern {
+        GradientPattern {
+            a,
+            b,
+            transformation: Transform::identity(),
+        }
+    }
+}
+
+impl Pattern for GradientPattern {
+    fn transformation(&self) -> &Transform {
+        &self.transformation
+    }
+
+    fn set_transformation(&mut self, transformation: Transform) {
+        self.transformation = transformation;
+    }
+
+    fn local_pattern_at(&self, point: &Point4) -> Color {
+        blend(self.a, self.b, point.x - point.x.floor())
+    }
+}
+
+//Concentric rings around the y axis, 1 unit wide.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RingPattern {
+    a: Color,
+    b: Color,
+    transformation: Transform,
+}
+
+impl RingPattern {
+    pub fn new(a: Color, b: Color) -> RingPattern {
+        RingPattern {
+            a,
+            b,
+            transformation: Transform::identity(),
+        }
+    }
+}
+
+impl Pattern for RingPattern {
+    fn transformation(&self) -> &Transform {
+        &self.transformation
+    }
+
+    fn set_transformation(&mut self, transformation: Transform) {
+        self.transformation = transformation;
+    }
+
+    fn local_pattern_at(&self, point: &Point4) -> Color {
+        if is_even((point.x * point.x + point.z * point.z).sqrt()) {
+            self.a
+        } else {
+            self.b
+        }
+    }
+}
+
+//Unit cubes of alternating colors filling the whole space.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CheckersPattern {
+    a: Color,
+    b: Color,
+    transformation: Transform,
+}
+
+impl CheckersPattern {
+    pub fn new(a: Color, b: Color) -> CheckersPattern {
+        CheckersPattern {
+            a,
+            b,
+            transformation: Transform::identity(),
+        }
+    }
+}
+
+impl Pattern for CheckersPattern {
+    fn transformation(&self) -> &Transform {
+        &self.transformation
+    }
+
+    fn set_transformation(&mut self, transformation: Transform) {
+        self.transformation = transformation;
+    }
+
+    fn local_pattern_at(&self, point: &Point4) -> Color {
+        //Tiny offset keeps surfaces lying exactly on cell borders (like planes) from speckling.
+        let sum =
+            (point.x + EPSILON).floor() + (point.y + EPSILON).floor() + (point.z + EPSILON).floor();
+        if sum.rem_euclid(2.0) == 0.0 {
+            self.a
+        } else {
+            self.b
+        }
+    }
+}
+
+//Cloudy blend of two colors driven by the turbulence of the noise.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TurbulencePattern {
+    a: Color,
+    b: Color,
+    noise: Perlin,
+    octaves: u32,
+    transformation: Transform,
+}
+
+impl TurbulencePattern {
+    pub fn new(a: Color, b: Color, seed: u64) -> TurbulencePattern {
+        TurbulencePattern {
+            a,
+            b,
+            noise: Perlin::new(seed),
+            octaves: DEFAULT_OCTAVES,
+            transformation: Transform::identity(),
+        }
+    }
+
+    pub fn set_octaves(&mut self, octaves: u32) {
+        self.octaves = octaves;
+    }
+}
+
+impl Pattern for TurbulencePattern {
+    fn transformation(&self) -> &Transform {
+        &self.transformation
+    }
+
+    fn set_transformation(&mut self, transformation: Transform) {
+        self.transformation = transformation;
+    }
+
+    fn local_pattern_at(&self, point: &Point4) -> Color {
+        let t = self.noise.turbulence(point, self.octaves).min(1.0);
+        blend(self.a, self.b, t)
+    }
+}
+
+//Veins running along the x axis, bent by turbulence. With `turbulence` set to 0
+//they are perfectly straight bands, 1 unit wide.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarblePattern {
+    a: Color,
+    b: Color,
+    turbulence: f32,
+    noise: Perlin,
+    octaves: u32,
+    transformation: Transform,
+}
+
+impl MarblePattern {
+    pub fn new(a: Color, b: Color, turbulence: f32, seed: u64) -> MarblePattern {
+        MarblePattern {
+            a,
+            b,
+            turbulence,
+            noise: Perlin::new(seed),
+            octaves: DEFAULT_OCTAVES,
+            transformation: Transform::identity(),
+        }
+    }
+
+    pub fn set_octaves(&mut self, octaves: u32) {
+        self.octaves = octaves;
+    }
+}
+
+impl Pattern for MarblePattern {
+    fn transformation(&self) -> &Transform {
+        &self.transformation
+    }
+
+    fn set_transformation(&mut self, transformation: Transform) {
+        self.transformation = transformation;
+    }
+
+    fn local_pattern_at(&self, point: &Point4) -> Color {
+        let phase = point.x + self.turbulence * self.noise.turbulence(point, self.octaves);
+        let t = 0.5 - 0.5 * (phase * PI).cos();
+        blend(self.a, self.b, t)
+    }
+}
+
+//Growth rings around the y axis, like `RingPattern`, but blended smoothly and
+//made irregular by the noise. Rings are 1 unit apart.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WoodPattern {
+    a: Color,
+    b: Color,
+    turbulence: f32,
+    noise: Perlin,
+    octaves: u32,
+    transformation: Transform,
+}
+
+impl WoodPattern {
+    pub fn new(a: Color, b: Color, turbulence: f32, seed: u64) -> WoodPattern {
+        WoodPattern {
+            a,
+            b,
+            turbulence,
+            noise: Perlin::new(seed),
+            octaves: DEFAULT_OCTAVES,
+            transformation: Transform::identity(),
+        }
+    }
+
+    pub fn set_octaves(&mut self, octaves: u32) {
+        self.octaves = octaves;
+    }
+}
+
+impl Pattern for WoodPattern {
+    fn transformation(&self) -> &Transform {
+        &self.transformation
+    }
+
+    fn set_transformation(&mut self, transformation: Transform) {
+        self.transformation = transformation;
+    }
+
+    fn local_pattern_at(&self, point: &Point4) -> Color {
+        let distance = (point.x * point.x + point.z * point.z).sqrt()
+            + self.turbulence * self.noise.fbm(point, self.octaves);
+        let t = 0.5 - 0.5 * (distance * 2.0 * PI).cos();
+        blend(self.a, self.b, t)
+    }
+}
+
+//Wraps another pattern and jitters the point it is sampled at, so straight
+//stripes or checkers get wobbly edges. The wrapped pattern keeps its own transformation,
+//applied after the jitter.
+#[derive(Debug, Clone)]
+pub struct PerturbedPattern {
+    pattern: Arc<dyn Pattern>,
+    scale: f32,
+    noise: Perlin,
+    transformation: Transform,
+}
+
+impl PerturbedPattern {
+    //`scale` is the largest distance a point can be moved by along each axis.
+    pub fn new(pattern: Arc<dyn Pattern>, scale: f32, seed: u64) -> PerturbedPattern {
+        PerturbedPattern {
+            pattern,
+            scale,
+            noise: Perlin::new(seed),
+            transformation: Transform::identity(),
+        }
+    }
+}
+
+impl Pattern for PerturbedPattern {
+    fn transformation(&self) -> &Transform {
+        &self.transformation
+    }
+
+    fn set_transformation(&mut self, transformation: Transform) {
+        self.transformation = transformation;
+    }
+
+    fn local_pattern_at(&self, point: &Point4) -> Color {
+        //Offsets decorrelate the three axes, otherwise every point would move along the diagonal.
+        let dx = self.noise.noise(point);
+        let dy = self.noise.noise(&(point + vector!(31.7, 17.3, 5.9)));
+        let dz = self.noise.noise(&(point + vector!(-13.1, 47.9, 23.3)));
+        let jittered = point + vector!(dx, dy, dz) * self.scale;
+        let inner_point = self.pattern.transformation().inverse() * jittered;
+        self.pattern.local_pattern_at(&inner_point)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::objects::SphereBuilder;
+
+    //Pattern that shows where it was sampled.
+    #[derive(Debug, Default)]
+    struct TestPattern {
+        transformation: Transform,
+    }
+
+    impl Pattern for TestPattern {
+        fn transformation(&self) -> &Transform {
+            &self.transformation
+        }
+
+        fn set_transformation(&mut self, transformation: Transform) {
+            self.transformation = transformation;
+        }
+
+        fn local_pattern_at(&self, point: &Point4) -> Color {
+            Color::new(point.x, point.y, point.z)
+        }
+    }
+
+    fn white() -> Color {
+        Color::new(1.0, 1.0, 1.0)
+    }
+
+    fn black() -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    #[test]
+    fn creating_stripe_pattern() {
+        let pattern = StripePattern::new(white(), black());
+        assert_eq!(pattern.a(), white());
+        assert_eq!(pattern.b(), black());
+        assert_eq!(pattern.transformation(), &Transform::identity());
+    }
+
+    #[test]
+    fn stripe_pattern_is_constant_in_y_and_z() {
+        let pattern = StripePattern::new(white(), black());
+        for &(y, z) in [(0.0, 0.0), (1.0, 0.0), (2.0, 0.0), (0.0, 1.0), (0.0, 2.0)].iter() {
+            assert_eq!(pattern.local_pattern_at(&point!(0.0, y, z)), white());
+        }
+    }
+
+    #[test]
+    fn stripe_pattern_alternates_in_x() {
+        let pattern = StripePattern::new(white(), black());
+        let cases = [
+            (0.0, white()),
+            (0.9, white()),
+            (1.0, black()),
+            (-0.1, black()),
+            (-1.0, black()),
+            (-1.1, white()),
+        ];
+        for (x, expected) in cases.iter() {
+            assert_eq!(pattern.local_pattern_at(&point!(*x, 0.0, 0.0)), *expected);
+        }
+    }
+
+    #[test]
+    fn pattern_with_object_transformation() {
+        let object = SphereBuilder::new()
+            .with_transformation(scaling!(2.0, 2.0, 2.0))
+            .create()
+            .unwrap();
+        let pattern = TestPattern::default();
+        let color = pattern.pattern_at(&object, &point!(2.0, 3.0, 4.0));
+        assert_eq!(color, Color::new(1.0, 1.5, 2.0));
+    }
+
+    #[test]
+    fn pattern_with_pattern_transformation() {
+        let object = SphereBuilder::new().create().unwrap();
+        let mut pattern = TestPattern::default();
+        pattern.set_transformation(Transform::new(scaling!(2.0, 2.0, 2.0)).unwrap());
+        let color = pattern.pattern_at(&object, &point!(2.0, 3.0, 4.0));
+        assert_eq!(color, Color::new(1.0, 1.5, 2.0));
+    }
+
+    #[test]
+    fn pattern_with_object_and_pattern_transformation() {
+        let object = SphereBuilder::new()
+            .with_transformation(scaling!(2.0, 2.0, 2.0))
+            .create()
+            .unwrap();
+        let mut pattern = TestPattern::default();
+        pattern.set_transformation(Transform::new(translation!(0.5, 1.0, 1.5)).unwrap());
+        let color = pattern.pattern_at(&object, &point!(2.5, 3.0, 3.5));
+        assert_eq!(color, Color::new(0.75, 0.5, 0.25));
+    }
+
+    #[test]
+    fn stripes_follow_both_transformations() {
+        let object = SphereBuilder::new()
+            .with_transformation(scaling!(2.0, 2.0, 2.0))
+            .create()
+            .unwrap();
+        let mut pattern = StripePattern::new(white(), black());
+        pattern.set_transformation(Transform::new(translation!(0.5, 0.0, 0.0)).unwrap());
+        assert_eq!(pattern.pattern_at(&object, &point!(2.5, 0.0, 0.0)), white());
+        assert_eq!(pattern.pattern_at(&object, &point!(3.5, 0.0, 0.0)), black());
+    }
+
+    #[test]
+    fn gradient_interpolates_between_colors() {
+        let pattern = GradientPattern::new(white(), black());
+        let cases = [
+            (0.0, white()),
+            (0.25, Color::new(0.75, 0.75, 0.75)),
+            (0.5, Color::new(0.5, 0.5, 0.5)),
+            (0.75, Color::new(0.25, 0.25, 0.25)),
+        ];
+        for (x, expected) in cases.iter() {
+            assert_eq!(pattern.local_pattern_at(&point!(*x, 0.0, 0.0)), *expected);
+        }
+    }
+
+    #[test]
+    fn ring_extends_in_x_and_z() {
+        let pattern = RingPattern::new(white(), black());
+        assert_eq!(pattern.local_pattern_at(&point!(0.0, 0.0, 0.0)), white());
+        assert_eq!(pattern.local_pattern_at(&point!(1.0, 0.0, 0.0)), black());
+        assert_eq!(pattern.local_pattern_at(&point!(0.0, 0.0, 1.0)), black());
+        //0.708 is just over sqrt(2)/2.
+        assert_eq!(
+            pattern.local_pattern_at(&point!(0.708, 0.0, 0.708)),
+            black()
+        );
+    }
+
+    #[test]
+    fn checkers_repeat_in_every_dimension() {
+        let pattern = CheckersPattern::new(white(), black());
+        let cases = [
+            (point!(0.0, 0.0, 0.0), white()),
+            (point!(0.99, 0.0, 0.0), white()),
+            (point!(1.01, 0.0, 0.0), black()),
+            (point!(0.0, 0.99, 0.0), white()),
+            (point!(0.0, 1.01, 0.0), black()),
+            (point!(0.0, 0.0, 0.99), white()),
+            (point!(0.0, 0.0, 1.01), black()),
+        ];
+        for (point, expected) in cases.iter() {
+            assert_eq!(pattern.local_pattern_at(point), *expected);
+        }
+    }
+
+    #[test]
+    fn noise_patterns_are_reproducible() {
+        let point = point!(0.3, -1.2, 2.7);
+        let marble = MarblePattern::new(white(), black(), 2.0, 11);
+        assert_eq!(
+            marble.local_pattern_at(&point),
+            MarblePattern::new(white(), black(), 2.0, 11).local_pattern_at(&point)
+        );
+        let wood = WoodPattern::new(white(), black(), 0.5, 11);
+        assert_eq!(
+            wood.local_pattern_at(&point),
+            WoodPattern::new(white(), black(), 0.5, 11).local_pattern_at(&point)
+        );
+        let turbulence = TurbulencePattern::new(white(), black(), 11);
+        assert_eq!(
+            turbulence.local_pattern_at(&point),
+            TurbulencePattern::new(white(), black(), 11).local_pattern_at(&point)
+        );
+    }
+
+    #[test]
+    fn turbulence_blends_between_colors() {
+        let pattern = TurbulencePattern::new(white(), black(), 3);
+        //Noise is 0 on lattice points at every octave.
+        assert_eq!(pattern.local_pattern_at(&point!(1.0, 2.0, 3.0)), white());
+        let color = pattern.local_pattern_at(&point!(0.4, 0.7, 0.2));
+        assert!(color.r() > 0.0 && color.r() < 1.0);
+    }
+
+    #[test]
+    fn marble_without_turbulence_is_banded_along_x() {
+        let pattern = MarblePattern::new(white(), black(), 0.0, 0);
+        matrix_eq!(
+            pattern.local_pattern_at(&point!(0.0, 0.3, 0.8)).as_array(),
+            [1.0, 1.0, 1.0]
+        );
+        matrix_eq!(
+            pattern.local_pattern_at(&point!(1.0, -2.0, 4.0)).as_array(),
+            [0.0, 0.0, 0.0]
+        );
+        matrix_eq!(
+            pattern.local_pattern_at(&point!(0.5, 5.0, 0.0)).as_array(),
+            [0.5, 0.5, 0.5]
+        );
+    }
+
+    #[test]
+    fn wood_without_turbulence_has_rings_around_y() {
+        let pattern = WoodPattern::new(white(), black(), 0.0, 0);
+        matrix_eq!(
+            pattern.local_pattern_at(&point!(0.0, 7.0, 0.0)).as_array(),
+            [1.0, 1.0, 1.0]
+        );
+        matrix_eq!(
+            pattern.local_pattern_at(&point!(0.5, 0.0, 0.0)).as_array(),
+            [0.0, 0.0, 0.0]
+        );
+        matrix_eq!(
+            pattern.local_pattern_at(&point!(0.0, 0.0, -1.0)).as_array(),
+            [1.0, 1.0, 1.0]
+        );
+    }
+
+    #[test]
+    fn perturbed_pattern_moves_sample_point() {
+        let stripes: Arc<dyn Pattern> = Arc::new(StripePattern::new(white(), black()));
+        let still = PerturbedPattern::new(stripes.clone(), 0.0, 1);
+        assert_eq!(still.local_pattern_at(&point!(0.99, 0.0, 0.0)), white());
+
+        let perturbed = PerturbedPattern::new(stripes, 0.5, 1);
+        //Noise vanishes on lattice points, so they are left where they are.
+        assert_eq!(perturbed.local_pattern_at(&point!(1.0, 0.0, 0.0)), black());
+        let moved = (0..100).any(|i| {
+            let point = point!(i as f32 * 0.1 + 0.05, 0.3, 0.6);
+            perturbed.local_pattern_at(&point) != stripes_at(&point)
+        });
+        assert!(moved);
+    }
+
+    fn stripes_at(point: &Point4) -> Color {
+        StripePattern::new(white(), black()).local_pattern_at(point)
+    }
+
+    #[test]
+    fn perturbed_pattern_uses_inner_transformation() {
+        let mut stripes = StripePattern::new(white(), black());
+        stripes.set_transformation(Transform::new(translation!(0.5, 0.0, 0.0)).unwrap());
+        let perturbed = PerturbedPattern::new(Arc::new(stripes), 0.0, 0);
+        assert_eq!(perturbed.local_pattern_at(&point!(0.25, 0.0, 0.0)), black());
+    }
+}
@@ -0,0 +1,301 @@
+use crate::noise::next_random;
+
+//Where, inside of a pixel, rays are shot through.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum Sampler {
+    //Centers of the cells of an even grid. A single sample is the pixel center.
+    #[default]
+    Regular,
+    //Same grid, but every sample is moved randomly within its cell. Trades
+    //aliasing for noise. The same seed always gives the same image.
+    Stratified {
+        seed: u64,
+    },
+    //Halton sequence (bases 2 and 3), evenly spread for any number of samples.
+    //Shifted differently in every pixel, so the pattern doesn't repeat.
+    Halton,
+}
+
+//Rows and columns of a grid with exactly `count` cells, as close to square as possible.
+fn grid_size(count: u32) -> (u32, u32) {
+    let rows = (1..=count)
+        .take_while(|rows| rows * rows <= count)
+        .filter(|rows| count.is_multiple_of(*rows))
+        .last()
+        .unwrap_or(1);
+    (rows, count / rows)
+}
+
+//Digits of `index` in the given base mirrored around the decimal point.
+fn radical_inverse(mut index: u32, base: u32) -> f32 {
+    let mut result = 0.0;
+    let mut fraction = 1.0 / base as f32;
+    while index > 0 {
+        result += (index % base) as f32 * fraction;
+        index /= base;
+        fraction /= base as f32;
+    }
+    result
+}
+
+//Random number in [0, 1) from the top 24 bits, all an f32 can hold.
+fn random_f32(state: &mut u64) -> f32 {
+    (next_random(state) >> 40) as f32 / (1u64 << 24) as f32
+}
+
+fn pixel_state(seed: u64, x: u32, y: u32) -> u64 {
+    let mut state = seed ^ ((x as u64) << 32 | y as u64);
+    next_random(&mut state)
+}
+
+impl Sampler {
+    //Offsets of `count` samples from the top left corner of pixel (x, y), all in [0, 1).
+    pub fn offsets(&self, x: u32, y: u32, count: u32) -> Vec<(f32, f32)> {
+        let count = count.max(1);
+        match *self {
+            Sampler::Regular => {
+                let (rows, columns) = grid_size(count);
+                (0..count)
+                    .map(|i| {
+                        let (row, column) = (i / columns, i % columns);
+                        (
+                            (column as f32 + 0.5) / columns as f32,
+                            (row as f32 + 0.5) / rows as f32,
+                        )
+                    })
+                    .collect()
+            }
+            Sampler::Stratified { seed } => {
+                let (rows, columns) = grid_size(count);
+                let mut state = pixel_state(seed, x, y);
+                (0..count)
+                    .map(|i| {
+                        let (row, column) = (i / columns, i % columns);
+                        (
+                            (column as f32 + random_f32(&mut state)) / columns as f32,
+                            (row as f32 + random_f32(&mut state)) / rows as f32,
+                        )
+                    })
+                    .collect()
+            }
+            Sampler::Halton => {
+                let mut state = pixel_state(0, x, y);
+                let (shift_x, shift_y) = (random_f32(&mut state), random_f32(&mut state));
+                //Index 0 is (0, 0) in every base, so the sequence starts at 1.
+                (1..=count)
+                    .map(|i| {
+                        (
+                            (radical_inverse(i, 2) + shift_x).fract(),
+                            (radical_inverse(i, 3) + shift_y).fract(),
+                        )
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+//How much a sample contributes to the pixels around it, by its distance (in pixels)
+//from their centers. Every pixel is the weighted average of the samples near it.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum ReconstructionFilter {
+    //Plain average of the samples inside the pixel. The pixel covers [-0.5, 0.5)
+    //around its center, so a sample on a border counts in exactly one pixel.
+    #[default]
+    Box,
+    //Weight falls linearly to 0 at `radius`.
+    Tent {
+        radius: f32,
+    },
+    //Bell curve e^(-alpha * d^2), shifted to be 0 at `radius`.
+    Gaussian {
+        radius: f32,
+        alpha: f32,
+    },
+    //Cubic with a radius of 2 pixels and slightly negative lobes, which keeps
+    //edges sharp. b = c = 1/3 is the usual choice.
+    MitchellNetravali {
+        b: f32,
+        c: f32,
+    },
+}
+
+impl ReconstructionFilter {
+    //Samples farther than this from the pixel center, along either axis, don't count.
+    pub fn radius(&self) -> f32 {
+        match *self {
+            ReconstructionFilter::Box => 0.5,
+            ReconstructionFilter::Tent { radius } => radius,
+            ReconstructionFilter::Gaussian { radius, .. } => radius,
+            ReconstructionFilter::MitchellNetravali { .. } => 2.0,
+        }
+    }
+
+    pub fn weight(&self, dx: f32, dy: f32) -> f32 {
+        let radius = self.radius();
+        if dx.abs() > radius || dy.abs() > radius {
+            return 0.0;
+        }
+        match *self {
+            //[-0.5, 0.5), so a sample on a border counts in only one of the pixels.
+            ReconstructionFilter::Box if dx == 0.5 || dy == 0.5 => 0.0,
+            ReconstructionFilter::Box => 1.0,
+            ReconstructionFilter::Tent { radius } => {
+                (1.0 - dx.abs() / radius) * (1.0 - dy.abs() / radius)
+            }
+            ReconstructionFilter::Gaussian { radius, alpha } => {
+                let gaussian = |d: f32| (-alpha * d * d).exp() - (-alpha * radius * radius).exp();
+                gaussian(dx) * gaussian(dy)
+            }
+            ReconstructionFilter::MitchellNetravali { b, c } => {
+                mitchell(dx, b, c) * mitchell(dy, b, c)
+            }
+        }
+    }
+}
+
+fn mitchell(d: f32, b: f32, c: f32) -> f32 {
+    let x = d.abs();
+    let value = if x < 1.0 {
+        (12.0 - 9.0 * b - 6.0 * c) * x * x * x
+            + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+            + (6.0 - 2.0 * b)
+    } else if x < 2.0 {
+        (-b - 6.0 * c) * x * x * x
+            + (6.0 * b + 30.0 * c) * x * x
+            + (-12.0 * b - 48.0 * c) * x
+            + (8.0 * b + 24.0 * c)
+    } else {
+        0.0
+    };
+    value / 6.0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::math::EPSILON;
+
+    fn all_inside(offsets: &[(f32, f32)]) -> bool {
+        offsets
+            .iter()
+            .all(|&(x, y)| (0.0..1.0).contains(&x) && (0.0..1.0).contains(&y))
+    }
+
+    #[test]
+    fn single_regular_sample_is_pixel_center() {
+        assert_eq!(Sampler::Regular.offsets(3, 4, 1), vec![(0.5, 0.5)]);
+        assert_eq!(Sampler::Regular.offsets(3, 4, 0), vec![(0.5, 0.5)]);
+    }
+
+    #[test]
+    fn regular_samples_form_grid() {
+        let offsets = Sampler::Regular.offsets(0, 0, 4);
+        assert_eq!(
+            offsets,
+            vec![(0.25, 0.25), (0.75, 0.25), (0.25, 0.75), (0.75, 0.75)]
+        );
+        assert_eq!(grid_size(6), (2, 3));
+        assert_eq!(grid_size(7), (1, 7));
+        assert_eq!(grid_size(16), (4, 4));
+    }
+
+    #[test]
+    fn stratified_samples_stay_in_their_cells() {
+        let sampler = Sampler::Stratified { seed: 9 };
+        let offsets = sampler.offsets(10, 20, 4);
+        assert_eq!(offsets.len(), 4);
+        let cells = [(0.0, 0.0), (0.5, 0.0), (0.0, 0.5), (0.5, 0.5)];
+        for (&(x, y), &(cx, cy)) in offsets.iter().zip(cells.iter()) {
+            assert!(x >= cx && x < cx + 0.5);
+            assert!(y >= cy && y < cy + 0.5);
+        }
+        assert_ne!(offsets, Sampler::Regular.offsets(10, 20, 4));
+    }
+
+    #[test]
+    fn stratified_samples_are_reproducible() {
+        let sampler = Sampler::Stratified { seed: 9 };
+        assert_eq!(sampler.offsets(1, 2, 9), sampler.offsets(1, 2, 9));
+        assert_ne!(sampler.offsets(1, 2, 9), sampler.offsets(2, 1, 9));
+        let other = Sampler::Stratified { seed: 10 };
+        assert_ne!(sampler.offsets(1, 2, 9), other.offsets(1, 2, 9));
+    }
+
+    #[test]
+    fn radical_inverse_mirrors_digits() {
+        assert_eq!(radical_inverse(1, 2), 0.5);
+        assert_eq!(radical_inverse(2, 2), 0.25);
+        assert_eq!(radical_inverse(3, 2), 0.75);
+        assert!((radical_inverse(1, 3) - 1.0 / 3.0).abs() < EPSILON);
+        assert!((radical_inverse(5, 3) - 7.0 / 9.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn halton_samples_cover_pixel() {
+        let offsets = Sampler::Halton.offsets(5, 5, 16);
+        assert_eq!(offsets.len(), 16);
+        assert!(all_inside(&offsets));
+        //Every quarter of the pixel gets the same share of samples.
+        for &(qx, qy) in [(0.0, 0.0), (0.5, 0.0), (0.0, 0.5), (0.5, 0.5)].iter() {
+            let inside = offsets
+                .iter()
+                .filter(|&&(x, y)| {
+                    let (x, y) = ((x - qx).rem_euclid(1.0), (y - qy).rem_euclid(1.0));
+                    x < 0.5 && y < 0.5
+                })
+                .count();
+            assert!((2..=6).contains(&inside));
+        }
+    }
+
+    #[test]
+    fn box_filter_covers_pixel() {
+        let filter = ReconstructionFilter::Box;
+        assert_eq!(filter.weight(0.0, 0.0), 1.0);
+        assert_eq!(filter.weight(0.49, -0.49), 1.0);
+        assert_eq!(filter.weight(0.51, 0.0), 0.0);
+        //Left and top borders belong to the pixel, right and bottom ones don't.
+        assert_eq!(filter.weight(-0.5, -0.5), 1.0);
+        assert_eq!(filter.weight(0.5, 0.0), 0.0);
+        assert_eq!(filter.weight(0.0, 0.5), 0.0);
+    }
+
+    #[test]
+    fn tent_filter_falls_off_linearly() {
+        let filter = ReconstructionFilter::Tent { radius: 1.0 };
+        assert_eq!(filter.weight(0.0, 0.0), 1.0);
+        assert_eq!(filter.weight(0.5, 0.0), 0.5);
+        assert_eq!(filter.weight(0.5, -0.5), 0.25);
+        assert_eq!(filter.weight(1.0, 0.0), 0.0);
+        assert_eq!(filter.weight(1.5, 0.0), 0.0);
+    }
+
+    #[test]
+    fn gaussian_filter_is_zero_at_radius() {
+        let filter = ReconstructionFilter::Gaussian {
+            radius: 1.5,
+            alpha: 2.0,
+        };
+        assert!(filter.weight(0.0, 0.0) > filter.weight(0.5, 0.0));
+        assert!(filter.weight(0.5, 0.0) > filter.weight(1.0, 0.0));
+        assert_eq!(filter.weight(1.5, 0.0), 0.0);
+        assert_eq!(filter.weight(0.3, 0.2), filter.weight(-0.2, 0.3));
+    }
+
+    #[test]
+    fn mitchell_filter_has_negative_lobes() {
+        let filter = ReconstructionFilter::MitchellNetravali {
+            b: 1.0 / 3.0,
+            c: 1.0 / 3.0,
+        };
+        assert_eq!(filter.radius(), 2.0);
+        assert!((mitchell(0.0, 1.0 / 3.0, 1.0 / 3.0) - 8.0 / 9.0).abs() < EPSILON);
+        assert!(mitchell(1.0, 1.0 / 3.0, 1.0 / 3.0) > 0.0);
+        assert!(filter.weight(1.5, 0.0) < 0.0);
+        assert_eq!(filter.weight(2.0, 0.0), 0.0);
+        //Weights of a unit spaced row of samples sum up to 1.
+        let sum: f32 = (-2..=2).map(|i| mitchell(i as f32 + 0.3, 0.4, 0.3)).sum();
+        assert!((sum - 1.0).abs() < 0.0001);
+    }
+}
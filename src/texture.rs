@@ -0,0 +1,243 @@
+use crate::math::*;
+use crate::uv::UvPattern;
+use std::path::Path;
+
+//How a texture is read between texel centers.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Filter {
+    //Color of the closest texel, blocky when magnified.
+    Nearest,
+    //Weighted average of the 4 closest texels.
+    Bilinear,
+}
+
+//What happens to coordinates outside of [0, 1].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Addressing {
+    //Texture repeats, like tiles.
+    Wrap,
+    //Texels at the border are stretched out.
+    Clamp,
+}
+
+impl Addressing {
+    fn texel(self, index: i64, size: u32) -> u32 {
+        match self {
+            Addressing::Wrap => index.rem_euclid(size as i64) as u32,
+            Addressing::Clamp => index.clamp(0, size as i64 - 1) as u32,
+        }
+    }
+}
+
+//Picture sampled through UV coordinates. u = 0 is the left edge of the image,
+//v = 0 the bottom one, so textures aren't upside down on the shapes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageTexture {
+    width: u32,
+    height: u32,
+    //Row by row, starting from the top of the image.
+    texels: Vec<Color>,
+    filter: Filter,
+    addressing: Addressing,
+}
+
+impl ImageTexture {
+    //Texture with nearest filtering and wrapping. Panics if the number of
+    //texels doesn't match the size, or the size is 0.
+    pub fn new(width: u32, height: u32, texels: Vec<Color>) -> ImageTexture {
+        assert!(width > 0 && height > 0);
+        assert_eq!(texels.len(), (width * height) as usize);
+        ImageTexture {
+            width,
+            height,
+            texels,
+            filter: Filter::Nearest,
+            addressing: Addressing::Wrap,
+        }
+    }
+
+    //Any format `image` can read, PNG and JPEG included.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> image::error::ImageResult<ImageTexture> {
+        Ok(ImageTexture::from_image(&image::open(path)?))
+    }
+
+    //8-bit images are sRGB encoded, texels are decoded to linear colors.
+    pub fn from_image(image: &image::DynamicImage) -> ImageTexture {
+        let rgb = image.to_rgb8();
+        let texels = rgb
+            .pixels()
+            .map(|pixel| Color::from_srgb8(pixel[0], pixel[1], pixel[2]))
+            .collect();
+        ImageTexture::new(rgb.width(), rgb.height(), texels)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn filter(&self) -> Filter {
+        self.filter
+    }
+
+    pub fn set_filter(&mut self, filter: Filter) {
+        self.filter = filter;
+    }
+
+    pub fn addressing(&self) -> Addressing {
+        self.addressing
+    }
+
+    pub fn set_addressing(&mut self, addressing: Addressing) {
+        self.addressing = addressing;
+    }
+
+    //Texel at integer coordinates, out of range ones are resolved by the addressing mode.
+    pub fn texel(&self, x: i64, y: i64) -> Color {
+        let x = self.addressing.texel(x, self.width);
+        let y = self.addressing.texel(y, self.height);
+        self.texels[(y * self.width + x) as usize]
+    }
+}
+
+impl UvPattern for ImageTexture {
+    fn uv_pattern_at(&self, u: f32, v: f32) -> Color {
+        //Position in texels, texel n covers [n, n + 1).
+        let x = u * self.width as f32;
+        let y = (1.0 - v) * self.height as f32;
+        match self.filter {
+            Filter::Nearest => self.texel(x.floor() as i64, y.floor() as i64),
+            Filter::Bilinear => {
+                //Shifted so that texel centers are at whole numbers.
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (x0, y0) = (x.floor(), y.floor());
+                let (fx, fy) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+                let top = self.texel(x0, y0) * (1.0 - fx) + self.texel(x0 + 1, y0) * fx;
+                let bottom = self.texel(x0, y0 + 1) * (1.0 - fx) + self.texel(x0 + 1, y0 + 1) * fx;
+                top * (1.0 - fy) + bottom * fy
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::material::Material;
+    use crate::objects::PlaneBuilder;
+    use crate::uv::{TextureMapPattern, UvMapping};
+    use std::sync::Arc;
+
+    fn gray(value: f32) -> Color {
+        Color::new(value, value, value)
+    }
+
+    //2x2 texture: black and white on the top row, red and green on the bottom one.
+    fn small_texture() -> ImageTexture {
+        ImageTexture::new(
+            2,
+            2,
+            vec![gray(0.0), gray(1.0), Color::red(), Color::green()],
+        )
+    }
+
+    #[test]
+    fn nearest_sampling_picks_texel() {
+        let texture = small_texture();
+        assert_eq!(texture.uv_pattern_at(0.25, 0.75), gray(0.0));
+        assert_eq!(texture.uv_pattern_at(0.75, 0.75), gray(1.0));
+        assert_eq!(texture.uv_pattern_at(0.25, 0.25), Color::red());
+        assert_eq!(texture.uv_pattern_at(0.6, 0.1), Color::green());
+    }
+
+    #[test]
+    fn nearest_sampling_at_texture_edge() {
+        let mut texture = ImageTexture::new(2, 1, vec![gray(0.0), gray(1.0)]);
+        assert_eq!(texture.uv_pattern_at(0.0, 0.5), gray(0.0));
+        assert_eq!(texture.uv_pattern_at(0.2, 0.5), gray(0.0));
+        assert_eq!(texture.uv_pattern_at(0.5, 0.5), gray(1.0));
+        texture.set_addressing(Addressing::Clamp);
+        assert_eq!(texture.uv_pattern_at(0.0, 0.5), gray(0.0));
+        assert_eq!(texture.uv_pattern_at(0.49, 0.5), gray(0.0));
+        assert_eq!(texture.uv_pattern_at(1.0, 0.5), gray(1.0));
+    }
+
+    #[test]
+    fn bilinear_sampling_blends_texels() {
+        let mut texture = ImageTexture::new(2, 1, vec![gray(0.0), gray(1.0)]);
+        texture.set_filter(Filter::Bilinear);
+        texture.set_addressing(Addressing::Clamp);
+        //Texel centers are at u = 0.25 and u = 0.75.
+        matrix_eq!(texture.uv_pattern_at(0.25, 0.5).as_array(), [0.0; 3]);
+        matrix_eq!(texture.uv_pattern_at(0.5, 0.5).as_array(), [0.5; 3]);
+        matrix_eq!(texture.uv_pattern_at(0.625, 0.5).as_array(), [0.75; 3]);
+        matrix_eq!(texture.uv_pattern_at(1.0, 0.5).as_array(), [1.0; 3]);
+    }
+
+    #[test]
+    fn bilinear_sampling_wraps_around_edges() {
+        let mut texture = ImageTexture::new(2, 1, vec![gray(0.0), gray(1.0)]);
+        texture.set_filter(Filter::Bilinear);
+        //Halfway between the last texel and the first one repeated after it.
+        matrix_eq!(texture.uv_pattern_at(1.0, 0.5).as_array(), [0.5; 3]);
+        matrix_eq!(texture.uv_pattern_at(0.0, 0.5).as_array(), [0.5; 3]);
+    }
+
+    #[test]
+    fn addressing_modes() {
+        let mut texture = small_texture();
+        assert_eq!(texture.uv_pattern_at(1.25, 0.75), gray(0.0));
+        assert_eq!(texture.uv_pattern_at(-0.25, -0.25), gray(1.0));
+        texture.set_addressing(Addressing::Clamp);
+        assert_eq!(texture.uv_pattern_at(1.25, 0.75), gray(1.0));
+        assert_eq!(texture.uv_pattern_at(-0.25, -0.25), Color::red());
+    }
+
+    #[test]
+    fn loading_texture_from_file() {
+        //Process id keeps parallel runs from different checkouts apart.
+        let path = std::env::temp_dir().join(format!(
+            "raytrace_rs_texture_test_{}.png",
+            std::process::id()
+        ));
+        let mut image = image::RgbImage::new(3, 1);
+        image.put_pixel(0, 0, image::Rgb([255, 0, 0]));
+        image.put_pixel(1, 0, image::Rgb([0, 0, 255]));
+        image.put_pixel(2, 0, image::Rgb([188, 188, 188]));
+        image.save(&path).unwrap();
+
+        let texture = ImageTexture::from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(texture.width(), 3);
+        assert_eq!(texture.height(), 1);
+        assert_eq!(texture.texel(0, 0), Color::red());
+        assert_eq!(texture.texel(1, 0), Color::blue());
+        //Stored as sRGB, middle gray in linear light.
+        matrix_eq!(texture.texel(2, 0).as_array(), [0.5; 3], 0.003);
+    }
+
+    #[test]
+    fn missing_texture_file_is_error() {
+        assert!(ImageTexture::from_file("no/such/texture.png").is_err());
+    }
+
+    #[test]
+    fn texture_as_material_color() {
+        let texture = Arc::new(small_texture());
+        let pattern = TextureMapPattern::new(texture, UvMapping::Planar);
+        let material = Material::default_with_pattern(Arc::new(pattern));
+        let plane = PlaneBuilder::new().create().unwrap();
+        assert_eq!(
+            material.color_at(&plane, &point!(0.25, 0.0, 0.25)),
+            Color::red()
+        );
+        assert_eq!(
+            material.color_at(&plane, &point!(0.75, 0.0, 0.75)),
+            gray(1.0)
+        );
+    }
+}
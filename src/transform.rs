@@ -0,0 +1,131 @@
+use crate::math::*;
+use std::error::Error;
+use std::fmt;
+use std::ops::Mul;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TransformError {
+    //Matrix has no inverse, so rays can't be moved into the object space.
+    Singular(Mat4),
+}
+
+impl fmt::Display for TransformError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransformError::Singular(matrix) => {
+                write!(f, "transformation matrix is not invertible: {}", matrix)
+            }
+        }
+    }
+}
+
+impl Error for TransformError {}
+
+//Transformation matrix together with its inverse and inverse-transpose.
+//They are needed for every ray and normal, so they are computed once, when the
+//transformation is created, and invalid (singular) matrices never get that far.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Transform {
+    matrix: Mat4,
+    inverse: Mat4,
+    inverse_transpose: Mat4,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::identity()
+    }
+}
+
+impl Transform {
+    pub fn new(matrix: Mat4) -> Result<Transform, TransformError> {
+        let inverse = matrix
+            .try_inverse()
+            .filter(|inverse| inverse.iter().all(|x| x.is_finite()))
+            .ok_or(TransformError::Singular(matrix))?;
+        Ok(Transform {
+            matrix,
+            inverse,
+            inverse_transpose: inverse.transpose(),
+        })
+    }
+
+    pub fn identity() -> Transform {
+        Transform {
+            matrix: Mat4::identity(),
+            inverse: Mat4::identity(),
+            inverse_transpose: Mat4::identity(),
+        }
+    }
+
+    pub fn matrix(&self) -> &Mat4 {
+        &self.matrix
+    }
+
+    pub fn inverse(&self) -> &Mat4 {
+        &self.inverse
+    }
+
+    pub fn inverse_transpose(&self) -> &Mat4 {
+        &self.inverse_transpose
+    }
+}
+
+//Composition, `a * b` applies `b` first. The inverse of the product is the product
+//of inverses, so nothing has to be inverted again.
+impl Mul for Transform {
+    type Output = Transform;
+
+    fn mul(self, rhs: Transform) -> Transform {
+        let inverse = rhs.inverse * self.inverse;
+        Transform {
+            matrix: self.matrix * rhs.matrix,
+            inverse,
+            inverse_transpose: inverse.transpose(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    #[test]
+    fn transform_stores_inverse() {
+        let matrix = translation!(1.0, 2.0, 3.0) * scaling!(2.0, 4.0, 8.0);
+        let transform = Transform::new(matrix).unwrap();
+        assert_eq!(transform.matrix(), &matrix);
+        matrix_eq!(transform.inverse() * matrix, Mat4::identity());
+        matrix_eq!(
+            *transform.inverse_transpose(),
+            transform.inverse().transpose()
+        );
+    }
+
+    #[test]
+    fn singular_matrix_is_rejected() {
+        let matrix = scaling!(1.0, 0.0, 1.0);
+        assert_eq!(
+            Transform::new(matrix),
+            Err(TransformError::Singular(matrix))
+        );
+    }
+
+    #[test]
+    fn composing_transforms() {
+        let a = translation!(0.0, 1.0, 0.0);
+        let b = rotation!(0.0, FRAC_PI_2, 0.0) * scaling!(1.0, 2.0, 3.0);
+        let composed = Transform::new(a).unwrap() * Transform::new(b).unwrap();
+        let expected = Transform::new(a * b).unwrap();
+        matrix_eq!(composed.matrix(), expected.matrix());
+        matrix_eq!(composed.inverse(), expected.inverse());
+        matrix_eq!(composed.inverse_transpose(), expected.inverse_transpose());
+    }
+
+    #[test]
+    fn identity_transform() {
+        let transform = Transform::default();
+        assert_eq!(transform, Transform::new(Mat4::identity()).unwrap());
+    }
+}
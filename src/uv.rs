@@ -0,0 +1,530 @@
+use crate::math::*;
+use crate::objects::Shape;
+use crate::pattern::Pattern;
+use crate::transform::Transform;
+use std::f32::consts::PI;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+//Flat, 2D pattern addressed with texture coordinates, both in [0, 1].
+//Put on a shape with `TextureMapPattern` or `CubeMapPattern`.
+pub trait UvPattern: Debug + Send + Sync {
+    fn uv_pattern_at(&self, u: f32, v: f32) -> Color;
+}
+
+//Wraps the surface of a unit sphere. u goes around the y axis, v from the south
+//pole (0) to the north pole (1).
+pub fn spherical_map(point: &Point4) -> (f32, f32) {
+    let theta = point.x.atan2(point.z);
+    let radius = point.coords.xyz().magnitude();
+    let phi = (point.y / radius).acos();
+    let raw_u = theta / (2.0 * PI);
+    (1.0 - (raw_u + 0.5), 1.0 - phi / PI)
+}
+
+//Tiles the xz plane, repeating every unit.
+pub fn planar_map(point: &Point4) -> (f32, f32) {
+    (point.x.rem_euclid(1.0), point.z.rem_euclid(1.0))
+}
+
+//u goes around the y axis like `spherical_map`, v repeats every unit of height.
+pub fn cylindrical_map(point: &Point4) -> (f32, f32) {
+    let theta = point.x.atan2(point.z);
+    let raw_u = theta / (2.0 * PI);
+    (1.0 - (raw_u + 0.5), point.y.rem_euclid(1.0))
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum UvMapping {
+    Spherical,
+    Planar,
+    Cylindrical,
+}
+
+impl UvMapping {
+    pub fn map(&self, point: &Point4) -> (f32, f32) {
+        match self {
+            UvMapping::Spherical => spherical_map(point),
+            UvMapping::Planar => planar_map(point),
+            UvMapping::Cylindrical => cylindrical_map(point),
+        }
+    }
+}
+
+//Side of the unit cube, as seen from outside of it, with the cube's front facing +z.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CubeFace {
+    Left,
+    Front,
+    Right,
+    Back,
+    Up,
+    Down,
+}
+
+impl CubeFace {
+    //Face the point lies on, picked by its largest coordinate.
+    pub fn from_point(point: &Point4) -> CubeFace {
+        let coord = point.x.abs().max(point.y.abs()).max(point.z.abs());
+        if coord == point.x {
+            CubeFace::Right
+        } else if coord == -point.x {
+            CubeFace::Left
+        } else if coord == point.y {
+            CubeFace::Up
+        } else if coord == -point.y {
+            CubeFace::Down
+        } else if coord == point.z {
+            CubeFace::Front
+        } else {
+            CubeFace::Back
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+//Face of the unit cube the point is on, and (u, v) within that face. Every face
+//is unfolded so that v points up (or towards the back, for the top and bottom).
+pub fn cube_map(point: &Point4) -> (CubeFace, f32, f32) {
+    let face = CubeFace::from_point(point);
+    let wrap = |value: f32| value.rem_euclid(2.0) / 2.0;
+    let (u, v) = match face {
+        CubeFace::Front => (wrap(point.x + 1.0), wrap(point.y + 1.0)),
+        CubeFace::Back => (wrap(1.0 - point.x), wrap(point.y + 1.0)),
+        CubeFace::Left => (wrap(point.z + 1.0), wrap(point.y + 1.0)),
+        CubeFace::Right => (wrap(1.0 - point.z), wrap(point.y + 1.0)),
+        CubeFace::Up => (wrap(point.x + 1.0), wrap(1.0 - point.z)),
+        CubeFace::Down => (wrap(point.x + 1.0), wrap(point.z + 1.0)),
+    };
+    (face, u, v)
+}
+
+//UV pattern put on a shape with one of the mappings.
+#[derive(Debug, Clone)]
+pub struct TextureMapPattern {
+    uv_pattern: Arc<dyn UvPattern>,
+    mapping: UvMapping,
+    transformation: Transform,
+}
+
+impl TextureMapPattern {
+    pub fn new(uv_pattern: Arc<dyn UvPattern>, mapping: UvMapping) -> TextureMapPattern {
+        TextureMapPattern {
+            uv_pattern,
+            mapping,
+            transformation: Transform::identity(),
+        }
+    }
+}
+
+impl Pattern for TextureMapPattern {
+    fn transformation(&self) -> &Transform {
+        &self.transformation
+    }
+
+    fn set_transformation(&mut self, transformation: Transform) {
+        self.transformation = transformation;
+    }
+
+    fn local_pattern_at(&self, point: &Point4) -> Color {
+        let (u, v) = self.mapping.map(point);
+        self.uv_pattern.uv_pattern_at(u, v)
+    }
+}
+
+//UV pattern put on a shape with the texture coordinates the shape carries itself,
+//like triangles of an OBJ mesh with `vt` records. Shapes without them fall back
+//to `UvMapping::Planar`, which is also what the pattern transformation affects.
+#[derive(Debug, Clone)]
+pub struct SurfaceUvPattern {
+    uv_pattern: Arc<dyn UvPattern>,
+    transformation: Transform,
+}
+
+impl SurfaceUvPattern {
+    pub fn new(uv_pattern: Arc<dyn UvPattern>) -> SurfaceUvPattern {
+        SurfaceUvPattern {
+            uv_pattern,
+            transformation: Transform::identity(),
+        }
+    }
+}
+
+impl Pattern for SurfaceUvPattern {
+    fn transformation(&self) -> &Transform {
+        &self.transformation
+    }
+
+    fn set_transformation(&mut self, transformation: Transform) {
+        self.transformation = transformation;
+    }
+
+    fn local_pattern_at(&self, point: &Point4) -> Color {
+        let (u, v) = planar_map(point);
+        self.uv_pattern.uv_pattern_at(u, v)
+    }
+
+    fn pattern_at(&self, object: &dyn Shape, world_point: &Point4) -> Color {
+        let object_point = object.world_transformation().inverse() * world_point;
+        match object.texture_coords_at(&object_point) {
+            Some((u, v)) => self.uv_pattern.uv_pattern_at(u, v),
+            None => self.local_pattern_at(&(self.transformation.inverse() * object_point)),
+        }
+    }
+}
+
+//Separate UV pattern for every side of a cube.
+#[derive(Debug, Clone)]
+pub struct CubeMapPattern {
+    //In `CubeFace` order: left, front, right, back, up, down.
+    faces: [Arc<dyn UvPattern>; 6],
+    transformation: Transform,
+}
+
+impl CubeMapPattern {
+    pub fn new(
+        left: Arc<dyn UvPattern>,
+        front: Arc<dyn UvPattern>,
+        right: Arc<dyn UvPattern>,
+        back: Arc<dyn UvPattern>,
+        up: Arc<dyn UvPattern>,
+        down: Arc<dyn UvPattern>,
+    ) -> CubeMapPattern {
+        CubeMapPattern {
+            faces: [left, front, right, back, up, down],
+            transformation: Transform::identity(),
+        }
+    }
+
+    pub fn face(&self, face: CubeFace) -> &Arc<dyn UvPattern> {
+        &self.faces[face.index()]
+    }
+}
+
+impl Pattern for CubeMapPattern {
+    fn transformation(&self) -> &Transform {
+        &self.transformation
+    }
+
+    fn set_transformation(&mut self, transformation: Transform) {
+        self.transformation = transformation;
+    }
+
+    fn local_pattern_at(&self, point: &Point4) -> Color {
+        let (face, u, v) = cube_map(point);
+        self.face(face).uv_pattern_at(u, v)
+    }
+}
+
+//Checkerboard with `width` squares along u and `height` along v.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct UvCheckers {
+    width: f32,
+    height: f32,
+    a: Color,
+    b: Color,
+}
+
+impl UvCheckers {
+    pub fn new(width: f32, height: f32, a: Color, b: Color) -> UvCheckers {
+        UvCheckers {
+            width,
+            height,
+            a,
+            b,
+        }
+    }
+}
+
+impl UvPattern for UvCheckers {
+    fn uv_pattern_at(&self, u: f32, v: f32) -> Color {
+        let sum = (u * self.width).floor() + (v * self.height).floor();
+        if sum.rem_euclid(2.0) == 0.0 {
+            self.a
+        } else {
+            self.b
+        }
+    }
+}
+
+//Main color with a differently colored square in every corner. Shows how a
+//texture is oriented on a surface, handy when debugging mappings.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AlignCheck {
+    main: Color,
+    upper_left: Color,
+    upper_right: Color,
+    bottom_left: Color,
+    bottom_right: Color,
+}
+
+impl AlignCheck {
+    pub fn new(
+        main: Color,
+        upper_left: Color,
+        upper_right: Color,
+        bottom_left: Color,
+        bottom_right: Color,
+    ) -> AlignCheck {
+        AlignCheck {
+            main,
+            upper_left,
+            upper_right,
+            bottom_left,
+            bottom_right,
+        }
+    }
+}
+
+impl UvPattern for AlignCheck {
+    fn uv_pattern_at(&self, u: f32, v: f32) -> Color {
+        if v > 0.8 {
+            if u < 0.2 {
+                return self.upper_left;
+            }
+            if u > 0.8 {
+                return self.upper_right;
+            }
+        } else if v < 0.2 {
+            if u < 0.2 {
+                return self.bottom_left;
+            }
+            if u > 0.8 {
+                return self.bottom_right;
+            }
+        }
+        self.main
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::f32::consts::FRAC_1_SQRT_2;
+
+    fn white() -> Color {
+        Color::new(1.0, 1.0, 1.0)
+    }
+
+    fn black() -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    fn uv_eq(actual: (f32, f32), expected: (f32, f32)) {
+        matrix_eq!([actual.0, actual.1], [expected.0, expected.1], 0.0001);
+    }
+
+    fn align_check() -> AlignCheck {
+        AlignCheck::new(
+            white(),
+            Color::red(),
+            Color::new(1.0, 1.0, 0.0),
+            Color::green(),
+            Color::new(0.0, 1.0, 1.0),
+        )
+    }
+
+    #[test]
+    fn checkers_in_uv() {
+        let checkers = UvCheckers::new(2.0, 2.0, black(), white());
+        let cases = [
+            (0.0, 0.0, black()),
+            (0.5, 0.0, white()),
+            (0.0, 0.5, white()),
+            (0.5, 0.5, black()),
+            (1.0, 1.0, black()),
+        ];
+        for &(u, v, expected) in cases.iter() {
+            assert_eq!(checkers.uv_pattern_at(u, v), expected);
+        }
+    }
+
+    #[test]
+    fn spherical_mapping_on_sphere() {
+        let cases = [
+            (point!(0.0, 0.0, -1.0), (0.0, 0.5)),
+            (point!(1.0, 0.0, 0.0), (0.25, 0.5)),
+            (point!(0.0, 0.0, 1.0), (0.5, 0.5)),
+            (point!(-1.0, 0.0, 0.0), (0.75, 0.5)),
+            (point!(0.0, 1.0, 0.0), (0.5, 1.0)),
+            (point!(0.0, -1.0, 0.0), (0.5, 0.0)),
+            (point!(FRAC_1_SQRT_2, FRAC_1_SQRT_2, 0.0), (0.25, 0.75)),
+        ];
+        for (point, expected) in cases.iter() {
+            uv_eq(spherical_map(point), *expected);
+        }
+    }
+
+    #[test]
+    fn planar_mapping_on_plane() {
+        let cases = [
+            (point!(0.25, 0.0, 0.5), (0.25, 0.5)),
+            (point!(0.25, 0.0, -0.25), (0.25, 0.75)),
+            (point!(0.25, 0.5, -0.25), (0.25, 0.75)),
+            (point!(1.25, 0.0, 0.5), (0.25, 0.5)),
+            (point!(0.25, 0.0, -1.75), (0.25, 0.25)),
+            (point!(1.0, 0.0, -1.0), (0.0, 0.0)),
+            (point!(0.0, 0.0, 0.0), (0.0, 0.0)),
+        ];
+        for (point, expected) in cases.iter() {
+            uv_eq(planar_map(point), *expected);
+        }
+    }
+
+    #[test]
+    fn cylindrical_mapping_on_cylinder() {
+        let cases = [
+            (point!(0.0, 0.0, -1.0), (0.0, 0.0)),
+            (point!(0.0, 0.5, -1.0), (0.0, 0.5)),
+            (point!(0.0, 1.0, -1.0), (0.0, 0.0)),
+            (point!(FRAC_1_SQRT_2, 0.5, -FRAC_1_SQRT_2), (0.125, 0.5)),
+            (point!(1.0, 0.5, 0.0), (0.25, 0.5)),
+            (point!(FRAC_1_SQRT_2, 0.5, FRAC_1_SQRT_2), (0.375, 0.5)),
+            (point!(0.0, -0.25, 1.0), (0.5, 0.75)),
+            (point!(-FRAC_1_SQRT_2, 0.5, FRAC_1_SQRT_2), (0.625, 0.5)),
+            (point!(-1.0, 1.25, 0.0), (0.75, 0.25)),
+            (point!(-FRAC_1_SQRT_2, 0.5, -FRAC_1_SQRT_2), (0.875, 0.5)),
+        ];
+        for (point, expected) in cases.iter() {
+            uv_eq(cylindrical_map(point), *expected);
+        }
+    }
+
+    #[test]
+    fn align_check_pattern() {
+        let pattern = align_check();
+        let cases = [
+            (0.5, 0.5, white()),
+            (0.1, 0.9, Color::red()),
+            (0.9, 0.9, Color::new(1.0, 1.0, 0.0)),
+            (0.1, 0.1, Color::green()),
+            (0.9, 0.1, Color::new(0.0, 1.0, 1.0)),
+        ];
+        for &(u, v, expected) in cases.iter() {
+            assert_eq!(pattern.uv_pattern_at(u, v), expected);
+        }
+    }
+
+    #[test]
+    fn identifying_face_of_cube() {
+        let cases = [
+            (point!(-1.0, 0.5, -0.25), CubeFace::Left),
+            (point!(1.1, -0.75, 0.8), CubeFace::Right),
+            (point!(0.1, 0.6, 0.9), CubeFace::Front),
+            (point!(-0.7, 0.0, -2.0), CubeFace::Back),
+            (point!(0.5, 1.0, 0.9), CubeFace::Up),
+            (point!(-0.2, -1.3, 1.1), CubeFace::Down),
+        ];
+        for (point, expected) in cases.iter() {
+            assert_eq!(CubeFace::from_point(point), *expected);
+        }
+    }
+
+    #[test]
+    fn uv_mapping_of_cube_faces() {
+        let cases = [
+            (point!(-0.5, 0.5, 1.0), CubeFace::Front, (0.25, 0.75)),
+            (point!(0.5, -0.5, 1.0), CubeFace::Front, (0.75, 0.25)),
+            (point!(0.5, 0.5, -1.0), CubeFace::Back, (0.25, 0.75)),
+            (point!(-0.5, -0.5, -1.0), CubeFace::Back, (0.75, 0.25)),
+            (point!(-1.0, 0.5, -0.5), CubeFace::Left, (0.25, 0.75)),
+            (point!(-1.0, -0.5, 0.5), CubeFace::Left, (0.75, 0.25)),
+            (point!(1.0, 0.5, 0.5), CubeFace::Right, (0.25, 0.75)),
+            (point!(1.0, -0.5, -0.5), CubeFace::Right, (0.75, 0.25)),
+            (point!(-0.5, 1.0, -0.5), CubeFace::Up, (0.25, 0.75)),
+            (point!(0.5, 1.0, 0.5), CubeFace::Up, (0.75, 0.25)),
+            (point!(-0.5, -1.0, 0.5), CubeFace::Down, (0.25, 0.75)),
+            (point!(0.5, -1.0, -0.5), CubeFace::Down, (0.75, 0.25)),
+        ];
+        for (point, face, expected) in cases.iter() {
+            let (actual_face, u, v) = cube_map(point);
+            assert_eq!(actual_face, *face);
+            uv_eq((u, v), *expected);
+        }
+    }
+
+    #[test]
+    fn texture_map_with_spherical_mapping() {
+        let checkers = Arc::new(UvCheckers::new(16.0, 8.0, black(), white()));
+        let pattern = TextureMapPattern::new(checkers, UvMapping::Spherical);
+        let cases = [
+            (point!(0.4315, 0.4670, 0.7719), white()),
+            (point!(-0.9654, 0.2552, -0.0534), black()),
+            (point!(0.1039, 0.7090, 0.6975), white()),
+            (point!(-0.4986, -0.7856, -0.3663), black()),
+            (point!(-0.0317, -0.9395, 0.3411), black()),
+            (point!(0.4809, -0.7721, 0.4154), black()),
+            (point!(0.0285, -0.9612, -0.2745), black()),
+            (point!(-0.5734, -0.2162, -0.7903), white()),
+            (point!(0.7688, -0.1470, 0.6223), black()),
+            (point!(-0.7652, 0.2175, 0.6060), black()),
+        ];
+        for (point, expected) in cases.iter() {
+            assert_eq!(pattern.local_pattern_at(point), *expected);
+        }
+    }
+
+    #[test]
+    fn cube_map_picks_pattern_per_face() {
+        let face = |main: Color| -> Arc<dyn UvPattern> {
+            Arc::new(AlignCheck::new(main, white(), white(), white(), white()))
+        };
+        let pattern = CubeMapPattern::new(
+            face(Color::new(1.0, 1.0, 0.0)),
+            face(Color::new(0.0, 1.0, 1.0)),
+            face(Color::red()),
+            face(Color::green()),
+            face(Color::new(1.0, 0.5, 0.0)),
+            face(Color::new(1.0, 0.0, 1.0)),
+        );
+        let cases = [
+            (point!(-1.0, 0.0, 0.0), Color::new(1.0, 1.0, 0.0)),
+            (point!(0.0, 0.0, 1.0), Color::new(0.0, 1.0, 1.0)),
+            (point!(1.0, 0.0, 0.0), Color::red()),
+            (point!(0.0, 0.0, -1.0), Color::green()),
+            (point!(0.0, 1.0, 0.0), Color::new(1.0, 0.5, 0.0)),
+            (point!(0.0, -1.0, 0.0), Color::new(1.0, 0.0, 1.0)),
+            (point!(-1.0, 0.9, -0.9), white()),
+        ];
+        for (point, expected) in cases.iter() {
+            assert_eq!(pattern.local_pattern_at(point), *expected);
+        }
+    }
+
+    #[test]
+    fn surface_uv_pattern_uses_shape_coords() {
+        use crate::objects::{PlaneBuilder, TriangleBuilder};
+        let checkers = Arc::new(UvCheckers::new(2.0, 2.0, black(), white()));
+        let pattern = SurfaceUvPattern::new(checkers);
+        let triangle = TriangleBuilder::new(
+            point!(0.0, 1.0, 0.0),
+            point!(-1.0, 0.0, 0.0),
+            point!(1.0, 0.0, 0.0),
+        )
+        .with_texture_coords((0.5, 1.0), (0.0, 0.0), (1.0, 0.0))
+        .with_transformation(translation!(0.0, 0.0, 5.0))
+        .create()
+        .unwrap();
+        assert_eq!(
+            pattern.pattern_at(&triangle, &point!(-0.9, 0.05, 5.0)),
+            black()
+        );
+        assert_eq!(
+            pattern.pattern_at(&triangle, &point!(0.9, 0.05, 5.0)),
+            white()
+        );
+        //No coordinates on a plane, so it is mapped like `UvMapping::Planar`.
+        let plane = PlaneBuilder::new().create().unwrap();
+        assert_eq!(
+            pattern.pattern_at(&plane, &point!(0.25, 0.0, 0.25)),
+            black()
+        );
+        assert_eq!(
+            pattern.pattern_at(&plane, &point!(0.75, 0.0, 0.25)),
+            white()
+        );
+    }
+}
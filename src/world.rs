@@ -1,13 +1,24 @@
+use crate::background::Background;
+use crate::bvh::Bvh;
 use crate::intersection::*;
-use crate::light::{LightSource, PointLight};
+use crate::light::{shade_hit, LightSource, PointLight};
 use crate::material::Material;
 use crate::math::*;
-use crate::objects::{Ray, Sphere};
-use crate::SphereBuilder;
+use crate::objects::{Ray, Shape, SphereBuilder};
+use std::cell::OnceCell;
+
+//How many times rays can bounce off reflective surfaces, unless set otherwise.
+pub const DEFAULT_MAX_DEPTH: u32 = 5;
 
 pub struct World {
-    objects: Vec<Sphere>, // TODO: Maybe there should be generic Object.
-    light_source: Box<dyn LightSource>,
+    objects: Vec<Box<dyn Shape>>,
+    //Contributions of all the lights are added together.
+    lights: Vec<Box<dyn LightSource>>,
+    //Built lazily on the first ray, dropped whenever objects change.
+    bvh: OnceCell<Bvh>,
+    max_depth: u32,
+    //Seen by rays that hit nothing, including reflected and refracted ones.
+    background: Background,
 }
 
 impl Default for World {
@@ -21,45 +32,137 @@ impl Default for World {
                 0.2,
                 200.0,
             ))
-            .create();
-        let s2 = sb.with_transformation(scaling!(0.5, 0.5, 0.5)).create();
+            .create()
+            .expect("Identity transformation is always invertible");
+        let s2 = sb
+            .with_transformation(scaling!(0.5, 0.5, 0.5))
+            .create()
+            .expect("Uniform scaling is always invertible");
 
-        let objects = vec![s1, s2];
+        let objects: Vec<Box<dyn Shape>> = vec![Box::new(s1), Box::new(s2)];
         let light = PointLight::new(point!(-10.0, 10.0, -10.0), Color::new(1.0, 1.0, 1.0));
         World {
             objects,
-            light_source: Box::new(light),
+            lights: vec![Box::new(light)],
+            bvh: OnceCell::new(),
+            max_depth: DEFAULT_MAX_DEPTH,
+            background: Background::default(),
         }
     }
 }
 
 impl World {
+    //Empty world lit by the given light source.
+    pub fn new(light_source: Box<dyn LightSource>) -> World {
+        World {
+            objects: Vec::new(),
+            lights: vec![light_source],
+            bvh: OnceCell::new(),
+            max_depth: DEFAULT_MAX_DEPTH,
+            background: Background::default(),
+        }
+    }
+
+    fn bvh(&self) -> &Bvh {
+        self.bvh.get_or_init(|| Bvh::build(&self.objects))
+    }
+
     //Find all intersections with all objects in the world
-    pub fn ray_intersect(&self, ray: &Ray) -> Intersections {
-        let mut result = Vec::new();
-        for object in &self.objects {
-            if let Some(mut intersections) = intersect(ray, object) {
-                result.append(&mut intersections);
-            }
+    pub fn ray_intersect(&self, ray: &Ray) -> Intersections<'_> {
+        self.bvh().intersect(&self.objects, ray)
+    }
+
+    //Closest visible intersection, same as `hit(&world.ray_intersect(ray))`
+    //but skips everything behind it.
+    pub fn ray_hit(&self, ray: &Ray) -> Option<Intersection<'_>> {
+        self.bvh().hit(&self.objects, ray)
+    }
+
+    pub fn max_depth(&self) -> u32 {
+        self.max_depth
+    }
+
+    //Limit of recursion for secondary rays, 0 disables reflections altogether.
+    pub fn set_max_depth(&mut self, max_depth: u32) {
+        self.max_depth = max_depth;
+    }
+
+    pub fn background(&self) -> &Background {
+        &self.background
+    }
+
+    pub fn set_background(&mut self, background: Background) {
+        self.background = background;
+    }
+
+    //Color seen along the ray, the background if it hits nothing.
+    pub fn color_at(&self, ray: &Ray) -> Color {
+        self.color_at_depth(ray, self.max_depth)
+    }
+
+    //Same as `color_at`, but the ray may bounce only `remaining` more times.
+    pub fn color_at_depth(&self, ray: &Ray, remaining: u32) -> Color {
+        let hit = match self.ray_hit(ray) {
+            Some(hit) => hit,
+            None => return self.background.color(&ray.direction),
+        };
+        //Refractive indices around the hit matter only for transparent surfaces,
+        //only then all the intersections along the ray are collected.
+        let precomps = if hit.obj.material().transparency > 0.0 {
+            Precomputation::compute(&hit, ray, &self.ray_intersect(ray))
+        } else {
+            Precomputation::compute(&hit, ray, &[hit])
+        };
+        shade_hit(self, &precomps, remaining)
+    }
+
+    //Is anything between the point and the light.
+    pub fn is_shadowed(&self, light: &dyn LightSource, point: &Point4) -> bool {
+        let to_light = light.position() - point;
+        let distance = to_light.norm();
+        let ray = Ray::new(*point, to_light / distance);
+        match self.ray_hit(&ray) {
+            Some(hit) => hit.t < distance,
+            None => false,
         }
-        result.sort();
-        result
     }
 
-    pub fn shapes_iter<'a>(&'a self) -> impl Iterator<Item = &'a Sphere> {
-        self.objects.iter()
+    pub fn shapes_iter(&self) -> impl Iterator<Item = &dyn Shape> {
+        self.objects.iter().map(|object| object.as_ref())
+    }
+
+    pub fn add_object(&mut self, object: Box<dyn Shape>) {
+        self.objects.push(object);
+        self.bvh = OnceCell::new();
+    }
+
+    pub fn lights_iter(&self) -> impl Iterator<Item = &dyn LightSource> {
+        self.lights.iter().map(|light| light.as_ref())
     }
 
-    pub fn light_source(&self) -> &Box<dyn LightSource> {
-        &self.light_source
+    pub fn add_light(&mut self, light: Box<dyn LightSource>) {
+        self.lights.push(light);
+    }
+
+    //Removes the light at given position in `lights_iter` order, None if there is no such light.
+    pub fn remove_light(&mut self, index: usize) -> Option<Box<dyn LightSource>> {
+        if index < self.lights.len() {
+            Some(self.lights.remove(index))
+        } else {
+            None
+        }
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::intersection::{Intersection, Precomputation};
+    use crate::background::Background;
+    use crate::intersection::hit;
+    use crate::light::PointLight;
+    use crate::material::Material;
     use crate::math::*;
-    use crate::objects::Ray;
+    use crate::obj_file::ObjFile;
+    use crate::objects::{ConeBuilder, CylinderBuilder, PlaneBuilder, Ray, SphereBuilder};
     use crate::world::World;
 
     #[test]
@@ -67,11 +170,305 @@ mod test {
         let w = World::default();
         let ray = Ray::new(point!(0.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0));
         let result = w.ray_intersect(&ray);
-        let expected = vec![4.0, 4.5, 5.5, 6.0];
+        let expected = [4.0, 4.5, 5.5, 6.0];
         assert_eq!(result.len(), 4);
         assert_eq!(result[0].t, expected[0]);
         assert_eq!(result[1].t, expected[1]);
         assert_eq!(result[2].t, expected[2]);
         assert_eq!(result[3].t, expected[3]);
     }
+
+    #[test]
+    fn world_add_object() {
+        let mut w = World::default();
+        let sphere = SphereBuilder::new()
+            .with_transformation(translation!(0.0, 0.0, 10.0))
+            .create()
+            .unwrap();
+        w.add_object(Box::new(sphere));
+        assert_eq!(w.shapes_iter().count(), 3);
+        let ray = Ray::new(point!(0.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0));
+        let result = w.ray_intersect(&ray);
+        assert_eq!(result.len(), 6);
+        assert_eq!(result[5].t, 16.0);
+    }
+
+    #[test]
+    fn ray_world_intersection_cylinder_and_cone() {
+        let light = PointLight::new(point!(-10.0, 10.0, -10.0), Color::new(1.0, 1.0, 1.0));
+        let mut w = World::new(Box::new(light));
+        let cylinder = CylinderBuilder::new()
+            .with_minimum(-1.0)
+            .with_maximum(1.0)
+            .with_closed(true)
+            .create()
+            .unwrap();
+        let cone = ConeBuilder::new()
+            .with_transformation(translation!(0.0, 5.0, 0.0))
+            .with_minimum(-1.0)
+            .with_maximum(0.0)
+            .with_closed(true)
+            .create()
+            .unwrap();
+        w.add_object(Box::new(cylinder));
+        w.add_object(Box::new(cone));
+        //Straight down through the cone cap, cone tip and both cylinder caps.
+        let ray = Ray::new(point!(0.0, 10.0, 0.0), vector!(0.0, -1.0, 0.0));
+        let result = w.ray_intersect(&ray);
+        let expected = [5.0, 6.0, 9.0, 11.0];
+        assert_eq!(result.len(), 4);
+        for (intersection, t) in result.iter().zip(expected.iter()) {
+            assert!((intersection.t - t).abs() < EPSILON);
+        }
+    }
+
+    #[test]
+    fn ray_world_intersection_obj_mesh() {
+        let input = "v -1 -1 0\nv 1 -1 0\nv 1 1 0\nv -1 1 0\nf 1 2 3 4\n";
+        let obj = ObjFile::from_reader(input.as_bytes()).unwrap();
+        let light = PointLight::new(point!(-10.0, 10.0, -10.0), Color::new(1.0, 1.0, 1.0));
+        let mut w = World::new(Box::new(light));
+        for shape in obj.into_shapes() {
+            w.add_object(shape);
+        }
+        assert_eq!(w.shapes_iter().count(), 2);
+        let ray = Ray::new(point!(0.5, -0.5, -5.0), vector!(0.0, 0.0, 1.0));
+        let result = w.ray_intersect(&ray);
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].t, 5.0);
+    }
+
+    #[test]
+    fn world_ray_hit() {
+        let mut w = World::default();
+        let ray = Ray::new(point!(0.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0));
+        assert_eq!(w.ray_hit(&ray).map(|hit| hit.t), Some(4.0));
+        //From inside of both spheres the closest hit is the inner one.
+        let ray = Ray::new(point!(0.0, 0.0, 0.0), vector!(0.0, 0.0, 1.0));
+        assert_eq!(w.ray_hit(&ray).map(|hit| hit.t), Some(0.5));
+        let ray = Ray::new(point!(0.0, 5.0, -5.0), vector!(0.0, 0.0, 1.0));
+        assert!(w.ray_hit(&ray).is_none());
+        //Objects added later are hit as well.
+        w.add_object(Box::new(
+            PlaneBuilder::new()
+                .with_transformation(translation!(0.0, -1.0, 0.0))
+                .create()
+                .unwrap(),
+        ));
+        let ray = Ray::new(point!(0.0, 5.0, -5.0), vector!(0.0, -1.0, 0.0));
+        assert_eq!(w.ray_hit(&ray).map(|hit| hit.t), Some(6.0));
+    }
+
+    #[test]
+    fn ray_hit_matches_hit_of_all_intersections() {
+        let light = PointLight::new(point!(-10.0, 10.0, -10.0), Color::new(1.0, 1.0, 1.0));
+        let mut w = World::new(Box::new(light));
+        for x in -5..5 {
+            for y in -5..5 {
+                let sphere = SphereBuilder::new()
+                    .with_transformation(
+                        translation!(x as f32 * 2.5, y as f32 * 2.5, (x * y) as f32)
+                            * scaling!(0.8, 0.8, 0.8),
+                    )
+                    .create()
+                    .unwrap();
+                w.add_object(Box::new(sphere));
+            }
+        }
+        for i in 0..40 {
+            let target = point!((i % 9) as f32 - 4.0, (i % 7) as f32 - 3.0, 0.0);
+            let origin = point!(0.5, 0.5, -30.0);
+            let ray = Ray::new(origin, (target - origin).normalize());
+            let intersections = w.ray_intersect(&ray);
+            assert_eq!(w.ray_hit(&ray).as_ref(), hit(&intersections));
+        }
+    }
+
+    #[test]
+    fn color_when_ray_misses() {
+        let w = World::default();
+        let ray = Ray::new(point!(0.0, 0.0, -5.0), vector!(0.0, 1.0, 0.0));
+        assert_eq!(w.color_at(&ray), Color::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn missed_ray_sees_background() {
+        let mut w = World::default();
+        w.set_background(Background::Gradient {
+            bottom: Color::new(0.0, 0.0, 0.0),
+            top: Color::new(0.0, 0.0, 1.0),
+        });
+        let ray = Ray::new(point!(0.0, 0.0, -5.0), vector!(0.0, 1.0, 0.0));
+        assert_eq!(w.color_at(&ray), Color::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn escaping_reflected_ray_sees_background() {
+        let light = PointLight::new(point!(0.0, 10.0, 0.0), Color::new(1.0, 1.0, 1.0));
+        let mut w = World::new(Box::new(light));
+        w.set_background(Background::Solid(Color::red()));
+        let mirror = PlaneBuilder::new()
+            .with_material(Material {
+                color: Color::new(0.0, 0.0, 0.0),
+                ambient: 0.0,
+                diffuse: 0.0,
+                specular: 0.0,
+                reflective: 1.0,
+                ..Default::default()
+            })
+            .create()
+            .unwrap();
+        w.add_object(Box::new(mirror));
+        let sq = 2.0f32.sqrt() / 2.0;
+        let ray = Ray::new(point!(0.0, 1.0, -1.0), vector!(0.0, -sq, sq));
+        matrix_eq!(w.color_at(&ray).as_array(), [1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn color_when_ray_hits() {
+        let w = World::default();
+        let ray = Ray::new(point!(0.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0));
+        matrix_eq!(
+            w.color_at(&ray).as_array(),
+            [0.38066, 0.47583, 0.2855],
+            0.0001
+        );
+    }
+
+    #[test]
+    fn no_shadow_when_nothing_between_point_and_light() {
+        let w = World::default();
+        let light = w.lights_iter().next().unwrap();
+        assert!(!w.is_shadowed(light, &point!(0.0, 10.0, 0.0)));
+    }
+
+    #[test]
+    fn shadow_when_object_between_point_and_light() {
+        let w = World::default();
+        let light = w.lights_iter().next().unwrap();
+        assert!(w.is_shadowed(light, &point!(10.0, -10.0, 10.0)));
+    }
+
+    #[test]
+    fn no_shadow_when_object_behind_light() {
+        let w = World::default();
+        let light = w.lights_iter().next().unwrap();
+        assert!(!w.is_shadowed(light, &point!(-20.0, 20.0, -20.0)));
+    }
+
+    #[test]
+    fn no_shadow_when_object_behind_point() {
+        let w = World::default();
+        let light = w.lights_iter().next().unwrap();
+        assert!(!w.is_shadowed(light, &point!(-2.0, 2.0, -2.0)));
+    }
+
+    #[test]
+    fn adding_and_removing_lights() {
+        let mut w = World::default();
+        assert_eq!(w.lights_iter().count(), 1);
+        let fill = PointLight::new(point!(10.0, 10.0, -10.0), Color::new(0.5, 0.5, 0.5));
+        w.add_light(Box::new(fill));
+        assert_eq!(w.lights_iter().count(), 2);
+        assert_eq!(w.lights_iter().nth(1).unwrap().position(), fill.position);
+        let removed = w.remove_light(0).expect("Expected the default light!");
+        assert_eq!(removed.position(), point!(-10.0, 10.0, -10.0));
+        assert!(w.remove_light(1).is_none());
+        assert_eq!(w.lights_iter().count(), 1);
+    }
+
+    #[test]
+    fn light_contributions_are_summed() {
+        let ray = Ray::new(point!(0.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0));
+        let mut w = World::default();
+        let single = w.color_at(&ray);
+        w.add_light(Box::new(PointLight::new(
+            point!(-10.0, 10.0, -10.0),
+            Color::new(1.0, 1.0, 1.0),
+        )));
+        matrix_eq!(w.color_at(&ray).as_array(), (single * 2.0).as_array());
+        //Without any lights there is nothing to see.
+        w.remove_light(0);
+        w.remove_light(0);
+        assert_eq!(w.color_at(&ray), Color::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn every_light_has_its_own_shadow() {
+        let light = PointLight::new(point!(0.0, 0.0, -10.0), Color::new(1.0, 1.0, 1.0));
+        let mut w = World::new(Box::new(light));
+        //Second light is behind the blocking sphere, the first one sees the point.
+        w.add_light(Box::new(PointLight::new(
+            point!(0.0, 0.0, 20.0),
+            Color::new(1.0, 1.0, 1.0),
+        )));
+        let blocker = SphereBuilder::new()
+            .with_transformation(translation!(0.0, 0.0, 10.0))
+            .create()
+            .unwrap();
+        w.add_object(Box::new(blocker));
+        let point = point!(0.0, 0.0, 0.0);
+        let mut lights = w.lights_iter();
+        assert!(!w.is_shadowed(lights.next().unwrap(), &point));
+        assert!(w.is_shadowed(lights.next().unwrap(), &point));
+    }
+
+    #[test]
+    fn mutually_reflective_surfaces_terminate() {
+        let light = PointLight::new(point!(0.0, 0.0, 0.0), Color::new(1.0, 1.0, 1.0));
+        let mut w = World::new(Box::new(light));
+        let mirror = Material {
+            reflective: 1.0,
+            ..Default::default()
+        };
+        let lower = PlaneBuilder::new()
+            .with_material(mirror.clone())
+            .with_transformation(translation!(0.0, -1.0, 0.0))
+            .create()
+            .unwrap();
+        let upper = PlaneBuilder::new()
+            .with_material(mirror)
+            .with_transformation(translation!(0.0, 1.0, 0.0))
+            .create()
+            .unwrap();
+        w.add_object(Box::new(lower));
+        w.add_object(Box::new(upper));
+        let ray = Ray::new(point!(0.0, 0.0, 0.0), vector!(0.0, 1.0, 0.0));
+        let color = w.color_at(&ray);
+        assert!(color.r() > 0.0);
+        //Every bounce adds the light seen from the mirror once more.
+        w.set_max_depth(0);
+        let direct = w.color_at(&ray);
+        assert!(color.r() > direct.r());
+    }
+
+    #[test]
+    fn color_through_glass_sphere() {
+        let light = PointLight::new(point!(-10.0, 10.0, -10.0), Color::new(1.0, 1.0, 1.0));
+        let mut w = World::new(Box::new(light));
+        let glass = SphereBuilder::new()
+            .with_material(Material {
+                color: Color::new(0.0, 0.0, 0.0),
+                ambient: 0.0,
+                diffuse: 0.0,
+                specular: 0.0,
+                transparency: 1.0,
+                refractive_index: 1.5,
+                ..Default::default()
+            })
+            .create()
+            .unwrap();
+        let core = SphereBuilder::new()
+            .with_transformation(scaling!(0.5, 0.5, 0.5))
+            .with_material(Material {
+                ambient: 1.0,
+                ..Material::default_with_color(Color::red())
+            })
+            .create()
+            .unwrap();
+        w.add_object(Box::new(glass));
+        w.add_object(Box::new(core));
+        let ray = Ray::new(point!(0.0, 0.0, -5.0), vector!(0.0, 0.0, 1.0));
+        matrix_eq!(w.color_at(&ray).as_array(), [1.0, 0.0, 0.0], 0.0001);
+    }
 }